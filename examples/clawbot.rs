@@ -8,8 +8,15 @@
 use std::sync::Arc;
 
 use antaeus::{
-    motion::pid::{DrivetrainConfig, pid::*},
-    peripherals::{controller::*, drivetrain::Differential},
+    motion::{
+        lifecycle::LoopHandle,
+        options::{MotionDefaults, MotionOptions},
+        pid::{DrivetrainConfig, pid::*, telemetry::Telemetry},
+    },
+    peripherals::{
+        controller::*,
+        drivetrain::{Differential, DriveOwner},
+    },
 };
 use heapless::Vec;
 use vexide::{prelude::*, sync::Mutex};
@@ -37,21 +44,31 @@ impl Compete for Clawbot {
             active:       false,
             target_left:  0.0,
             target_right: 0.0,
+            feedforward:  0.0,
+            profiling:    false,
+            hold:         false,
+            motion_power: (0.0, 12.0),
         };
         let pid = PIDMovement {
             drivetrain:        self.drivetrain.clone(),
             drivetrain_config: dt_conf,
             pid_values:        Arc::new(Mutex::new(pid_values)),
+            angular_values:    Arc::new(Mutex::new(AngularPIDValues::default())),
+            lifecycle:         LoopHandle::default(),
+            telemetry:         Telemetry::default(),
+            defaults:          MotionDefaults::new(MotionOptions::default().timeout(2000)),
         };
 
         pid.init();
         pid.set_maximum_power(12.0).await;
-        pid.travel(10.0, 2000, 10).await;
-        pid.rotate(180.0, 2000, 10).await;
+        pid.travel(10.0, pid.options()).await;
+        pid.rotate(180.0, pid.options()).await;
     }
 
     async fn driver(&mut self) {
         let control = ControllerControl::new(&self.controller, ControllerButton::ButtonX);
+        // Take the drivetrain back from the autonomous PID loop
+        self.drivetrain.acquire(DriveOwner::Driver);
         loop {
             self.drivetrain.tank(&self.controller);
             control.dual_button_to_motors(
@@ -1,22 +1,12 @@
 use antaeus::motion::{pusuit::geo, *};
 
 use crate::hardware::Robot;
-pub fn main_auton(robot: &mut Robot) {
+pub async fn main_auton(robot: &mut Robot) {
     let mut path = pusuit::geo::Path::origin();
     path.add(geo::Point::new(20.0, 20.0));
     path.add(geo::Point::new(-20.0, 20.0));
     path.add(geo::Point::new(0.0, 0.0));
 
-    let arcpid_val = pid::arcpid::ArcPIDValues {
-        kp:        0.0,
-        kd:        0.0,
-        tolerance: 0.0,
-        maxpwr:    0.0,
-        active:    false,
-        target:    0.0,
-        offset:    0.0,
-    };
-
     let dtc = pid::DrivetrainConfig {
         wheel_diameter: 3.25,
         driving_gear:   3.0,
@@ -24,12 +14,6 @@ pub fn main_auton(robot: &mut Robot) {
         track_width:    13.9,
     };
 
-    let pid = pid::arcpid::ArcPIDMovement {
-        drivetrain:        robot.dt.clone(),
-        drivetrain_config: dtc,
-        arcpid_values:     std::sync::Arc::new(vexide::sync::Mutex::new(arcpid_val)),
-    };
-
     let vertical = odom::WheelTracker {
         device:         odom::TrackingDevice::RotationSensor(robot.v_tracker.clone()),
         offset:         0.0,
@@ -45,9 +29,9 @@ pub fn main_auton(robot: &mut Robot) {
     };
 
     let trackers = odom::Trackers {
-        horizontal: horizontal,
-        vertical:   vertical,
-        imu:        robot.imu.clone(),
+        horizontal,
+        vertical,
+        imu: robot.imu.clone(),
     };
 
     let odom_values = odom::OdomValues {
@@ -58,11 +42,29 @@ pub fn main_auton(robot: &mut Robot) {
 
     let odom = odom::OdomMovement {
         odometry_values: std::sync::Arc::new(vexide::sync::Mutex::new(odom_values)),
-        trackers:        trackers,
-        pid:             None,
-        arc_pid:         Some(pid),
+        trackers,
+        pid: None,
+        arc_pid: None,
+        lifecycle: lifecycle::LoopHandle::default(),
+        defaults: options::MotionDefaults::default(),
     };
 
-    let pursuit = pusuit::Pursuit { lookahead: 10.0 };
-    let _ = pursuit.follow(odom, path);
+    let velocity = velocity::VelocityDrive::new(
+        robot.dt.clone(),
+        dtc,
+        velocity::VelocityValues::new(
+            feedforward::Feedforward::new(0.8, 0.15, 0.02),
+            0.05,
+            0.0,
+            0.0,
+        ),
+    );
+
+    // Pursuit reads the pose from odometry and drives through the velocity
+    // loop, so both have to be running
+    odom.init();
+    velocity.init();
+
+    let pursuit = pusuit::Pursuit::new(10.0);
+    pursuit.follow(&odom, &velocity, path, odom.options()).await;
 }
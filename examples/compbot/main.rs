@@ -4,7 +4,7 @@ pub mod hardware;
 pub mod opcontrol;
 
 impl Compete for hardware::Robot {
-    async fn autonomous(&mut self) { auton::main_auton(self); }
+    async fn autonomous(&mut self) { auton::main_auton(self).await; }
 
     async fn driver(&mut self) { opcontrol::opcontrol(self); }
 }
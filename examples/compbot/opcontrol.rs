@@ -1,10 +1,12 @@
-use antaeus::peripherals::controller::*;
+use antaeus::peripherals::{controller::*, drivetrain::DriveOwner};
 use heapless::Vec;
 
 use crate::hardware::Robot;
 
 pub fn opcontrol(robot: &mut Robot) {
     let cc = ControllerControl::new(&robot.main_con, ControllerButton::ButtonRight);
+    // Take the drivetrain back from any autonomous controller
+    robot.dt.acquire(DriveOwner::Driver);
     loop {
         robot.dt.tank(&robot.main_con);
         cc.dual_button_to_motors(
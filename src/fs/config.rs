@@ -0,0 +1,405 @@
+//! Robot constants stored on the SD card.
+//!
+//! PID gains and drivetrain measurements normally live in code, so every
+//! retune means re-uploading. This module reads them from a plain
+//! `key = value` file instead, falling back to the compiled values for any
+//! key that is missing, and can write tuned values back.
+//!
+//! # File Format
+//!
+//! One value per line, with `#` comments. Keys are a prefix chosen by the
+//! program followed by the field name:
+//!
+//! ```text
+//! # antaeus config
+//! drivetrain.wheel_diameter = 3.25
+//! drivetrain.track_width = 12.5
+//! pid.kp = 0.12
+//! pid.kd = 0.01
+//! arm.kp = 0.8
+//! arm.gear_ratio = 5
+//! ```
+//!
+//! # Example
+//!
+//! ```ignore
+//! use antaeus::fs::config::{CONFIG_PATH, ConfigFile};
+//!
+//! let mut config = ConfigFile::load_or_default(CONFIG_PATH);
+//! config.apply("drivetrain", &mut dt_config)?;
+//! config.apply("pid", &mut *pid.pid_values.lock().await)?;
+//!
+//! // After tuning at runtime
+//! config.store("pid", &*pid.pid_values.lock().await);
+//! config.save(CONFIG_PATH)?;
+//! ```
+
+use std::collections::BTreeMap;
+
+use log::warn;
+
+use crate::motion::pid::{
+    DrivetrainConfig,
+    arcpid::ArcPIDValues,
+    pid::{AngularPIDValues, PIDValues},
+    singlepid::SinglePIDValues,
+};
+
+/// Default location of the config file on the SD card.
+pub const CONFIG_PATH: &str = "antaeus.cfg";
+
+/// Header written at the top of saved config files.
+const HEADER: &str = "# antaeus config";
+
+/// Errors from reading, parsing or applying a config file.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read or written.
+    Io(std::io::Error),
+    /// A line could not be parsed.
+    Parse {
+        /// The 1-based line number.
+        line:   usize,
+        /// What was wrong with the line.
+        reason: &'static str,
+    },
+    /// A value was parsed but is out of range for its field.
+    Invalid {
+        /// The full key of the value.
+        key:    String,
+        /// Why the value was rejected.
+        reason: &'static str,
+    },
+}
+
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "config file error: {}", e),
+            ConfigError::Parse { line, reason } => write!(f, "line {}: {}", line, reason),
+            ConfigError::Invalid { key, reason } => write!(f, "{}: {}", key, reason),
+        }
+    }
+}
+
+impl core::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(error: std::io::Error) -> Self { ConfigError::Io(error) }
+}
+
+/// Parsed contents of a config file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigFile {
+    values: BTreeMap<String, f64>,
+}
+
+impl ConfigFile {
+    /// Parses config text.
+    pub fn parse(text: &str) -> Result<ConfigFile, ConfigError> {
+        let mut values = BTreeMap::new();
+        for (index, raw) in text.lines().enumerate() {
+            let line = index + 1;
+            let content = raw.split('#').next().unwrap_or_default().trim();
+            if content.is_empty() {
+                continue;
+            }
+            let (key, value) = content.split_once('=').ok_or(ConfigError::Parse {
+                line,
+                reason: "expected `key = value`",
+            })?;
+            let key = key.trim();
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return Err(ConfigError::Parse {
+                    line,
+                    reason: "invalid key",
+                });
+            }
+            let value: f64 = value.trim().parse().map_err(|_| ConfigError::Parse {
+                line,
+                reason: "value is not a number",
+            })?;
+            if !value.is_finite() {
+                return Err(ConfigError::Parse {
+                    line,
+                    reason: "value is not finite",
+                });
+            }
+            if values.insert(key.to_string(), value).is_some() {
+                return Err(ConfigError::Parse {
+                    line,
+                    reason: "duplicate key",
+                });
+            }
+        }
+        Ok(ConfigFile { values })
+    }
+
+    /// Reads and parses a config file.
+    pub fn load(path: &str) -> Result<ConfigFile, ConfigError> {
+        let text = std::fs::read_to_string(path)?;
+        ConfigFile::parse(&text)
+    }
+
+    /// Reads a config file, returning an empty config if it is missing or
+    /// invalid so the compiled values are used.
+    pub fn load_or_default(path: &str) -> ConfigFile {
+        ConfigFile::load(path).unwrap_or_else(|e| {
+            warn!("Using compiled defaults, could not load {}: {}", path, e);
+            ConfigFile::default()
+        })
+    }
+
+    /// Writes the config to a file, replacing it.
+    pub fn save(&self, path: &str) -> Result<(), ConfigError> {
+        std::fs::write(path, self.serialize())?;
+        Ok(())
+    }
+
+    /// Formats the config as file text.
+    pub fn serialize(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        for (key, value) in &self.values {
+            text.push_str(&format!("{} = {}\n", key, value));
+        }
+        text
+    }
+
+    /// Returns the value of a key.
+    pub fn get(&self, key: &str) -> Option<f64> { self.values.get(key).copied() }
+
+    /// Sets the value of a key.
+    pub fn set(&mut self, key: &str, value: f64) { self.values.insert(key.to_string(), value); }
+
+    /// Overwrites the fields of `target` with any values stored under
+    /// `prefix`.
+    ///
+    /// Missing keys keep their current value. If any stored value is out of
+    /// range, nothing is changed and the error is returned.
+    pub fn apply<T: Configurable>(&self, prefix: &str, target: &mut T) -> Result<(), ConfigError> {
+        let mut updates = Vec::new();
+        for (field, _) in target.fields() {
+            let key = format!("{}.{}", prefix, field);
+            if let Some(value) = self.get(&key) {
+                validate(field, value).map_err(|reason| ConfigError::Invalid { key, reason })?;
+                updates.push((field, value));
+            }
+        }
+        for (field, slot) in target.fields_mut() {
+            if let Some((_, value)) = updates.iter().find(|(f, _)| *f == field) {
+                *slot = *value;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stores the fields of `source` under `prefix`.
+    pub fn store<T: Configurable>(&mut self, prefix: &str, source: &T) {
+        for (field, value) in source.fields() {
+            self.set(&format!("{}.{}", prefix, field), value);
+        }
+    }
+}
+
+/// Checks a value against the range allowed for its field.
+fn validate(field: &str, value: f64) -> Result<(), &'static str> {
+    match field {
+        "kp" | "ki" | "kd" | "limit_margin" if value < 0.0 => Err("must not be negative"),
+        "kp" | "ki" | "kd" | "limit_margin" => Ok(()),
+        "maxpwr" if value <= 0.0 || value > 12.0 => Err("must be above 0 and at most 12"),
+        "velocity_filter" if !(0.0..=1.0).contains(&value) => Err("must be between 0 and 1"),
+        "maxpwr" | "velocity_filter" => Ok(()),
+        _ if value <= 0.0 => Err("must be positive"),
+        _ => Ok(()),
+    }
+}
+
+/// A set of constants that can be loaded from and saved to a [`ConfigFile`].
+pub trait Configurable {
+    /// Returns each stored field name with its current value.
+    fn fields(&self) -> Vec<(&'static str, f64)>;
+
+    /// Returns each stored field name with a mutable reference to it.
+    fn fields_mut(&mut self) -> Vec<(&'static str, &mut f64)>;
+}
+
+impl Configurable for PIDValues {
+    fn fields(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("kp", self.kp),
+            ("ki", self.ki),
+            ("kd", self.kd),
+            ("tolerance", self.tolerance),
+            ("maxpwr", self.maxpwr),
+        ]
+    }
+
+    fn fields_mut(&mut self) -> Vec<(&'static str, &mut f64)> {
+        vec![
+            ("kp", &mut self.kp),
+            ("ki", &mut self.ki),
+            ("kd", &mut self.kd),
+            ("tolerance", &mut self.tolerance),
+            ("maxpwr", &mut self.maxpwr),
+        ]
+    }
+}
+
+impl Configurable for AngularPIDValues {
+    fn fields(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("kp", self.kp),
+            ("ki", self.ki),
+            ("kd", self.kd),
+            ("tolerance", self.tolerance),
+            ("maxpwr", self.maxpwr),
+        ]
+    }
+
+    fn fields_mut(&mut self) -> Vec<(&'static str, &mut f64)> {
+        vec![
+            ("kp", &mut self.kp),
+            ("ki", &mut self.ki),
+            ("kd", &mut self.kd),
+            ("tolerance", &mut self.tolerance),
+            ("maxpwr", &mut self.maxpwr),
+        ]
+    }
+}
+
+impl Configurable for ArcPIDValues {
+    fn fields(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("kp", self.kp),
+            ("kd", self.kd),
+            ("tolerance", self.tolerance),
+            ("maxpwr", self.maxpwr),
+        ]
+    }
+
+    fn fields_mut(&mut self) -> Vec<(&'static str, &mut f64)> {
+        vec![
+            ("kp", &mut self.kp),
+            ("kd", &mut self.kd),
+            ("tolerance", &mut self.tolerance),
+            ("maxpwr", &mut self.maxpwr),
+        ]
+    }
+}
+
+impl Configurable for SinglePIDValues {
+    fn fields(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("kp", self.kp),
+            ("ki", self.ki),
+            ("kd", self.kd),
+            ("tolerance", self.tolerance),
+            ("maxpwr", self.maxpwr),
+            ("gear_ratio", self.gear_ratio),
+            ("limit_margin", self.limit_margin),
+            ("velocity_tolerance", self.velocity_tolerance),
+            ("velocity_filter", self.velocity_filter),
+        ]
+    }
+
+    fn fields_mut(&mut self) -> Vec<(&'static str, &mut f64)> {
+        vec![
+            ("kp", &mut self.kp),
+            ("ki", &mut self.ki),
+            ("kd", &mut self.kd),
+            ("tolerance", &mut self.tolerance),
+            ("maxpwr", &mut self.maxpwr),
+            ("gear_ratio", &mut self.gear_ratio),
+            ("limit_margin", &mut self.limit_margin),
+            ("velocity_tolerance", &mut self.velocity_tolerance),
+            ("velocity_filter", &mut self.velocity_filter),
+        ]
+    }
+}
+
+impl Configurable for DrivetrainConfig {
+    fn fields(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("wheel_diameter", self.wheel_diameter),
+            ("driving_gear", self.driving_gear),
+            ("driven_gear", self.driven_gear),
+            ("track_width", self.track_width),
+        ]
+    }
+
+    fn fields_mut(&mut self) -> Vec<(&'static str, &mut f64)> {
+        vec![
+            ("wheel_diameter", &mut self.wheel_diameter),
+            ("driving_gear", &mut self.driving_gear),
+            ("driven_gear", &mut self.driven_gear),
+            ("track_width", &mut self.track_width),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let config = ConfigFile::parse(
+            "# antaeus config\n\npid.kp = 0.12   # tuned\n  drivetrain.track_width=12.5\n",
+        )
+        .unwrap();
+        assert_eq!(config.get("pid.kp"), Some(0.12));
+        assert_eq!(config.get("drivetrain.track_width"), Some(12.5));
+        assert_eq!(config.get("pid.kd"), None);
+    }
+
+    #[test]
+    fn parse_error_test() {
+        let line = |text: &str| match ConfigFile::parse(text) {
+            Err(ConfigError::Parse { line, .. }) => line,
+            other => panic!("expected parse error, got {:?}", other),
+        };
+        assert_eq!(line("pid.kp = 1\npid.kd 0.1"), 2);
+        assert_eq!(line("pid.kp = fast"), 1);
+        assert_eq!(line("pid.kp = 1\n\npid.kp = 2"), 3);
+        assert_eq!(line(" = 1"), 1);
+        assert_eq!(line("pid.kp = inf"), 1);
+    }
+
+    #[test]
+    fn apply_fallback_test() {
+        let config = ConfigFile::parse("pid.kp = 0.3\npid.maxpwr = 10").unwrap();
+        let mut values = PIDValues::new(0.1, 0.0, 0.05, 0.5, 12.0);
+        config.apply("pid", &mut values).unwrap();
+        assert_eq!(values.kp, 0.3);
+        assert_eq!(values.maxpwr, 10.0);
+        // Missing keys keep the compiled value
+        assert_eq!(values.kd, 0.05);
+    }
+
+    #[test]
+    fn apply_invalid_test() {
+        let config = ConfigFile::parse("arm.kp = 2\narm.maxpwr = 20").unwrap();
+        let mut values = SinglePIDValues::new(0.5, 0.0, 0.0, 0.1, 12.0);
+        match config.apply("arm", &mut values) {
+            Err(ConfigError::Invalid { key, .. }) => assert_eq!(key, "arm.maxpwr"),
+            other => panic!("expected invalid value, got {:?}", other),
+        }
+        // Nothing is applied when any value is rejected
+        assert_eq!(values.kp, 0.5);
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let mut config = ConfigFile::default();
+        let dt = DrivetrainConfig::new(3.25, 36.0, 48.0, 12.5);
+        config.store("drivetrain", &dt);
+        let parsed = ConfigFile::parse(&config.serialize()).unwrap();
+        assert_eq!(parsed, config);
+
+        let mut loaded = DrivetrainConfig::new(4.0, 1.0, 1.0, 10.0);
+        parsed.apply("drivetrain", &mut loaded).unwrap();
+        assert_eq!(loaded.wheel_diameter, 3.25);
+        assert_eq!(loaded.driven_gear, 48.0);
+        assert_eq!(loaded.track_width, 12.5);
+    }
+}
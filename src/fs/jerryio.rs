@@ -0,0 +1,290 @@
+//! Paths exported from [PATH.JERRY.IO](https://path.jerryio.com).
+//!
+//! path.jerryio's LemLib format writes one `x, y, speed` line per point,
+//! then an `endData` line followed by settings, control points and a
+//! `#PATH.JERRYIO-DATA` line used by the editor. Only the points are read;
+//! everything after `endData` is ignored.
+//!
+//! # Frames
+//!
+//! path.jerryio measures from the center of the field, with x to the right
+//! and y away from the driver station. Antaeus measures from wherever
+//! odometry was started, with y pointing the way the robot faced. The
+//! points are shifted to that origin and turned by the robot's starting
+//! heading, both given by [`JerryOrigin`]. Speeds are exported as motor voltage out of 127 and
+//! are scaled to inches per second with [`JerryImport::max_speed`], and
+//! cap the velocity when the path is built with
+//! [`AdaptivePath::from_waypoints`](crate::motion::pusuit::adaptive::AdaptivePath::from_waypoints).
+//!
+//! # Example
+//!
+//! ```ignore
+//! use antaeus::{
+//!     fs::jerryio::JerryImport,
+//!     motion::pusuit::adaptive::{AdaptiveConfig, AdaptivePath},
+//! };
+//!
+//! // The robot starts on the first point of the path, facing up the field
+//! let file = JerryImport::default().load("skills.txt")?;
+//! // Keeps the exported speeds, which `file.to_path()` would drop
+//! let path = AdaptivePath::from_waypoints(&file.waypoints, &AdaptiveConfig::default());
+//! pursuit.follow_adaptive(&odom, &velocity, &path, odom.options()).await;
+//! ```
+
+use super::path::{PathError, PathFile};
+use crate::motion::pusuit::geo::{Point, Waypoint};
+
+/// Line that ends the points in an export.
+const END_DATA: &str = "endData";
+
+/// Speed path.jerryio exports for full power.
+const FULL_SPEED: f64 = 127.0;
+
+/// Where odometry's origin is in path.jerryio's field coordinates.
+///
+/// Headings are the way the robot faces when odometry starts, in degrees
+/// clockwise from path.jerryio's y axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JerryOrigin {
+    /// The robot starts on the first point of the path.
+    Start {
+        /// The robot's starting heading on the field.
+        heading: f64,
+    },
+    /// Odometry measures from the center of the field, facing along the
+    /// field's y axis.
+    FieldCenter,
+    /// Odometry measures from this point, in exported units.
+    At {
+        /// Where the robot starts on the field.
+        point:   Point,
+        /// The robot's starting heading on the field.
+        heading: f64,
+    },
+}
+
+impl Default for JerryOrigin {
+    fn default() -> Self { JerryOrigin::Start { heading: 0.0 } }
+}
+
+/// Settings for importing a path.jerryio export.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JerryImport {
+    /// Where odometry's origin is on the field.
+    pub origin:    JerryOrigin,
+    /// Inches per exported unit. LemLib exports are in inches.
+    pub unit:      f64,
+    /// Speed in inches per second of an exported speed of 127.
+    pub max_speed: f64,
+}
+
+impl Default for JerryImport {
+    fn default() -> Self {
+        JerryImport {
+            origin:    JerryOrigin::default(),
+            unit:      1.0,
+            max_speed: 48.0,
+        }
+    }
+}
+
+impl JerryImport {
+    /// Parses an export, such as one embedded with `include_str!`.
+    ///
+    /// A point with a speed of zero has no speed. A point that repeats the
+    /// one before it, as exports do at the end of a path, is dropped.
+    pub fn parse(&self, text: &str) -> Result<PathFile, PathError> {
+        let mut waypoints: Vec<Waypoint> = Vec::new();
+        for (index, raw) in text.lines().enumerate() {
+            let line = index + 1;
+            let content = raw.trim();
+            if content == END_DATA || content.starts_with("#PATH.JERRYIO-DATA") {
+                break;
+            }
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+            let (point, speed) =
+                parse_point(content).map_err(|reason| PathError::Parse { line, reason })?;
+            if waypoints.last().is_some_and(|last| last.point == point) {
+                continue;
+            }
+            waypoints.push(Waypoint {
+                point,
+                speed: (speed > 0.0).then_some(speed / FULL_SPEED * self.max_speed),
+                heading: None,
+                lookahead: None,
+            });
+        }
+
+        if waypoints.is_empty() {
+            return Err(PathError::Empty);
+        }
+        let (origin, heading) = match self.origin {
+            JerryOrigin::Start { heading } => (waypoints[0].point, heading),
+            JerryOrigin::FieldCenter => (Point::new(0.0, 0.0), 0.0),
+            JerryOrigin::At { point, heading } => (point, heading),
+        };
+        // Odometry's y axis points along the starting heading and its x axis
+        // to the robot's right
+        let (sin, cos) = heading.to_radians().sin_cos();
+        for waypoint in &mut waypoints {
+            let dx = waypoint.point.x - origin.x;
+            let dy = waypoint.point.y - origin.y;
+            waypoint.point =
+                Point::new((dx * cos - dy * sin) * self.unit, (dx * sin + dy * cos) * self.unit);
+        }
+        Ok(PathFile::new(waypoints))
+    }
+
+    /// Reads and parses an export.
+    pub fn load(&self, path: &str) -> Result<PathFile, PathError> {
+        let text = std::fs::read_to_string(path)?;
+        self.parse(&text)
+    }
+}
+
+/// Parses an `x, y, speed` line.
+fn parse_point(content: &str) -> Result<(Point, f64), &'static str> {
+    let columns: Vec<&str> = content.split(',').map(str::trim).collect();
+    if columns.len() != 3 {
+        return Err("expected `x, y, speed`");
+    }
+    let mut values = [0.0_f64; 3];
+    for (value, column) in values.iter_mut().zip(&columns) {
+        *value = column.parse().map_err(|_| "value is not a number")?;
+        if !value.is_finite() {
+            return Err("value is not finite");
+        }
+    }
+    if values[2] < 0.0 {
+        return Err("speed must not be negative");
+    }
+    Ok((Point::new(values[0], values[1]), values[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRAIGHT: &str = include_str!("../../assets/paths/jerryio-straight.txt");
+    const S_CURVE: &str = include_str!("../../assets/paths/jerryio-s-curve.txt");
+
+    fn close(a: f64, b: f64) -> bool { (a - b).abs() < 1e-9 }
+
+    #[test]
+    fn straight_test() {
+        let file = JerryImport::default().parse(STRAIGHT).unwrap();
+        assert_eq!(file.waypoints.len(), 13);
+        // Shifted so the path starts at the origin and drives forward
+        assert_eq!(file.waypoints[0].point, Point::new(0.0, 0.0));
+        assert_eq!(file.waypoints[12].point, Point::new(0.0, 24.0));
+        assert!(close(file.waypoints[0].speed.unwrap(), 48.0));
+        assert!(close(file.waypoints[10].speed.unwrap(), 24.0));
+        assert_eq!(file.waypoints[12].speed, None);
+    }
+
+    #[test]
+    fn s_curve_test() {
+        let import = JerryImport {
+            origin:    JerryOrigin::FieldCenter,
+            unit:      1.0,
+            max_speed: 127.0,
+        };
+        let file = import.parse(S_CURVE).unwrap();
+        // The repeated end point is dropped
+        assert_eq!(file.waypoints.len(), 11);
+        assert_eq!(file.waypoints[5].point, Point::new(24.0, 14.0));
+        assert_eq!(file.waypoints[10].point, Point::new(48.0, 28.0));
+        assert_eq!(file.waypoints[0].speed, Some(100.0));
+        assert_eq!(file.waypoints[10].speed, Some(40.0));
+        assert_eq!(file.to_path().waypoints.len(), 11);
+    }
+
+    #[test]
+    fn origin_unit_test() {
+        let import = JerryImport {
+            origin:    JerryOrigin::At {
+                point:   Point::new(-48.0, -48.0),
+                heading: 0.0,
+            },
+            unit:      1.0 / 2.54,
+            max_speed: 48.0,
+        };
+        let file = import
+            .parse("-48, -48, 127\n-22.6, -48, 0\nendData\n")
+            .unwrap();
+        assert_eq!(file.waypoints[0].point, Point::new(0.0, 0.0));
+        assert!(close(file.waypoints[1].point.x, 10.0));
+        assert!(close(file.waypoints[1].point.y, 0.0));
+    }
+
+    #[test]
+    fn heading_test() {
+        let turned = |heading: f64| {
+            let import = JerryImport {
+                origin: JerryOrigin::Start { heading },
+                ..JerryImport::default()
+            };
+            let file = import.parse(STRAIGHT).unwrap();
+            file.waypoints[12].point
+        };
+        // The path drives up the field, so a robot facing right has it on
+        // its left, and one facing down has it behind
+        let left = turned(90.0);
+        assert!(close(left.x, -24.0) && close(left.y, 0.0));
+        let right = turned(-90.0);
+        assert!(close(right.x, 24.0) && close(right.y, 0.0));
+        let behind = turned(180.0);
+        assert!(close(behind.x, 0.0) && close(behind.y, -24.0));
+
+        // Turning happens about the given start point
+        let import = JerryImport {
+            origin: JerryOrigin::At {
+                point:   Point::new(-48.0, -48.0),
+                heading: 45.0,
+            },
+            ..JerryImport::default()
+        };
+        let file = import.parse("-48, -48, 127\n-36, -36, 0\n").unwrap();
+        assert!(close(file.waypoints[0].point.x, 0.0));
+        assert!(close(file.waypoints[1].point.x, 0.0));
+        assert!(close(file.waypoints[1].point.y, 12.0 * 2.0_f64.sqrt()));
+    }
+
+    #[test]
+    fn trailer_test() {
+        // The editor data line ends the points even without `endData`
+        let (points, rest) = STRAIGHT.split_once("endData").unwrap();
+        let trailer = rest
+            .lines()
+            .find(|line| line.starts_with("#PATH.JERRYIO-DATA"))
+            .unwrap();
+        let text = format!("{}{}\n", points, trailer);
+        let file = JerryImport::default().parse(&text).unwrap();
+        assert_eq!(file, JerryImport::default().parse(STRAIGHT).unwrap());
+        // Control points after the data line are not points
+        let text = format!("{}{}\n1, 2, 3\n", points, trailer);
+        assert_eq!(JerryImport::default().parse(&text).unwrap().waypoints.len(), 13);
+    }
+
+    #[test]
+    fn error_test() {
+        let import = JerryImport::default();
+        assert!(matches!(import.parse("endData\n200\n"), Err(PathError::Empty)));
+        assert!(matches!(
+            import.parse("0, 0, 127\n1, 2\n"),
+            Err(PathError::Parse { line: 2, .. })
+        ));
+        assert!(matches!(
+            import.parse("0, zero, 127\n"),
+            Err(PathError::Parse { line: 1, .. })
+        ));
+        assert!(matches!(
+            import.parse("0, 0, -5\n"),
+            Err(PathError::Parse { line: 1, .. })
+        ));
+        // Control points after `endData` are not points
+        assert!(import.parse("0, 0, 127\nendData\n1, 2\n").is_ok());
+    }
+}
@@ -10,6 +10,18 @@
 //! `log.txt` on the SD card. This is useful for debugging issues that
 //! only occur on the robot.
 //!
+//! # Configuration
+//!
+//! The `config` submodule loads PID gains and drivetrain constants from a
+//! `key = value` file on the SD card, so they can be retuned without
+//! re-uploading.
+//!
+//! # Paths
+//!
+//! The `path` submodule loads and saves pursuit paths as a versioned CSV
+//! file, so paths can be edited without recompiling. The `jerryio`
+//! submodule imports paths drawn in PATH.JERRY.IO.
+//!
 //! # Example
 //!
 //! ```ignore
@@ -23,6 +35,23 @@
 //! info!("Robot initialized successfully");
 //! ```
 
+/// Robot constants stored on the SD card.
+///
+/// Loads and saves PID gains and drivetrain measurements as a simple
+/// `key = value` file, falling back to the compiled values.
+pub mod config;
+
+/// Pursuit paths stored on the SD card.
+///
+/// Reads and writes waypoints with optional speeds and headings as a
+/// versioned CSV file.
+pub mod path;
+
+/// Paths exported from PATH.JERRY.IO.
+///
+/// Converts LemLib-format exports into waypoints in Antaeus' frame.
+pub mod jerryio;
+
 /// File-based logging for the V5 Brain.
 ///
 /// Provides a logger implementation that writes to both the console
@@ -0,0 +1,343 @@
+//! Paths stored on the SD card.
+//!
+//! Building a [`Path`] in code means re-uploading the program for every
+//! tweak. This module reads waypoints from a small CSV file instead, which
+//! can be edited by hand or written by the robot.
+//!
+//! # File Format
+//!
+//! The first line is a version header. Every other line is either blank,
+//! a `#` comment, or a waypoint with comma-separated columns:
+//!
+//! ```text
+//! # antaeus path v2
+//! # x, y, speed, heading, lookahead
+//! 0, 0
+//! 24, 0, 30
+//! 24, 24, , 90   # turn to face right
+//! 48, 24, , , 6  # follow the next part closely
+//! ```
+//!
+//! - `x` and `y` are the position in inches and are required.
+//! - `speed` is the fastest the robot drives from the waypoint to the next
+//!   in inches per second and is optional. It must be positive.
+//! - `heading` is the heading at the waypoint in degrees and is optional.
+//!   It is kept for the program to read but is not used for following.
+//! - `lookahead` overrides the pursuit lookahead distance in inches until
+//!   the next waypoint and is optional. It must be positive.
+//!
+//! Optional columns can be left empty or left out at the end of the line.
+//!
+//! Version 1 files have no `lookahead` column. They are still read, and are
+//! written back as version 2.
+//!
+//! # Example
+//!
+//! Build the path with
+//! [`AdaptivePath::from_waypoints`](crate::motion::pusuit::adaptive::AdaptivePath::from_waypoints)
+//! so the robot follows the speed and lookahead columns.
+//!
+//! ```ignore
+//! use antaeus::{
+//!     fs::path::PathFile,
+//!     motion::pusuit::adaptive::{AdaptiveConfig, AdaptivePath},
+//! };
+//!
+//! let file = PathFile::load("skills.path")?;
+//! let path = AdaptivePath::from_waypoints(&file.waypoints, &AdaptiveConfig::default());
+//! pursuit.follow_adaptive(&odom, &velocity, &path, odom.options()).await;
+//! ```
+
+use crate::motion::pusuit::geo::{Path, Point, Waypoint};
+
+/// Version of the format written by [`PathFile::serialize`].
+pub const VERSION: u32 = 2;
+
+/// Oldest version of the format that can still be read.
+const OLDEST_VERSION: u32 = 1;
+
+/// Start of the version header, followed by the version number.
+const HEADER: &str = "# antaeus path v";
+
+/// Errors from reading, parsing or writing a path file.
+#[derive(Debug)]
+pub enum PathError {
+    /// The file could not be read or written.
+    Io(std::io::Error),
+    /// The first line is not a path file header.
+    MissingHeader,
+    /// The file was written in a version of the format this one can't read.
+    UnsupportedVersion(u32),
+    /// A line could not be parsed.
+    Parse {
+        /// The 1-based line number.
+        line:   usize,
+        /// What was wrong with the line.
+        reason: &'static str,
+    },
+    /// The file has no waypoints.
+    Empty,
+}
+
+impl core::fmt::Display for PathError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PathError::Io(e) => write!(f, "path file error: {}", e),
+            PathError::MissingHeader => write!(f, "missing `{}{}` header", HEADER, VERSION),
+            PathError::UnsupportedVersion(v) => {
+                write!(f, "unsupported path file version {} (expected {})", v, VERSION)
+            }
+            PathError::Parse { line, reason } => write!(f, "line {}: {}", line, reason),
+            PathError::Empty => write!(f, "path file has no waypoints"),
+        }
+    }
+}
+
+impl core::error::Error for PathError {}
+
+impl From<std::io::Error> for PathError {
+    fn from(error: std::io::Error) -> Self { PathError::Io(error) }
+}
+
+/// Parsed contents of a path file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PathFile {
+    /// The waypoints in the order they are driven.
+    pub waypoints: Vec<Waypoint>,
+}
+
+impl PathFile {
+    /// Creates a path file from waypoints.
+    pub fn new(waypoints: Vec<Waypoint>) -> PathFile { PathFile { waypoints } }
+
+    /// Creates a path file from the points of a path, with no speeds or
+    /// headings.
+    pub fn from_path(path: &Path) -> PathFile {
+        PathFile::new(path.waypoints.iter().copied().map(Waypoint::new).collect())
+    }
+
+    /// Returns the waypoints' positions as a path.
+    ///
+    /// The speeds, headings and lookaheads are dropped. Use
+    /// [`AdaptivePath::from_waypoints`](crate::motion::pusuit::adaptive::AdaptivePath::from_waypoints)
+    /// with [`waypoints`](PathFile::waypoints) to follow them.
+    pub fn to_path(&self) -> Path {
+        Path::from_vec(self.waypoints.iter().map(|w| w.point).collect())
+    }
+
+    /// Parses path file text.
+    pub fn parse(text: &str) -> Result<PathFile, PathError> {
+        let mut lines = text.lines().enumerate();
+        let header = lines.next().map(|(_, l)| l.trim()).unwrap_or_default();
+        let version = header
+            .strip_prefix(HEADER)
+            .ok_or(PathError::MissingHeader)?
+            .parse::<u32>()
+            .map_err(|_| PathError::MissingHeader)?;
+        if !(OLDEST_VERSION..=VERSION).contains(&version) {
+            return Err(PathError::UnsupportedVersion(version));
+        }
+
+        let mut waypoints = Vec::new();
+        for (index, raw) in lines {
+            let line = index + 1;
+            let content = raw.split('#').next().unwrap_or_default().trim();
+            if content.is_empty() {
+                continue;
+            }
+            waypoints.push(
+                parse_waypoint(content, version)
+                    .map_err(|reason| PathError::Parse { line, reason })?,
+            );
+        }
+        if waypoints.is_empty() {
+            return Err(PathError::Empty);
+        }
+        Ok(PathFile { waypoints })
+    }
+
+    /// Reads and parses a path file.
+    pub fn load(path: &str) -> Result<PathFile, PathError> {
+        let text = std::fs::read_to_string(path)?;
+        PathFile::parse(&text)
+    }
+
+    /// Writes the path to a file, replacing it.
+    pub fn save(&self, path: &str) -> Result<(), PathError> {
+        std::fs::write(path, self.serialize())?;
+        Ok(())
+    }
+
+    /// Formats the path as file text.
+    pub fn serialize(&self) -> String {
+        let mut text = format!("{}{}\n# x, y, speed, heading, lookahead\n", HEADER, VERSION);
+        for w in &self.waypoints {
+            let optional = |v: Option<f64>| v.map_or(String::new(), |v| v.to_string());
+            let mut line = format!(
+                "{}, {}, {}, {}, {}",
+                w.point.x,
+                w.point.y,
+                optional(w.speed),
+                optional(w.heading),
+                optional(w.lookahead)
+            );
+            // Leave out empty columns at the end
+            while line.ends_with(", ") {
+                line.truncate(line.len() - 2);
+            }
+            text.push_str(&line);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Parses the columns of a waypoint line in a file of the given version.
+fn parse_waypoint(content: &str, version: u32) -> Result<Waypoint, &'static str> {
+    let columns: Vec<&str> = content.split(',').map(str::trim).collect();
+    if columns.len() < 2 {
+        return Err("expected at least `x, y`");
+    }
+    if version == 1 && columns.len() > 4 {
+        return Err("too many columns, expected `x, y, speed, heading` in version 1");
+    }
+    if columns.len() > 5 {
+        return Err("too many columns, expected `x, y, speed, heading, lookahead`");
+    }
+    let number = |column: &str| -> Result<f64, &'static str> {
+        let value: f64 = column.parse().map_err(|_| "value is not a number")?;
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err("value is not finite")
+        }
+    };
+    let optional = |index: usize| -> Result<Option<f64>, &'static str> {
+        match columns.get(index) {
+            Some(column) if !column.is_empty() => number(column).map(Some),
+            _ => Ok(None),
+        }
+    };
+    if columns[0].is_empty() || columns[1].is_empty() {
+        return Err("x and y are required");
+    }
+    let speed = optional(2)?;
+    if speed.is_some_and(|s| s <= 0.0) {
+        return Err("speed must be positive");
+    }
+    let lookahead = optional(4)?;
+    if lookahead.is_some_and(|l| l <= 0.0) {
+        return Err("lookahead must be positive");
+    }
+    Ok(Waypoint {
+        point: Point::new(number(columns[0])?, number(columns[1])?),
+        speed,
+        heading: optional(3)?,
+        lookahead,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let file = PathFile::parse(
+            "# antaeus path v2\n# x, y, speed, heading\n\n0, 0\n 24,0,30 \n24, 24, , 90  # \
+             corner\n",
+        )
+        .unwrap();
+        assert_eq!(
+            file.waypoints,
+            vec![
+                Waypoint::new(Point::new(0.0, 0.0)),
+                Waypoint {
+                    point:     Point::new(24.0, 0.0),
+                    speed:     Some(30.0),
+                    heading:   None,
+                    lookahead: None,
+                },
+                Waypoint {
+                    point:     Point::new(24.0, 24.0),
+                    speed:     None,
+                    heading:   Some(90.0),
+                    lookahead: None,
+                },
+            ]
+        );
+        assert_eq!(file.to_path().waypoints.len(), 3);
+    }
+
+    #[test]
+    fn header_error_test() {
+        assert!(matches!(PathFile::parse("0, 0"), Err(PathError::MissingHeader)));
+        assert!(matches!(PathFile::parse(""), Err(PathError::MissingHeader)));
+        assert!(matches!(
+            PathFile::parse("# antaeus path vX\n0, 0"),
+            Err(PathError::MissingHeader)
+        ));
+        assert!(matches!(
+            PathFile::parse("# antaeus path v3\n0, 0"),
+            Err(PathError::UnsupportedVersion(3))
+        ));
+        assert!(matches!(
+            PathFile::parse("# antaeus path v0\n0, 0"),
+            Err(PathError::UnsupportedVersion(0))
+        ));
+        assert!(matches!(
+            PathFile::parse("# antaeus path v1\n# nothing here\n"),
+            Err(PathError::Empty)
+        ));
+    }
+
+    #[test]
+    fn parse_error_test() {
+        let line = |text: &str| match PathFile::parse(text) {
+            Err(PathError::Parse { line, .. }) => line,
+            other => panic!("expected parse error, got {:?}", other),
+        };
+        assert_eq!(line("# antaeus path v2\n0, 0\n12"), 3);
+        assert_eq!(line("# antaeus path v1\n0, north"), 2);
+        assert_eq!(line("# antaeus path v2\n0, 0, 10, 90, 5, 1"), 2);
+        assert_eq!(line("# antaeus path v2\n0, 0, 10, 90, 0"), 2);
+        // Version 1 has no lookahead column
+        assert_eq!(line("# antaeus path v1\n0, 0, 10, 90, 5"), 2);
+        assert_eq!(line("# antaeus path v1\n0, 0, -10"), 2);
+        assert_eq!(line("# antaeus path v1\n, 0"), 2);
+        assert_eq!(line("# antaeus path v1\n0, inf"), 2);
+    }
+
+    #[test]
+    fn version_1_test() {
+        let file = PathFile::parse("# antaeus path v1\n0, 0\n24, 0, 30, 90\n").unwrap();
+        assert_eq!(file.waypoints[1].speed, Some(30.0));
+        assert_eq!(file.waypoints[1].heading, Some(90.0));
+        assert_eq!(file.waypoints[1].lookahead, None);
+        // Written back in the current version
+        assert!(file.serialize().starts_with("# antaeus path v2\n"));
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let file = PathFile::new(vec![
+            Waypoint::new(Point::new(0.0, 0.0)),
+            Waypoint {
+                point:     Point::new(-12.5, 36.0),
+                speed:     Some(24.0),
+                heading:   Some(-45.0),
+                lookahead: None,
+            },
+            Waypoint {
+                point:     Point::new(1.0, 2.0),
+                speed:     None,
+                heading:   Some(180.0),
+                lookahead: Some(8.0),
+            },
+        ]);
+        let text = file.serialize();
+        assert!(text.contains("\n0, 0\n"));
+        assert!(text.contains("\n1, 2, , 180, 8\n"));
+        assert_eq!(PathFile::parse(&text).unwrap(), file);
+    }
+}
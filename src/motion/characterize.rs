@@ -0,0 +1,409 @@
+//! Drivetrain feedforward characterization.
+//!
+//! This module measures the `kS`, `kV` and `kA` gains of a differential
+//! drivetrain, similar to WPILib's SysId. It runs four tests on the robot:
+//!
+//! - **Quasistatic forward/reverse**: The voltage ramps up slowly, so
+//!   acceleration is negligible and the data mostly shows `kS` and `kV`.
+//! - **Dynamic forward/reverse**: A constant voltage step is applied from
+//!   rest, so the robot accelerates hard and the data shows `kA`.
+//!
+//! Every sample (voltage, position and velocity) is recorded at the loop rate,
+//! optionally written to a CSV file on the SD card, and fitted to the model
+//! `V = kS·sign(v) + kV·v + kA·a` by least squares.
+//!
+//! # Space Requirements
+//!
+//! The robot drives in a straight line during each test. Make sure there is
+//! enough room in front of and behind the robot. Tests run forward then in
+//! reverse, so the robot ends up close to where it started.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use antaeus::motion::characterize::{characterize, CharacterizationConfig};
+//!
+//! // Run before any PID loop is initialized, the tests drive the motors directly.
+//! let ff = characterize(&drivetrain, &config, &CharacterizationConfig::default()).await;
+//! if let Some(ff) = ff {
+//!     info!("kS = {}, kV = {}, kA = {}", ff.ks, ff.kv, ff.ka);
+//! }
+//! ```
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    time::Duration,
+};
+
+use log::{info, warn};
+use vexide::time::*;
+
+use crate::{
+    motion::{feedforward::Feedforward, pid::DrivetrainConfig, timing::DEFAULT_LOOPRATE},
+    peripherals::drivetrain::{Differential, DriveOwner},
+};
+
+/// Samples slower than this (in inches per second) are not used for fitting,
+/// since the sign of the velocity is unreliable near rest.
+const MIN_VELOCITY: f64 = 0.1;
+
+/// The characterization test a sample was recorded in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharacterizationTest {
+    /// Slow voltage ramp driving forward.
+    QuasistaticForward,
+    /// Slow voltage ramp driving in reverse.
+    QuasistaticReverse,
+    /// Voltage step driving forward.
+    DynamicForward,
+    /// Voltage step driving in reverse.
+    DynamicReverse,
+}
+
+impl CharacterizationTest {
+    /// All tests, in the order they are run.
+    pub const ALL: [CharacterizationTest; 4] = [
+        CharacterizationTest::QuasistaticForward,
+        CharacterizationTest::QuasistaticReverse,
+        CharacterizationTest::DynamicForward,
+        CharacterizationTest::DynamicReverse,
+    ];
+
+    /// The name of the test as written to the CSV file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CharacterizationTest::QuasistaticForward => "quasistatic-forward",
+            CharacterizationTest::QuasistaticReverse => "quasistatic-reverse",
+            CharacterizationTest::DynamicForward => "dynamic-forward",
+            CharacterizationTest::DynamicReverse => "dynamic-reverse",
+        }
+    }
+
+    fn direction(&self) -> f64 {
+        match self {
+            CharacterizationTest::QuasistaticForward | CharacterizationTest::DynamicForward => 1.0,
+            CharacterizationTest::QuasistaticReverse | CharacterizationTest::DynamicReverse => -1.0,
+        }
+    }
+
+    fn is_quasistatic(&self) -> bool {
+        matches!(
+            self,
+            CharacterizationTest::QuasistaticForward | CharacterizationTest::QuasistaticReverse
+        )
+    }
+}
+
+/// A single recorded characterization sample.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CharacterizationSample {
+    /// The test this sample belongs to.
+    pub test:     CharacterizationTest,
+    /// Time since the start of the test in seconds.
+    pub time:     f64,
+    /// Voltage applied to both sides of the drivetrain.
+    pub voltage:  f64,
+    /// Distance travelled since the start of the test in inches.
+    pub position: f64,
+    /// Velocity in inches per second.
+    pub velocity: f64,
+}
+
+/// Settings for a characterization run.
+#[derive(Clone, Debug)]
+pub struct CharacterizationConfig {
+    /// Voltage ramp rate for the quasistatic tests in volts per second.
+    pub ramp_rate:           f64,
+    /// Voltage applied during the dynamic tests.
+    pub step_voltage:        f64,
+    /// Duration of each quasistatic test in milliseconds.
+    pub quasistatic_timeout: u64,
+    /// Duration of each dynamic test in milliseconds.
+    pub dynamic_timeout:     u64,
+    /// Pause between tests in milliseconds, to let the robot come to rest.
+    pub rest:                u64,
+    /// Time between samples in milliseconds.
+    pub period:              u64,
+    /// Path of the CSV file on the SD card, or `None` to skip writing it.
+    pub csv_path:            Option<String>,
+}
+
+impl Default for CharacterizationConfig {
+    fn default() -> Self {
+        Self {
+            ramp_rate:           1.0,
+            step_voltage:        6.0,
+            quasistatic_timeout: 5000,
+            dynamic_timeout:     1500,
+            rest:                1500,
+            period:              DEFAULT_LOOPRATE,
+            csv_path:            Some("characterization.csv".to_string()),
+        }
+    }
+}
+
+/// Runs all characterization tests on the drivetrain and fits the
+/// feedforward gains.
+///
+/// The tests drive the motors directly, so no PID loop should be running on
+/// the same drivetrain.
+///
+/// # Arguments
+///
+/// * `drivetrain` - The drivetrain to characterize.
+/// * `drivetrain_config` - Used to convert motor readings to inches.
+/// * `config` - Test settings.
+///
+/// # Returns
+///
+/// The fitted gains in volts per in/s and in/s², or `None` if the data
+/// could not be fitted (e.g. the robot never moved).
+pub async fn characterize(
+    drivetrain: &Differential,
+    drivetrain_config: &DrivetrainConfig,
+    config: &CharacterizationConfig,
+) -> Option<Feedforward> {
+    info!("Drivetrain Characterization Started");
+    drivetrain.acquire(DriveOwner::Manual);
+    let mut samples = Vec::new();
+    for test in CharacterizationTest::ALL {
+        info!("Running characterization test: {}", test.name());
+        run_test(drivetrain, drivetrain_config, config, test, &mut samples).await;
+        sleep(Duration::from_millis(config.rest)).await;
+    }
+    drivetrain.release(DriveOwner::Manual);
+
+    if let Some(path) = &config.csv_path &&
+        let Err(e) = write_csv(path, &samples)
+    {
+        warn!("Could not write characterization data to {}: {}", path, e);
+    }
+
+    let feedforward = fit_feedforward(&samples);
+    match feedforward {
+        Some(ff) => info!("Characterization: kS = {}, kV = {}, kA = {}", ff.ks, ff.kv, ff.ka),
+        None => warn!("Characterization data could not be fitted"),
+    }
+    feedforward
+}
+
+async fn run_test(
+    drivetrain: &Differential,
+    drivetrain_config: &DrivetrainConfig,
+    config: &CharacterizationConfig,
+    test: CharacterizationTest,
+    samples: &mut Vec<CharacterizationSample>,
+) {
+    let timeout = if test.is_quasistatic() {
+        config.quasistatic_timeout
+    } else {
+        config.dynamic_timeout
+    };
+
+    let start_position = drivetrain.position().as_radians();
+    let start_time = user_uptime().as_millis();
+    loop {
+        let elapsed = user_uptime().as_millis() - start_time;
+        let time = elapsed as f64 / 1000.0;
+        let voltage = if test.is_quasistatic() {
+            (config.ramp_rate * time).min(12.0) * test.direction()
+        } else {
+            config.step_voltage * test.direction()
+        };
+        set_voltage(drivetrain, voltage);
+
+        samples.push(CharacterizationSample {
+            test,
+            time,
+            voltage,
+            position: drivetrain_config
+                .angle_to_distance(drivetrain.position().as_radians() - start_position),
+            velocity: drivetrain_config.rpm_to_velocity(average_velocity(drivetrain)),
+        });
+
+        if elapsed >= timeout as u128 {
+            break;
+        }
+        sleep(Duration::from_millis(config.period.max(1))).await;
+    }
+    set_voltage(drivetrain, 0.0);
+}
+
+/// Fits `V = kS·sign(v) + kV·v + kA·a` to the recorded samples by least squares.
+///
+/// Acceleration is estimated with a central difference of the velocity
+/// within each test. Samples near rest are ignored.
+pub fn fit_feedforward(samples: &[CharacterizationSample]) -> Option<Feedforward> {
+    let mut rows = Vec::new();
+    let mut voltages = Vec::new();
+    for i in 1..samples.len().saturating_sub(1) {
+        let (prev, curr, next) = (samples[i - 1], samples[i], samples[i + 1]);
+        if prev.test != curr.test || next.test != curr.test {
+            continue;
+        }
+        if curr.velocity.abs() < MIN_VELOCITY || next.time <= prev.time {
+            continue;
+        }
+        let acceleration = (next.velocity - prev.velocity) / (next.time - prev.time);
+        rows.push([curr.velocity.signum(), curr.velocity, acceleration]);
+        voltages.push(curr.voltage);
+    }
+    let [ks, kv, ka] = least_squares(&rows, &voltages)?;
+    Some(Feedforward::new(ks, kv, ka))
+}
+
+/// Solves the 3-parameter linear least squares problem `rows · x ≈ y`
+/// through its normal equations.
+fn least_squares(rows: &[[f64; 3]], y: &[f64]) -> Option<[f64; 3]> {
+    let mut m = [[0.0; 4]; 3];
+    for (row, &value) in rows.iter().zip(y) {
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] += row[i] * row[j];
+            }
+            m[i][3] += row[i] * value;
+        }
+    }
+    // Gaussian elimination with partial pivoting
+    for col in 0..3 {
+        let pivot = (col..3).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+        if m[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        m.swap(col, pivot);
+        for row in 0..3 {
+            if row != col {
+                let factor = m[row][col] / m[col][col];
+                let pivot_row = m[col];
+                for (value, pivot_value) in m[row].iter_mut().zip(pivot_row).skip(col) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    Some([m[0][3] / m[0][0], m[1][3] / m[1][1], m[2][3] / m[2][2]])
+}
+
+/// Writes characterization samples to a CSV file.
+///
+/// The file has the header `test,time,voltage,position,velocity`.
+pub fn write_csv(path: &str, samples: &[CharacterizationSample]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "test,time,voltage,position,velocity")?;
+    for s in samples {
+        writeln!(
+            writer,
+            "{},{:.3},{:.3},{:.4},{:.4}",
+            s.test.name(),
+            s.time,
+            s.voltage,
+            s.position,
+            s.velocity
+        )?;
+    }
+    writer.flush()
+}
+
+fn set_voltage(drivetrain: &Differential, voltage: f64) {
+    for side in [&drivetrain.left, &drivetrain.right] {
+        let mut motors = side.borrow_mut();
+        for motor in motors.as_mut().iter_mut() {
+            let _ = motor.set_voltage(voltage);
+        }
+    }
+}
+
+/// Average motor velocity of both sides in RPM.
+fn average_velocity(drivetrain: &Differential) -> f64 {
+    let mut sum = 0.0;
+    let mut count = 0.0;
+    for side in [&drivetrain.left, &drivetrain.right] {
+        let mut motors = side.borrow_mut();
+        for motor in motors.as_mut().iter() {
+            if let Ok(velocity) = motor.velocity() {
+                sum += velocity;
+                count += 1.0;
+            }
+        }
+    }
+    if count > 0.0 { sum / count } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simulates the drivetrain as `V = kS·sign(v) + kV·v + kA·a`.
+    fn simulate(ff: Feedforward) -> Vec<CharacterizationSample> {
+        let dt = 0.005;
+        let mut samples = Vec::new();
+        for test in CharacterizationTest::ALL {
+            let (mut position, mut velocity) = (0.0, 0.0);
+            let duration = if test.is_quasistatic() { 5.0 } else { 1.5 };
+            for i in 0..(duration / dt) as usize {
+                let time = i as f64 * dt;
+                let voltage = if test.is_quasistatic() {
+                    1.0 * time * test.direction()
+                } else {
+                    6.0 * test.direction()
+                };
+                samples.push(CharacterizationSample {
+                    test,
+                    time,
+                    voltage,
+                    position,
+                    velocity,
+                });
+                let friction = if velocity != 0.0 {
+                    ff.ks * velocity.signum()
+                } else if voltage.abs() > ff.ks {
+                    ff.ks * voltage.signum()
+                } else {
+                    voltage
+                };
+                let acceleration = (voltage - friction - ff.kv * velocity) / ff.ka;
+                velocity += acceleration * dt;
+                position += velocity * dt;
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn fit_feedforward_test() {
+        let model = Feedforward::new(0.8, 0.15, 0.03);
+        let fit = fit_feedforward(&simulate(model)).unwrap();
+        assert!((fit.ks - model.ks).abs() < 0.02);
+        assert!((fit.kv - model.kv).abs() / model.kv < 0.02);
+        assert!((fit.ka - model.ka).abs() / model.ka < 0.05);
+    }
+
+    #[test]
+    fn fit_feedforward_no_motion_test() {
+        let samples: Vec<CharacterizationSample> = (0..100)
+            .map(|i| CharacterizationSample {
+                test:     CharacterizationTest::QuasistaticForward,
+                time:     i as f64 * 0.005,
+                voltage:  0.5,
+                position: 0.0,
+                velocity: 0.0,
+            })
+            .collect();
+        assert_eq!(fit_feedforward(&samples), None);
+    }
+
+    #[test]
+    fn least_squares_exact_test() {
+        let rows = [[1.0, 2.0, 0.0], [1.0, 4.0, 1.0], [-1.0, -3.0, 2.0], [1.0, 1.0, -1.0]];
+        let x = [0.5, 2.0, -1.0];
+        let y: Vec<f64> = rows
+            .iter()
+            .map(|r| r[0] * x[0] + r[1] * x[1] + r[2] * x[2])
+            .collect();
+        let fit = least_squares(&rows, &y).unwrap();
+        for i in 0..3 {
+            assert!((fit[i] - x[i]).abs() < 1e-9);
+        }
+    }
+}
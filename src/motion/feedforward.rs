@@ -0,0 +1,52 @@
+//! Feedforward models for motor-driven mechanisms.
+//!
+//! Feedforward predicts the voltage needed to follow a velocity and
+//! acceleration, so a PID controller only has to correct the remaining
+//! error. The model used here is the standard permanent-magnet DC motor
+//! model:
+//!
+//! ```text
+//! voltage = kS * sign(velocity) + kV * velocity + kA * acceleration
+//! ```
+//!
+//! - **kS**: Voltage needed to overcome static friction.
+//! - **kV**: Voltage per unit of velocity.
+//! - **kA**: Voltage per unit of acceleration.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use antaeus::motion::feedforward::Feedforward;
+//!
+//! let ff = Feedforward::new(0.8, 0.15, 0.02);
+//! let volts = ff.calculate(40.0, 0.0); // holding 40 in/s
+//! ```
+
+/// A `kS`/`kV`/`kA` feedforward model.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Feedforward {
+    /// Static friction voltage.
+    pub ks: f64,
+    /// Voltage per unit of velocity.
+    pub kv: f64,
+    /// Voltage per unit of acceleration.
+    pub ka: f64,
+}
+
+impl Feedforward {
+    /// Creates a new feedforward model.
+    pub fn new(ks: f64, kv: f64, ka: f64) -> Self { Self { ks, kv, ka } }
+
+    /// Returns the voltage needed to move at `velocity` while accelerating
+    /// at `acceleration`.
+    pub fn calculate(&self, velocity: f64, acceleration: f64) -> f64 {
+        let static_friction = if velocity > 0.0 {
+            self.ks
+        } else if velocity < 0.0 {
+            -self.ks
+        } else {
+            0.0
+        };
+        static_friction + self.kv * velocity + self.ka * acceleration
+    }
+}
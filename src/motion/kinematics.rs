@@ -0,0 +1,191 @@
+//! Differential drive kinematics.
+//!
+//! Converts between what the wheels do and what the chassis does, using the
+//! measurements in a [`DrivetrainConfig`]. Every controller goes through
+//! these conversions instead of repeating the formulas, so a drivetrain is
+//! described in exactly one place.
+//!
+//! # Conventions
+//!
+//! - Distances are in inches and linear velocities in inches per second.
+//! - Wheel angles are motor encoder angles in radians, before the gearing,
+//!   which is what the motors report.
+//! - Angular velocity is in radians per second and curvature in radians
+//!   per inch. Both are positive clockwise, like headings.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let config = DrivetrainConfig::new(3.25, 36.0, 48.0, 12.0);
+//!
+//! // Motor radians needed to drive 24 inches
+//! let target = config.distance_to_angle(24.0);
+//!
+//! // Wheel velocities for 30 in/s while turning right at 1 rad/s
+//! let (left, right) = config.inverse(30.0, 1.0);
+//! ```
+//!
+//! # Migrating from earlier versions
+//!
+//! `PIDMovement` and `ArcPIDMovement` used to convert inches to motor
+//! radians with `ratio * 2π / wheel_diameter`. They now use
+//! [`distance_to_angle`](DrivetrainConfig::distance_to_angle), which is
+//! `2 / (ratio * wheel_diameter)`. The old scale was larger by a factor of
+//!
+//! ```text
+//! k = ratio² · π
+//! ```
+//!
+//! for every drivetrain, including 1:1 ones, where `k = π`. All targets,
+//! tolerances and gains of those controllers change by this factor, so
+//! every existing tune is affected:
+//!
+//! - Distances: `travel(d, ..)` used to drive `k · d` inches and now
+//!   drives `d`. Pass `k · d` to drive the same distance as before. The
+//!   same goes for arc distances, encoder turns and exit ranges in inches.
+//! - Tolerances in radians: divide by `k` to keep the same tolerance in
+//!   inches.
+//! - Gains in volts per radian: multiply `kp`, `ki` and `kd` by `k` to keep
+//!   the same output per inch of error.
+//!
+//! For example, 36:48 gearing has `k = 0.5625π ≈ 1.77`.
+
+use std::f64::consts::PI;
+
+use super::pid::DrivetrainConfig;
+
+impl DrivetrainConfig {
+    /// Wheel rotations per motor rotation.
+    pub fn gear_ratio(&self) -> f64 { self.driving_gear / self.driven_gear }
+
+    /// Converts a motor angle in radians to the distance the wheel rolls.
+    pub fn angle_to_distance(&self, angle: f64) -> f64 {
+        angle * self.gear_ratio() * self.wheel_diameter / 2.0
+    }
+
+    /// Converts a distance the wheel rolls to a motor angle in radians.
+    ///
+    /// This is the scale of the PID controllers' targets. It changed by a
+    /// factor of `ratio² · π` in this release, see the
+    /// [module docs](self#migrating-from-earlier-versions).
+    pub fn distance_to_angle(&self, distance: f64) -> f64 {
+        distance * 2.0 / (self.gear_ratio() * self.wheel_diameter)
+    }
+
+    /// Converts a motor velocity in RPM to the wheel's velocity in inches
+    /// per second.
+    pub fn rpm_to_velocity(&self, rpm: f64) -> f64 { self.angle_to_distance(rpm * 2.0 * PI / 60.0) }
+
+    /// Converts a wheel velocity in inches per second to a motor velocity in
+    /// RPM.
+    pub fn velocity_to_rpm(&self, velocity: f64) -> f64 {
+        self.distance_to_angle(velocity) * 60.0 / (2.0 * PI)
+    }
+
+    /// Forward kinematics: turns left and right wheel velocities into the
+    /// chassis' linear and angular velocity `(v, ω)`.
+    pub fn forward(&self, left: f64, right: f64) -> (f64, f64) {
+        ((left + right) / 2.0, (left - right) / self.track_width)
+    }
+
+    /// Inverse kinematics: turns a linear and angular chassis velocity into
+    /// `(left, right)` wheel velocities.
+    pub fn inverse(&self, linear: f64, angular: f64) -> (f64, f64) {
+        let turn = angular * self.track_width / 2.0;
+        (linear + turn, linear - turn)
+    }
+
+    /// Wheel velocities that drive along an arc of `curvature` at `linear`
+    /// inches per second.
+    pub fn curvature_to_wheels(&self, linear: f64, curvature: f64) -> (f64, f64) {
+        self.inverse(linear, linear * curvature)
+    }
+
+    /// Ratio of the right wheel's speed to the left wheel's on an arc of
+    /// `curvature`.
+    ///
+    /// This is the `offset` used by [`ArcPIDMovement`](super::pid::arcpid::ArcPIDMovement):
+    /// below 1 curves right, above 1 curves left. Returns infinity for a
+    /// left turn about the left wheel.
+    pub fn curvature_to_ratio(&self, curvature: f64) -> f64 {
+        let half = curvature * self.track_width / 2.0;
+        (1.0 - half) / (1.0 + half)
+    }
+
+    /// Distance each side travels, in opposite directions, to turn in place
+    /// by `degrees`.
+    pub fn turn_distance(&self, degrees: f64) -> f64 {
+        degrees.to_radians() * self.track_width / 2.0
+    }
+
+    /// Distance the moving side travels to swing about the other side by
+    /// `degrees`.
+    pub fn swing_distance(&self, degrees: f64) -> f64 { degrees.to_radians() * self.track_width }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> DrivetrainConfig { DrivetrainConfig::new(3.25, 36.0, 48.0, 12.0) }
+
+    fn close(a: f64, b: f64) -> bool { (a - b).abs() < 1e-9 }
+
+    #[test]
+    fn angle_distance_test() {
+        let config = config();
+        // One motor turn is 0.75 wheel turns
+        assert!(close(config.angle_to_distance(2.0 * PI), 0.75 * PI * 3.25));
+        assert!(close(config.distance_to_angle(config.angle_to_distance(3.0)), 3.0));
+        assert!(close(config.rpm_to_velocity(60.0), 0.75 * PI * 3.25));
+        assert!(close(config.velocity_to_rpm(config.rpm_to_velocity(450.0)), 450.0));
+    }
+
+    #[test]
+    fn travel_distance_test() {
+        // 36:48 gearing on 3.25 inch wheels rolls 0.75 * 3.25π ≈ 7.66 inches
+        // per motor turn, so 24 inches takes about 3.13 motor turns.
+        let config = config();
+        let turns = config.distance_to_angle(24.0) / (2.0 * PI);
+        assert!((turns - 3.1341).abs() < 1e-4);
+        // Driving that far rolls the wheels 24 real inches
+        assert!(close(config.angle_to_distance(turns * 2.0 * PI), 24.0));
+        // A 1:1 drivetrain turns once per wheel circumference
+        let direct = DrivetrainConfig::new(4.0, 1.0, 1.0, 12.0);
+        assert!(close(direct.distance_to_angle(4.0 * PI), 2.0 * PI));
+    }
+
+    #[test]
+    fn forward_inverse_test() {
+        let config = config();
+        let (left, right) = config.inverse(30.0, 1.0);
+        assert!(close(left, 36.0));
+        assert!(close(right, 24.0));
+        let (linear, angular) = config.forward(left, right);
+        assert!(close(linear, 30.0));
+        assert!(close(angular, 1.0));
+        // Turning in place
+        assert_eq!(config.forward(-10.0, 10.0), (0.0, -20.0 / 12.0));
+    }
+
+    #[test]
+    fn curvature_test() {
+        let config = config();
+        // A 12 inch radius to the right: the inner wheel runs on a 6 inch radius
+        let (left, right) = config.curvature_to_wheels(12.0, 1.0 / 12.0);
+        assert!(close(left, 18.0));
+        assert!(close(right, 6.0));
+        assert!(close(config.curvature_to_ratio(1.0 / 12.0), right / left));
+        // Turning left mirrors it
+        assert!(close(config.curvature_to_ratio(-1.0 / 12.0), 3.0));
+        assert_eq!(config.curvature_to_ratio(0.0), 1.0);
+    }
+
+    #[test]
+    fn turn_distance_test() {
+        let config = config();
+        assert!(close(config.turn_distance(360.0), PI * 12.0));
+        assert!(close(config.swing_distance(90.0), PI * 6.0));
+        assert!(close(config.turn_distance(-90.0), -PI * 3.0));
+    }
+}
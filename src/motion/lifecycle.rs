@@ -0,0 +1,219 @@
+//! Lifecycle control for background control loops.
+//!
+//! Each controller's `init` spawns a background task. The [`LoopHandle`] it
+//! returns lets the program pause, resume, restart or shut that task down,
+//! and check whether it is still running. The same handle is stored on the
+//! controller, so calling `init` again while the loop is alive does not
+//! spawn a second one.
+//!
+//! The handle also sets the loop period and reports its timing statistics,
+//! see the [`timing`](super::timing) module.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let handle = pid.init();
+//!
+//! // Stop writing to the motors while a manual routine runs
+//! handle.pause();
+//! run_manual_routine().await;
+//! handle.resume();
+//!
+//! // Zero the encoders and start over
+//! handle.restart();
+//!
+//! // Stop the loop for good. It exits on its next tick.
+//! handle.shutdown();
+//! sleep(Duration::from_millis(handle.period() * 2)).await;
+//! assert!(!handle.is_alive());
+//! ```
+//!
+//! Calling `init` after [`shutdown`](LoopHandle::shutdown) but before the
+//! loop has exited cancels the shutdown and keeps the running loop.
+
+use std::{cell::Cell, rc::Rc};
+
+use super::timing::{DEFAULT_LOOPRATE, LoopStats};
+
+/// What a control loop should do on its next tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LoopCommand {
+    /// Run normally.
+    Run,
+    /// Skip the tick without writing to the motors.
+    Paused,
+    /// Reset encoders and controller state, then run.
+    Restart,
+    /// Stop the motors and exit the loop.
+    Shutdown,
+}
+
+struct LoopFlags {
+    alive:    Cell<bool>,
+    paused:   Cell<bool>,
+    restart:  Cell<bool>,
+    shutdown: Cell<bool>,
+    period:   Cell<u64>,
+    stats:    Cell<LoopStats>,
+}
+
+impl Default for LoopFlags {
+    fn default() -> Self {
+        Self {
+            alive:    Cell::new(false),
+            paused:   Cell::new(false),
+            restart:  Cell::new(false),
+            shutdown: Cell::new(false),
+            period:   Cell::new(DEFAULT_LOOPRATE),
+            stats:    Cell::new(LoopStats::default()),
+        }
+    }
+}
+
+/// A handle to a controller's background loop.
+///
+/// Clones share the same loop, so the handle returned by `init` and the one
+/// stored on the controller control the same task.
+#[derive(Clone, Default)]
+pub struct LoopHandle {
+    flags: Rc<LoopFlags>,
+}
+
+impl LoopHandle {
+    /// Stops the loop from writing to the motors until [`resume`](Self::resume).
+    ///
+    /// The motors are stopped once when the loop notices the pause.
+    pub fn pause(&self) { self.flags.paused.set(true); }
+
+    /// Lets a paused loop run again.
+    pub fn resume(&self) { self.flags.paused.set(false); }
+
+    /// Asks the loop to reset its encoders and controller state.
+    ///
+    /// Returns `false` if the loop is not running. A loop that was shut down
+    /// is started again with the controller's `init`.
+    pub fn restart(&self) -> bool {
+        if self.is_alive() {
+            self.flags.restart.set(true);
+        }
+        self.is_alive()
+    }
+
+    /// Asks the loop to stop the motors and exit.
+    ///
+    /// The loop exits on its next tick, so [`is_alive`](Self::is_alive)
+    /// stays `true` until then.
+    pub fn shutdown(&self) { self.flags.shutdown.set(true); }
+
+    /// Returns `true` while the loop task is running.
+    pub fn is_alive(&self) -> bool { self.flags.alive.get() }
+
+    /// Returns `true` if the loop is paused.
+    pub fn is_paused(&self) -> bool { self.flags.paused.get() }
+
+    /// Sets the loop period in milliseconds. Takes effect on the next tick.
+    ///
+    /// Periods below 1 ms are raised to 1 ms.
+    pub fn set_period(&self, period: u64) { self.flags.period.set(period.max(1)); }
+
+    /// Returns the loop period in milliseconds.
+    pub fn period(&self) -> u64 { self.flags.period.get() }
+
+    /// Returns the timing statistics of the loop since it started or since
+    /// [`reset_stats`](Self::reset_stats).
+    pub fn stats(&self) -> LoopStats { self.flags.stats.get() }
+
+    /// Clears the timing statistics.
+    pub fn reset_stats(&self) { self.flags.stats.set(LoopStats::default()); }
+
+    /// Records a tick `dt` seconds after the previous one. Returns `true` if
+    /// the tick overran its period.
+    pub(crate) fn record_tick(&self, dt: f64) -> bool {
+        let mut stats = self.flags.stats.get();
+        let overrun = stats.record(dt, self.period() as f64 / 1000.0);
+        self.flags.stats.set(stats);
+        overrun
+    }
+
+    /// Marks the loop as started. Returns `false` if it was already running,
+    /// in which case no new task should be spawned.
+    ///
+    /// A shutdown the running loop has not acted on yet is cancelled, so the
+    /// existing task keeps running instead of exiting with no loop left.
+    pub(crate) fn start(&self) -> bool {
+        if self.is_alive() {
+            self.flags.shutdown.set(false);
+            return false;
+        }
+        self.flags.alive.set(true);
+        self.flags.paused.set(false);
+        self.flags.restart.set(false);
+        self.flags.shutdown.set(false);
+        self.reset_stats();
+        true
+    }
+
+    /// Returns what the loop should do on this tick.
+    pub(crate) fn poll(&self) -> LoopCommand {
+        if self.flags.shutdown.get() {
+            LoopCommand::Shutdown
+        } else if self.flags.restart.replace(false) {
+            LoopCommand::Restart
+        } else if self.flags.paused.get() {
+            LoopCommand::Paused
+        } else {
+            LoopCommand::Run
+        }
+    }
+
+    /// Marks the loop as exited.
+    pub(crate) fn stopped(&self) {
+        self.flags.alive.set(false);
+        self.flags.shutdown.set(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_is_idempotent_test() {
+        let handle = LoopHandle::default();
+        assert!(handle.start());
+        assert!(!handle.clone().start());
+        handle.shutdown();
+        assert_eq!(handle.poll(), LoopCommand::Shutdown);
+        handle.stopped();
+        assert!(!handle.is_alive());
+        // A stopped loop can be started again
+        assert!(handle.start());
+        assert_eq!(handle.poll(), LoopCommand::Run);
+    }
+
+    #[test]
+    fn start_cancels_shutdown_test() {
+        let handle = LoopHandle::default();
+        assert!(handle.start());
+        handle.shutdown();
+        // Started again before the loop noticed the shutdown
+        assert!(!handle.start());
+        assert!(handle.is_alive());
+        assert_eq!(handle.poll(), LoopCommand::Run);
+    }
+
+    #[test]
+    fn poll_test() {
+        let handle = LoopHandle::default();
+        assert!(!handle.restart());
+        handle.start();
+        handle.pause();
+        assert_eq!(handle.poll(), LoopCommand::Paused);
+        // A restart is reported once, even while paused
+        assert!(handle.restart());
+        assert_eq!(handle.poll(), LoopCommand::Restart);
+        assert_eq!(handle.poll(), LoopCommand::Paused);
+        handle.resume();
+        assert_eq!(handle.poll(), LoopCommand::Run);
+    }
+}
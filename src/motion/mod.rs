@@ -8,6 +8,10 @@
 //!   linear and rotational movement.
 //! - **Path Following**: The Candidate-Based Pursuit algorithm for smooth path
 //!   tracking.
+//! - **Motion Profiles**: Trapezoidal and S-curve velocity profiles with
+//!   feedforward for smooth straight-line travel.
+//! - **Velocity Control**: Closed-loop wheel velocities in inches per second
+//!   that don't change with battery voltage.
 //!
 //! # Architecture
 //!
@@ -26,16 +30,47 @@
 //! pid.init();
 //!
 //! // Execute movements
-//! pid.travel(24.0, 2000, 100).await;  // Move 24 inches
-//! pid.rotate(90.0, 2000, 100).await;  // Turn 90 degrees
+//! pid.travel(24.0, pid.options()).await;  // Move 24 inches
+//! pid.rotate(90.0, pid.options()).await;  // Turn 90 degrees
 //! ```
 
+/// Drivetrain feedforward characterization.
+///
+/// Runs quasistatic and dynamic voltage tests on a drivetrain, records
+/// the results to the SD card and fits `kS`, `kV` and `kA`.
+pub mod characterize;
+
+/// Feedforward models for motor-driven mechanisms.
+///
+/// Provides the [`Feedforward`](feedforward::Feedforward) struct, which
+/// predicts the voltage needed for a given velocity and acceleration.
+pub mod feedforward;
+
+/// Differential drive kinematics.
+///
+/// Adds conversions to [`DrivetrainConfig`](pid::DrivetrainConfig) between
+/// motor angles and distances, and between wheel and chassis velocities.
+pub mod kinematics;
+
+/// Lifecycle handles for background control loops.
+///
+/// Provides the [`LoopHandle`](lifecycle::LoopHandle) returned by each
+/// controller's `init`, for pausing, restarting and shutting down its loop.
+pub mod lifecycle;
+
 /// Odometry tracking for position estimation.
 ///
 /// Provides the [`OdomMovement`](odom::OdomMovement) struct for tracking
 /// the robot's global position using tracking wheels and an inertial sensor.
 pub mod odom;
 
+/// Per-motion options for PID and odometry movements.
+///
+/// Provides the [`MotionOptions`](options::MotionOptions) builder for
+/// timeouts, power limits, exit conditions and delays, and the per-controller
+/// [`MotionDefaults`](options::MotionDefaults).
+pub mod options;
+
 /// PID control algorithms.
 ///
 /// Contains multiple PID implementations:
@@ -44,8 +79,26 @@ pub mod odom;
 /// - [`singlepid`](pid::singlepid): PID for single motor groups.
 pub mod pid;
 
+/// Trapezoidal and S-curve motion profiles.
+///
+/// Generates position, velocity and acceleration setpoints over time
+/// for rest-to-rest movements within velocity, acceleration and jerk limits.
+pub mod profile;
+
 /// Candidate-Based Pursuit path following algorithm.
 ///
 /// A more robust variant of pure pursuit that handles edge cases
 /// like the robot being off the path or near path endpoints.
 pub mod pusuit;
+
+/// Fixed-rate timing for control loops.
+///
+/// Paces the background loops, measures the real time between ticks and
+/// tracks jitter and overruns through [`LoopStats`](timing::LoopStats).
+pub mod timing;
+
+/// Closed-loop wheel velocity control.
+///
+/// Provides [`VelocityDrive`](velocity::VelocityDrive), which tracks left and
+/// right velocities in inches per second with feedforward and PID.
+pub mod velocity;
@@ -33,10 +33,10 @@
 //! odom.init();  // Start the tracking loop
 //!
 //! // Use for navigation
-//! odom.goto_point(24.0, 24.0).await;
+//! odom.goto_point(24.0, 24.0, odom.options()).await;
 //! ```
 
-use std::{borrow::Borrow, sync::Arc, time::Duration};
+use std::{borrow::Borrow, sync::Arc};
 
 use log::{info, warn};
 use vexide::{
@@ -44,30 +44,52 @@ use vexide::{
     prelude::{AdiOpticalEncoder, InertialSensor, RotationSensor},
     sync::Mutex,
     task::spawn,
-    time::sleep,
 };
 
 use crate::{
-    motion::pid::{arcpid::ArcPIDMovement, pid::PIDMovement},
+    motion::{
+        lifecycle::{LoopCommand, LoopHandle},
+        options::{MotionDefaults, MotionOptions},
+        pid::{arcpid::ArcPIDMovement, pid::PIDMovement},
+        timing::LoopTimer,
+    },
     peripherals::drivetrain::Differential,
 };
 
-/// Loop rate for the odometry tracking task in milliseconds.
-const LOOPRATE: u64 = 5;
-
-/// Default timeout for movement operations in milliseconds.
-const TIMEOUT: u64 = 10000;
-
-/// Default delay after movement completion in milliseconds.
-const AFTERDELAY: u64 = 10;
-
-async fn odom_tracker(values: &Arc<Mutex<OdomValues>>, trackers: &Trackers) {
+async fn odom_tracker(values: &Arc<Mutex<OdomValues>>, trackers: &Trackers, lifecycle: LoopHandle) {
     info!("Odometry Tracking Started");
     let mut prev_dist_v = 0.0;
     let mut prev_dist_h = 0.0;
     let mut prev_heading = 0.0;
+    let mut timer = LoopTimer::new("Odometry Tracking", &lifecycle);
 
     loop {
+        timer.tick();
+        // Whether to take this tick's readings as the new baseline without
+        // moving the pose
+        let mut rebaseline = false;
+        match lifecycle.poll() {
+            LoopCommand::Shutdown => break,
+            LoopCommand::Paused => {
+                timer.wait().await;
+                continue;
+            }
+            LoopCommand::Restart => {
+                info!("Odometry Tracking Restarted");
+                {
+                    let mut imu = trackers.imu.lock().await;
+                    let _ = imu.reset_rotation();
+                    let _ = imu.reset_heading();
+                }
+                let mut s = values.lock().await;
+                s.global_x = 0.0;
+                s.global_y = 0.0;
+                s.global_heading = 0.0;
+                rebaseline = true;
+            }
+            LoopCommand::Run => {}
+        }
+
         // The absolute number of radians turned by the robot
         let abs_rotation;
         let euler_heading;
@@ -115,6 +137,14 @@ async fn odom_tracker(values: &Arc<Mutex<OdomValues>>, trackers: &Trackers) {
             offset_h = horizontal.offset.clone();
         }
 
+        if rebaseline {
+            prev_dist_v = vertical_rad * wheel_dia_v / 2.0;
+            prev_dist_h = horizontal_rad * wheel_dia_h / 2.0;
+            prev_heading = abs_rotation;
+            timer.wait().await;
+            continue;
+        }
+
         // Getting local change in coords
         let (delta_y, delta_x);
         if delta_heading == 0.0 {
@@ -136,8 +166,11 @@ async fn odom_tracker(values: &Arc<Mutex<OdomValues>>, trackers: &Trackers) {
         prev_dist_h = horizontal_rad * wheel_dia_h / 2.0;
 
         prev_heading = abs_rotation;
-        sleep(Duration::from_millis(LOOPRATE)).await;
+        timer.wait().await;
     }
+
+    lifecycle.stopped();
+    info!("Odometry Tracking Stopped");
 }
 
 impl OdomMovement {
@@ -148,42 +181,72 @@ impl OdomMovement {
     ///
     /// **Must be called before any movement methods.**
     ///
+    /// Returns a [`LoopHandle`] for pausing, restarting or shutting down the
+    /// tracking task. Restarting resets the pose to the origin. Calling
+    /// `init` again while tracking is running returns the same handle.
+    ///
     /// # Example
     ///
     /// ```ignore
     /// let odom = OdomMovement { /* ... */ };
     /// odom.init();  // Start tracking
-    /// odom.goto_point(24.0, 0.0).await;
+    /// odom.goto_point(24.0, 0.0, odom.options()).await;
     /// ```
-    pub fn init(&self) {
+    pub fn init(&self) -> LoopHandle {
+        if !self.lifecycle.start() {
+            info!("Odometry Tracking Already Running");
+            return self.lifecycle.clone();
+        }
         let thread_clone = self.odometry_values.clone();
         let thread_trackers = self.trackers.clone();
+        let lifecycle = self.lifecycle.clone();
         let mainloop = spawn(async move {
-            odom_tracker(&thread_clone, &thread_trackers).await;
+            odom_tracker(&thread_clone, &thread_trackers, lifecycle).await;
         });
         mainloop.detach();
+        self.lifecycle.clone()
+    }
+
+    /// Returns the controller's default [`MotionOptions`], to pass to a
+    /// motion as they are or adjusted with the builder methods.
+    pub fn options(&self) -> MotionOptions { self.defaults.get() }
+
+    /// Returns the heading in degrees and distance in inches from the
+    /// robot to a point, facing backwards if `forwards` is `false`.
+    async fn point_offset(&self, x: f64, y: f64, forwards: bool) -> (f64, f64) {
+        let (delta_x, delta_y) = {
+            let odom = self.odometry_values.lock().await;
+            (x - odom.global_x, y - odom.global_y)
+        };
+        let angle = delta_y.atan2(delta_x).to_degrees();
+        let hyp = (delta_x.powi(2) + delta_y.powi(2)).sqrt();
+        if forwards {
+            (angle, hyp)
+        } else {
+            (angle + 180.0, -hyp)
+        }
     }
 
     /// Rotates the robot to face a specific point on the field.
     ///
     /// Calculates the angle to the target point and rotates the robot
-    /// to face that direction using the IMU for feedback.
+    /// to face that direction using the IMU for feedback. With
+    /// [`backwards`](MotionOptions::backwards) the back of the robot faces it.
     ///
     /// # Arguments
     ///
     /// * `x` - Target X coordinate in inches.
     /// * `y` - Target Y coordinate in inches.
+    /// * `options` - Timeout, power limits and exit conditions of the turn.
     ///
     /// # Panics
     ///
     /// Logs a warning and returns early if no PID controller is configured.
-    pub async fn face_point(&self, x: f64, y: f64) {
-        let delta_x = x - self.odometry_values.lock().await.global_x;
-        let delta_y = y - self.odometry_values.lock().await.global_y;
-        let angle = delta_y.atan2(delta_x).to_degrees();
+    pub async fn face_point(&self, x: f64, y: f64, options: MotionOptions) {
+        let (angle, _) = self.point_offset(x, y, options.forwards).await;
         if let Some(pid) = &self.pid {
             let imu = &self.trackers.imu.lock().await;
-            pid.rotate_imu(angle, imu, TIMEOUT, AFTERDELAY).await;
+            pid.rotate_imu(angle, imu, options).await;
         } else {
             warn!("Cannot go to point without Movement Algorithm (PID needed)")
         }
@@ -193,24 +256,27 @@ impl OdomMovement {
     ///
     /// First rotates to face the target, then drives straight to it.
     /// Uses the IMU for rotation feedback and motor encoders for distance.
+    /// With [`backwards`](MotionOptions::backwards) the robot backs into the
+    /// point.
     ///
     /// # Arguments
     ///
     /// * `x` - Target X coordinate in inches.
     /// * `y` - Target Y coordinate in inches.
+    /// * `options` - Options for each step. Only the last step honors
+    ///   [`no_wait`](MotionOptions::no_wait).
     ///
     /// # Panics
     ///
     /// Logs a warning and returns early if no PID controller is configured.
-    pub async fn goto_point(&self, x: f64, y: f64) {
-        let delta_x = x - self.odometry_values.lock().await.global_x;
-        let delta_y = y - self.odometry_values.lock().await.global_y;
-        let angle = delta_y.atan2(delta_x).to_degrees();
-        let hyp = (delta_x.powi(2) + delta_y.powi(2)).sqrt();
+    pub async fn goto_point(&self, x: f64, y: f64, options: MotionOptions) {
+        let (angle, hyp) = self.point_offset(x, y, options.forwards).await;
         if let Some(pid) = &self.pid {
-            let imu = &self.trackers.imu.lock().await;
-            pid.rotate_imu(angle, imu, TIMEOUT, AFTERDELAY).await;
-            pid.travel(hyp, TIMEOUT, AFTERDELAY).await;
+            {
+                let imu = &self.trackers.imu.lock().await;
+                pid.rotate_imu(angle, imu, waiting(options)).await;
+            }
+            pid.travel(hyp, options).await;
         } else {
             warn!("Cannot go to point without Movement Algorithm (PID needed)")
         }
@@ -228,20 +294,21 @@ impl OdomMovement {
     /// * `x` - Target X coordinate in inches.
     /// * `y` - Target Y coordinate in inches.
     /// * `heading` - Final heading in degrees.
+    /// * `options` - Options for each step. Only the last step honors
+    ///   [`no_wait`](MotionOptions::no_wait).
     ///
     /// # Panics
     ///
     /// Logs a warning and returns early if no PID controller is configured.
-    pub async fn goto_pose(&self, x: f64, y: f64, heading: f64) {
-        let delta_x = x - self.odometry_values.lock().await.global_x;
-        let delta_y = y - self.odometry_values.lock().await.global_y;
-        let angle = delta_y.atan2(delta_x).to_degrees();
-        let hyp = (delta_x.powi(2) + delta_y.powi(2)).sqrt();
+    pub async fn goto_pose(&self, x: f64, y: f64, heading: f64, options: MotionOptions) {
+        let (angle, hyp) = self.point_offset(x, y, options.forwards).await;
         if let Some(pid) = &self.pid {
-            let imu = &self.trackers.imu.lock().await;
-            pid.rotate_imu(angle, imu, TIMEOUT, AFTERDELAY).await;
-            pid.travel(hyp, TIMEOUT, AFTERDELAY).await;
-            pid.rotate(heading, TIMEOUT, AFTERDELAY).await;
+            {
+                let imu = &self.trackers.imu.lock().await;
+                pid.rotate_imu(angle, imu, waiting(options)).await;
+            }
+            pid.travel(hyp, waiting(options)).await;
+            pid.rotate(heading, options).await;
         } else {
             warn!("Cannot go to pose without Movement Algorithm (PID needed)")
         }
@@ -255,13 +322,14 @@ impl OdomMovement {
     /// # Arguments
     ///
     /// * `distance` - Distance to travel in inches.
+    /// * `options` - Timeout, power limits and exit conditions of the motion.
     ///
     /// # Panics
     ///
     /// Logs a warning and returns early if no PID controller is configured.
-    pub async fn travel(&self, distance: f64) {
+    pub async fn travel(&self, distance: f64, options: MotionOptions) {
         if let Some(pid) = &self.pid {
-            pid.travel(distance, TIMEOUT, AFTERDELAY).await;
+            pid.travel(distance, options).await;
         } else {
             warn!("Cannot travel without Movement Algorithm (PID needed)")
         }
@@ -276,13 +344,14 @@ impl OdomMovement {
     ///
     /// * `distance` - Arc length to travel in inches.
     /// * `offset` - Curvature offset (larger values = tighter turn).
+    /// * `options` - Timeout, power limits and exit conditions of the motion.
     ///
     /// # Panics
     ///
     /// Logs a warning and returns early if no Arc PID controller is configured.
-    pub async fn arc_travel(&self, distance: f64, offset: f64) {
+    pub async fn arc_travel(&self, distance: f64, offset: f64, options: MotionOptions) {
         if let Some(arc_pid) = &self.arc_pid {
-            arc_pid.travel(distance, offset, TIMEOUT, AFTERDELAY).await;
+            arc_pid.travel(distance, offset, options).await;
         } else {
             warn!("Cannot travel without Movement Algorithm (Arc PID needed)")
         }
@@ -291,8 +360,8 @@ impl OdomMovement {
     /// Moves the robot in an arc to reach specific coordinates.
     ///
     /// Calculates the appropriate arc to reach the target point from
-    /// the current position. This is an internal method used by path
-    /// following algorithms.
+    /// the current position. For following a path, use
+    /// [`Pursuit`](crate::motion::pusuit::Pursuit) instead.
     ///
     /// **Warning**: This method updates absolute position targets and
     /// should not be called directly in most cases.
@@ -325,6 +394,15 @@ impl OdomMovement {
     }
 }
 
+/// Options for an intermediate step of a compound motion, which always
+/// waits so the next step starts from where it ended.
+fn waiting(options: MotionOptions) -> MotionOptions {
+    MotionOptions {
+        wait: true,
+        ..options
+    }
+}
+
 fn rotate_vector(angle: f64, x: f64, y: f64) -> (f64, f64) {
     let cos_theta = angle.cos();
     let sin_theta = angle.sin();
@@ -541,10 +619,10 @@ impl Trackers {
 /// odom.init();
 ///
 /// // Navigate to a point
-/// odom.goto_point(24.0, 24.0).await;
+/// odom.goto_point(24.0, 24.0, odom.options()).await;
 ///
 /// // Face a specific heading
-/// odom.face_point(48.0, 0.0).await;
+/// odom.face_point(48.0, 0.0, odom.options()).await;
 /// ```
 pub struct OdomMovement {
     /// Thread-safe container for current position values.
@@ -557,6 +635,10 @@ pub struct OdomMovement {
     pub pid:             Option<PIDMovement>,
     /// Optional Arc PID controller for curved movements.
     pub arc_pid:         Option<ArcPIDMovement>,
+    /// Default options for motions, see [`options`](OdomMovement::options).
+    pub defaults:        MotionDefaults,
+    /// Handle to the tracking task started by [`init`](OdomMovement::init).
+    pub lifecycle:       LoopHandle,
 }
 
 impl OdomMovement {
@@ -597,6 +679,8 @@ impl OdomMovement {
             trackers,
             pid,
             arc_pid,
+            lifecycle: LoopHandle::default(),
+            defaults: MotionDefaults::default(),
         }
     }
 }
@@ -0,0 +1,408 @@
+//! Per-motion options for PID and odometry movements.
+//!
+//! Every movement of [`PIDMovement`](super::pid::pid::PIDMovement),
+//! [`ArcPIDMovement`](super::pid::arcpid::ArcPIDMovement) and
+//! [`OdomMovement`](super::odom::OdomMovement) takes a [`MotionOptions`]
+//! instead of positional timeout and delay arguments. Each controller keeps
+//! its own defaults in a [`MotionDefaults`]; `options()` on the controller
+//! returns a copy of them to adjust with the builder methods.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // Change the defaults for every later motion
+//! pid.defaults.set(pid.options().timeout(3000).after_delay(0));
+//!
+//! // Use the defaults
+//! pid.travel(24.0, pid.options()).await;
+//!
+//! // Slower, and move on as soon as it is within 2 inches
+//! pid.travel(-12.0, pid.options().max_power(6.0).early_exit(2.0)).await;
+//!
+//! // Start turning and keep running the intake meanwhile
+//! pid.turn_to_heading(90.0, &imu, pid.options().no_wait()).await;
+//!
+//! // Keep pushing against the goal after arriving
+//! pid.travel(6.0, pid.options().hold()).await;
+//! ```
+
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use log::info;
+use vexide::{task::spawn, time::sleep};
+
+use super::{lifecycle::LoopHandle, pid::pid::TurnDirection};
+
+/// Conditions that end a motion before the controller settles.
+///
+/// Ranges are in the units of the motion: inches for drives and arcs,
+/// degrees for heading turns and swings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExitConditions {
+    /// End the motion once the error is within this range, without waiting
+    /// for the controller tolerance. Useful for chaining motions without
+    /// stopping. `0.0` disables it.
+    pub early_exit:  f64,
+    /// End the motion once the error has changed by less than this range
+    /// for [`stall_time`](Self::stall_time) milliseconds.
+    pub stall_range: f64,
+    /// How long in milliseconds the error must stay within the stall range.
+    /// `0` disables stall detection.
+    pub stall_time:  u64,
+}
+
+impl Default for ExitConditions {
+    fn default() -> Self {
+        Self {
+            early_exit:  0.0,
+            stall_range: 0.0,
+            stall_time:  0,
+        }
+    }
+}
+
+/// Options for a single motion.
+///
+/// [`MotionOptions::default`] gives the library defaults. Controllers hand
+/// out their own defaults through `options()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MotionOptions {
+    /// Maximum time for the motion in milliseconds.
+    pub timeout:     u64,
+    /// Maximum output in volts. The controller's own maximum power still
+    /// applies, so this can only lower it.
+    pub max_power:   f64,
+    /// Minimum output in volts while the error is outside the tolerance, to
+    /// push through friction near the target.
+    pub min_power:   f64,
+    /// Which way heading turns and swings may turn.
+    pub direction:   TurnDirection,
+    /// Whether point motions drive to the point front first. When `false`
+    /// the robot backs into the point.
+    pub forwards:    bool,
+    /// Conditions that end the motion early.
+    pub exit:        ExitConditions,
+    /// Delay after the motion in milliseconds. Only applies when waiting.
+    pub after_delay: u64,
+    /// Whether the call waits for the motion to finish. When `false` the
+    /// call returns once the motion has started and the exit conditions are
+    /// checked in the background.
+    pub wait:        bool,
+    /// Whether the controller keeps driving to the target after the motion
+    /// settles. When `false` the controller releases the drivetrain, so the
+    /// driver or another controller can take it.
+    pub hold:        bool,
+}
+
+impl Default for MotionOptions {
+    fn default() -> Self {
+        Self {
+            timeout:     10000,
+            max_power:   12.0,
+            min_power:   0.0,
+            direction:   TurnDirection::Shortest,
+            forwards:    true,
+            exit:        ExitConditions::default(),
+            after_delay: 10,
+            wait:        true,
+            hold:        false,
+        }
+    }
+}
+
+impl MotionOptions {
+    /// Sets the timeout in milliseconds.
+    pub fn timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum output in volts.
+    pub fn max_power(mut self, max_power: f64) -> Self {
+        self.max_power = max_power;
+        self
+    }
+
+    /// Sets the minimum output in volts while outside the tolerance.
+    pub fn min_power(mut self, min_power: f64) -> Self {
+        self.min_power = min_power;
+        self
+    }
+
+    /// Sets which way heading turns and swings may turn.
+    pub fn direction(mut self, direction: TurnDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Makes point motions back into the point.
+    pub fn backwards(mut self) -> Self {
+        self.forwards = false;
+        self
+    }
+
+    /// Ends the motion once the error is within `range`.
+    pub fn early_exit(mut self, range: f64) -> Self {
+        self.exit.early_exit = range;
+        self
+    }
+
+    /// Ends the motion once the error changes by less than `range` for
+    /// `time` milliseconds.
+    pub fn stall(mut self, range: f64, time: u64) -> Self {
+        self.exit.stall_range = range;
+        self.exit.stall_time = time;
+        self
+    }
+
+    /// Sets the delay after the motion in milliseconds.
+    pub fn after_delay(mut self, after_delay: u64) -> Self {
+        self.after_delay = after_delay;
+        self
+    }
+
+    /// Returns as soon as the motion has started.
+    pub fn no_wait(mut self) -> Self {
+        self.wait = false;
+        self
+    }
+
+    /// Keeps the controller holding the target after the motion settles.
+    pub fn hold(mut self) -> Self {
+        self.hold = true;
+        self
+    }
+
+    /// The `(min, max)` output limits handed to the control loop.
+    pub(crate) fn power(&self) -> (f64, f64) { (self.min_power.abs(), self.max_power.abs()) }
+}
+
+/// A controller's default [`MotionOptions`].
+///
+/// Clones share the same defaults. It also tracks which motion is current,
+/// so a motion running in the background stops checking its exit
+/// conditions once a newer one starts.
+#[derive(Clone)]
+pub struct MotionDefaults {
+    options:    Rc<Cell<MotionOptions>>,
+    generation: Rc<Cell<u64>>,
+}
+
+impl Default for MotionDefaults {
+    fn default() -> Self { Self::new(MotionOptions::default()) }
+}
+
+impl MotionDefaults {
+    /// Creates defaults from a set of options.
+    pub fn new(options: MotionOptions) -> Self {
+        Self {
+            options:    Rc::new(Cell::new(options)),
+            generation: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// Returns the default options.
+    pub fn get(&self) -> MotionOptions { self.options.get() }
+
+    /// Replaces the default options.
+    pub fn set(&self, options: MotionOptions) { self.options.set(options); }
+
+    /// Marks the start of a new motion and returns its id.
+    pub(crate) fn begin(&self) -> u64 {
+        let id = self.generation.get().wrapping_add(1);
+        self.generation.set(id);
+        id
+    }
+
+    /// Returns `true` if no motion has started since `id`.
+    pub(crate) fn is_current(&self, id: u64) -> bool { self.generation.get() == id }
+}
+
+/// Why a motion ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ExitReason {
+    /// The controller reached its tolerance.
+    Settled,
+    /// The error came within the early exit range.
+    EarlyExit,
+    /// The error stopped changing.
+    Stalled,
+    /// The timeout passed.
+    Timeout,
+}
+
+/// Decides when a motion ends from its error over time.
+pub(crate) struct ExitTracker {
+    options: MotionOptions,
+    stall:   Option<(f64, u64)>,
+}
+
+impl ExitTracker {
+    pub(crate) fn new(options: MotionOptions) -> Self {
+        Self {
+            options,
+            stall: None,
+        }
+    }
+
+    /// Feeds the state of the motion `time` milliseconds after it started.
+    /// Returns why it ended, or `None` while it should keep running.
+    pub(crate) fn update(&mut self, time: u64, active: bool, error: f64) -> Option<ExitReason> {
+        let exit = self.options.exit;
+        if !active {
+            return Some(ExitReason::Settled);
+        }
+        if exit.early_exit > 0.0 && error.abs() <= exit.early_exit {
+            return Some(ExitReason::EarlyExit);
+        }
+        if exit.stall_time > 0 {
+            match self.stall {
+                Some((reference, since)) if (error - reference).abs() <= exit.stall_range => {
+                    if time - since >= exit.stall_time {
+                        return Some(ExitReason::Stalled);
+                    }
+                }
+                _ => self.stall = Some((error, time)),
+            }
+        }
+        if time >= self.options.timeout {
+            return Some(ExitReason::Timeout);
+        }
+        None
+    }
+}
+
+/// A running motion whose exit conditions can be checked.
+pub(crate) trait Motion: 'static {
+    /// Returns whether the controller is still moving and the current error
+    /// in the units of the motion.
+    async fn status(&self) -> (bool, f64);
+
+    /// Ends the motion and clears its output limits.
+    async fn end(&self);
+}
+
+/// Waits for a motion to end, or hands the waiting to a background task if
+/// `options.wait` is `false`.
+///
+/// The exit conditions are checked once per period of the controller's
+/// loop.
+pub(crate) async fn run_motion<M: Motion>(
+    motion: M,
+    defaults: &MotionDefaults,
+    lifecycle: &LoopHandle,
+    options: MotionOptions,
+) {
+    let id = defaults.begin();
+    let defaults = defaults.clone();
+    let lifecycle = lifecycle.clone();
+    let waiter = async move {
+        let start = Instant::now();
+        let mut tracker = ExitTracker::new(options);
+        loop {
+            if !defaults.is_current(id) {
+                return;
+            }
+            let (active, error) = motion.status().await;
+            let time = start.elapsed().as_millis() as u64;
+            if let Some(reason) = tracker.update(time, active, error) {
+                if matches!(reason, ExitReason::Timeout | ExitReason::Stalled) {
+                    info!("Motion ended after {} ms: {:?}", time, reason);
+                }
+                break;
+            }
+            sleep(Duration::from_millis(lifecycle.period())).await;
+        }
+        motion.end().await;
+    };
+    if options.wait {
+        waiter.await;
+        sleep(Duration::from_millis(options.after_delay)).await;
+    } else {
+        spawn(waiter).detach();
+    }
+}
+
+/// Applies a motion's minimum output to `u`, which is already capped at
+/// `max`. Zero outputs stay zero.
+pub(crate) fn power_floor(u: f64, min: f64, max: f64) -> f64 {
+    let min = min.min(max.abs());
+    if u != 0.0 && u.abs() < min {
+        min * u.signum()
+    } else {
+        u
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_test() {
+        let options = MotionOptions::default()
+            .timeout(1500)
+            .max_power(-8.0)
+            .min_power(2.0)
+            .backwards()
+            .early_exit(1.0)
+            .no_wait();
+        assert_eq!(options.timeout, 1500);
+        assert_eq!(options.power(), (2.0, 8.0));
+        assert!(!options.forwards);
+        assert!(!options.wait);
+        assert_eq!(options.exit.early_exit, 1.0);
+        // Untouched fields keep their defaults
+        assert_eq!(options.after_delay, 10);
+        assert_eq!(options.direction, TurnDirection::Shortest);
+    }
+
+    #[test]
+    fn defaults_generation_test() {
+        let defaults = MotionDefaults::default();
+        let first = defaults.begin();
+        assert!(defaults.is_current(first));
+        let shared = defaults.clone();
+        shared.set(defaults.get().timeout(500));
+        assert_eq!(defaults.get().timeout, 500);
+        let second = shared.begin();
+        assert!(!defaults.is_current(first));
+        assert!(defaults.is_current(second));
+    }
+
+    #[test]
+    fn exit_tracker_test() {
+        let options = MotionOptions::default().timeout(1000).early_exit(0.5);
+        let mut tracker = ExitTracker::new(options);
+        assert_eq!(tracker.update(0, true, 10.0), None);
+        assert_eq!(tracker.update(5, true, -0.4), Some(ExitReason::EarlyExit));
+        assert_eq!(tracker.update(10, false, 3.0), Some(ExitReason::Settled));
+        assert_eq!(tracker.update(1000, true, 3.0), Some(ExitReason::Timeout));
+    }
+
+    #[test]
+    fn exit_tracker_stall_test() {
+        let options = MotionOptions::default().stall(0.1, 100);
+        let mut tracker = ExitTracker::new(options);
+        assert_eq!(tracker.update(0, true, 5.0), None);
+        assert_eq!(tracker.update(50, true, 4.0), None);
+        // Stuck at 4 inches from the target
+        assert_eq!(tracker.update(100, true, 3.95), None);
+        assert_eq!(tracker.update(149, true, 4.02), None);
+        assert_eq!(tracker.update(150, true, 4.0), Some(ExitReason::Stalled));
+    }
+
+    #[test]
+    fn power_floor_test() {
+        assert_eq!(power_floor(0.5, 2.0, 12.0), 2.0);
+        assert_eq!(power_floor(-0.5, 2.0, 12.0), -2.0);
+        assert_eq!(power_floor(5.0, 2.0, 12.0), 5.0);
+        assert_eq!(power_floor(0.0, 2.0, 12.0), 0.0);
+        // The floor never exceeds the cap
+        assert_eq!(power_floor(0.5, 4.0, 3.0), 3.0);
+    }
+}
@@ -25,26 +25,34 @@
 //! arc_pid.init();
 //!
 //! // Travel in a curve: positive offset = curve right
-//! arc_pid.travel(24.0, 0.5, 2000, 100).await;
+//! arc_pid.travel(24.0, 0.5, arc_pid.options()).await;
 //! ```
 
-use std::{f64::consts::PI, sync::Arc, time::Duration};
+use std::sync::Arc;
 
 use log::info;
 use vexide::{smart::motor::BrakeMode, sync::Mutex, task::*, time::*};
 
 use crate::{
-    motion::pid::DrivetrainConfig,
-    peripherals::drivetrain::{self, Differential},
+    motion::{
+        lifecycle::{LoopCommand, LoopHandle},
+        options::{Motion, MotionDefaults, MotionOptions, power_floor, run_motion},
+        pid::{
+            DrivetrainConfig,
+            autotune::drivetrain_position,
+            telemetry::{Telemetry, TickRecord},
+        },
+        timing::LoopTimer,
+    },
+    peripherals::drivetrain::{self, Differential, DriveOwner},
     to_mutex,
 };
 
-/// Loop rate for the Arc PID control task in milliseconds.
-const LOOPRATE: u64 = 5;
-
 async fn arcpid_loop(
     arcpidvalues: &Arc<Mutex<ArcPIDValues>>,
     drivetrain: drivetrain::Differential,
+    lifecycle: LoopHandle,
+    telemetry: Telemetry,
 ) {
     info!("ArcPID Control Loop Started");
     // Set brake mode and reset positions for left motors
@@ -68,16 +76,58 @@ async fn arcpid_loop(
     }
 
     let mut perror = 0.0;
+    let mut was_active = false;
 
-    // seconds per loop from configured looprate in ms
-    let dt = (LOOPRATE as f64) / 1000.0;
+    let mut timer = LoopTimer::new("ArcPID Control Loop", &lifecycle);
+    let mut paused = false;
 
     loop {
-        let (target, offset, pwr, kp, kd, tolerance) = {
+        // Seconds since the previous tick
+        let dt = timer.tick();
+        match lifecycle.poll() {
+            LoopCommand::Shutdown => break,
+            LoopCommand::Paused => {
+                if !paused {
+                    drivetrain.set_voltages(DriveOwner::ArcPid, 0.0, 0.0);
+                    paused = true;
+                }
+                timer.wait().await;
+                continue;
+            }
+            LoopCommand::Restart => {
+                info!("ArcPID Control Loop Restarted");
+                drivetrain.reset_position();
+                {
+                    let mut s = arcpidvalues.lock().await;
+                    s.target = 0.0;
+                    s.active = false;
+                }
+                perror = 0.0;
+            }
+            LoopCommand::Run => {}
+        }
+        paused = false;
+
+        let (target, offset, (minpwr, pwr), kp, kd, tolerance, hold, active) = {
             let s = arcpidvalues.lock().await;
-            (s.target, s.offset, s.maxpwr, s.kp, s.kd, s.tolerance)
+            (
+                s.target,
+                s.offset,
+                (s.motion_power.0, s.maxpwr.abs().min(s.motion_power.1)),
+                s.kp,
+                s.kd,
+                s.tolerance,
+                s.hold,
+                s.active,
+            )
         };
 
+        let now = user_uptime().as_millis() as u64;
+        if active && !was_active {
+            telemetry.begin_motion(now);
+        }
+        was_active = active;
+
         let currs_left = {
             let mut left_motors = drivetrain.left.borrow_mut();
             let left_slice = left_motors.as_mut();
@@ -99,6 +149,27 @@ async fn arcpid_loop(
         };
 
         let currs = (currs_left + currs_right) / 2.0;
+
+        // While another owner drives, don't write to the motors and keep the
+        // target on the current position so later moves start from here.
+        if !drivetrain.is_owner(DriveOwner::ArcPid) {
+            {
+                let mut s = arcpidvalues.lock().await;
+                s.target = currs;
+            }
+            perror = 0.0;
+            timer.wait().await;
+            continue;
+        }
+
+        // Once the movement has settled, hand the drivetrain back unless it
+        // asked to hold its position.
+        if !active && !hold {
+            drivetrain.release(DriveOwner::ArcPid);
+            timer.wait().await;
+            continue;
+        }
+
         let error = target - currs;
 
         let u;
@@ -107,18 +178,20 @@ async fn arcpid_loop(
 
         let derror = (error - perror) / dt;
 
-        u = kp * error + kd * derror;
+        u = if error.abs() >= tolerance {
+            power_floor(abscap(kp * error + kd * derror, pwr), minpwr, pwr)
+        } else {
+            kp * error + kd * derror
+        };
 
-        if offset > 0.0 {
-            u_left = abscap(u, pwr.abs());
-            u_right = abscap(u * offset.abs(), pwr.abs());
+        // Scale both sides together so the arc keeps its curvature
+        (u_left, u_right) = if offset > 0.0 {
+            drivetrain.desaturate(u, u * offset.abs(), pwr)
         } else if offset < 0.0 {
-            u_left = abscap(u * offset.abs(), pwr.abs());
-            u_right = abscap(u, pwr.abs());
+            drivetrain.desaturate(u * offset.abs(), u, pwr)
         } else {
-            u_left = abscap(u, pwr.abs());
-            u_right = abscap(u, pwr.abs());
-        }
+            drivetrain.desaturate(u, u, pwr)
+        };
         // Set voltage for left motors
         {
             let mut left_motors = drivetrain.left.borrow_mut();
@@ -160,9 +233,25 @@ async fn arcpid_loop(
                 }
             }
         }
+        telemetry.record(TickRecord {
+            time: now,
+            channel: "arc",
+            target,
+            measurement: currs,
+            error,
+            p: kp * error,
+            i: 0.0,
+            d: kd * derror,
+            output: if in_band { 0.0 } else { abscap(u, pwr.abs()) },
+            tolerance,
+        });
         perror = error;
-        sleep(Duration::from_millis(LOOPRATE)).await;
+        timer.wait().await;
     }
+
+    drivetrain.release(DriveOwner::ArcPid);
+    lifecycle.stopped();
+    info!("ArcPID Control Loop Stopped");
 }
 
 impl ArcPIDMovement {
@@ -170,21 +259,34 @@ impl ArcPIDMovement {
     /// The ArcPID movements will require a ArcPID loop to run as a seperate task or thread.
     /// It is necessary to initialize the ArcPID before running any movements.
     ///
+    /// Returns a [`LoopHandle`] for pausing, restarting or shutting down the
+    /// loop. Calling `init` again while the loop is running returns the same
+    /// handle without spawning a second loop.
+    ///
     /// # Examples
     /// ```
     /// async fn auton(arcpid: ArcPIDMovement) {
     ///     arcpid.init(); // Initialize the ArcPID before any movements
     ///     arcpid.set_maximum_power(12.0).await;
-    ///     arcpid.travel(100.0, 1000, 10).await;
+    ///     arcpid
+    ///         .travel(100.0, 0.0, arcpid.options().timeout(1000))
+    ///         .await;
     /// }
     /// ```
-    pub fn init(&self) {
+    pub fn init(&self) -> LoopHandle {
+        if !self.lifecycle.start() {
+            info!("ArcPID Control Loop Already Running");
+            return self.lifecycle.clone();
+        }
         let mutex_clone = self.arcpid_values.clone();
         let drivetrain = self.drivetrain.clone();
+        let lifecycle = self.lifecycle.clone();
+        let telemetry = self.telemetry.clone();
         let mainloop = spawn(async move {
-            arcpid_loop(&mutex_clone, drivetrain).await;
+            arcpid_loop(&mutex_clone, drivetrain, lifecycle, telemetry).await;
         });
         mainloop.detach();
+        self.lifecycle.clone()
     }
 
     /// Set the tolerance, Kp, and Kd Values for ArcPD. The values are in radians.
@@ -202,48 +304,48 @@ impl ArcPIDMovement {
         arcpid_values.maxpwr = maximum_power;
     }
 
+    /// Returns the controller's default [`MotionOptions`], to pass to a
+    /// motion as they are or adjusted with the builder methods.
+    pub fn options(&self) -> MotionOptions { self.defaults.get() }
+
     /// Makes the robot travel in an arc or straight line
-    pub async fn travel(&self, distance: f64, offset: f64, timeout: u64, afterdelay: u64) {
-        let r = (distance *
-            (self.drivetrain_config.driving_gear / self.drivetrain_config.driven_gear) *
-            2.0 *
-            PI) /
-            self.drivetrain_config.wheel_diameter;
-        let mut s = self.arcpid_values.lock().await;
-        s.active = true;
-        s.target += r;
-        s.offset = offset;
-        timeout_wait(&self.arcpid_values, timeout).await;
+    pub async fn travel(&self, distance: f64, offset: f64, options: MotionOptions) {
+        let ratio = self.drivetrain_config.distance_to_angle(1.0);
         {
             let mut s = self.arcpid_values.lock().await;
-            s.active = false;
+            self.drivetrain.acquire(DriveOwner::ArcPid);
+            s.active = true;
+            s.hold = options.hold;
+            s.motion_power = options.power();
+            s.target += distance * ratio;
+            s.offset = offset;
         }
-        sleep(Duration::from_millis(afterdelay)).await;
+        let motion = ArcMotion {
+            values: self.arcpid_values.clone(),
+            drivetrain: self.drivetrain.clone(),
+            ratio,
+        };
+        run_motion(motion, &self.defaults, &self.lifecycle, options).await;
     }
 
     pub async fn abs_travel(&self, distance: f64, offset: f64) {
-        let r = (distance *
-            (self.drivetrain_config.driving_gear / self.drivetrain_config.driven_gear) *
-            2.0 *
-            PI) /
-            self.drivetrain_config.wheel_diameter;
+        let r = self.drivetrain_config.distance_to_angle(distance);
         let mut s = self.arcpid_values.lock().await;
+        self.drivetrain.acquire(DriveOwner::ArcPid);
         s.active = true;
+        s.hold = false;
         s.target = r;
         s.offset = offset;
     }
 
     pub async fn local_coords(&self, x: f64, y: f64) {
-        let track_width = self.drivetrain_config.track_width;
-        let offset;
         let (radius, angle) = get_arc(x, y);
-        if angle > 0.0 {
-            offset = ((2.0 * radius) - track_width) / ((2.0 * radius) + track_width);
-        } else if angle < 0.0 {
-            offset = ((2.0 * radius) + track_width) / ((2.0 * radius) - track_width);
+        let offset = if angle == 0.0 {
+            0.0
         } else {
-            offset = 0.0;
-        }
+            self.drivetrain_config
+                .curvature_to_ratio(angle.signum() / radius)
+        };
         let distance = radius * angle;
         self.abs_travel(distance, offset).await;
     }
@@ -282,11 +384,15 @@ impl ArcPIDMovement {
 ///         active:       true,
 ///         target_left:  0.0,
 ///         target_right: 0.0,
+///         motion_power: (0.0, 12.0),
 ///     };
 ///     let ArcPD_controller = ArcPIDMovement {
 ///         drivetrain:        dt,
 ///         drivetrain_config: config,
 ///         arcpid_values:     Arc::new(Mutex::new(values)),
+///         lifecycle:         LoopHandle::default(),
+///         telemetry:         Telemetry::default(),
+///         defaults:          MotionDefaults::default(),
 ///     };
 /// }
 /// ```
@@ -295,6 +401,12 @@ pub struct ArcPIDMovement {
     pub drivetrain:        Differential,
     pub drivetrain_config: DrivetrainConfig,
     pub arcpid_values:     Arc<Mutex<ArcPIDValues>>,
+    /// Handle to the background loop started by [`init`](ArcPIDMovement::init).
+    pub lifecycle:         LoopHandle,
+    /// Per-tick recording of the control loop, disabled by default.
+    pub telemetry:         Telemetry,
+    /// Default options for motions, see [`options`](ArcPIDMovement::options).
+    pub defaults:          MotionDefaults,
 }
 
 impl ArcPIDMovement {
@@ -307,6 +419,9 @@ impl ArcPIDMovement {
             drivetrain:        dt,
             drivetrain_config: dt_config,
             arcpid_values:     to_mutex(arcpid_values),
+            lifecycle:         LoopHandle::default(),
+            telemetry:         Telemetry::default(),
+            defaults:          MotionDefaults::default(),
         }
     }
 }
@@ -318,38 +433,50 @@ impl ArcPIDMovement {
 #[derive(Clone, Copy)]
 pub struct ArcPIDValues {
     /// Proportional gain for the PD controller.
-    pub kp:        f64,
+    pub kp:           f64,
     /// Derivative gain for the PD controller.
-    pub kd:        f64,
+    pub kd:           f64,
     /// Error tolerance in radians.
     ///
     /// Movement completes when error is below this value.
-    pub tolerance: f64,
+    pub tolerance:    f64,
     /// Maximum motor voltage (0-12 volts).
-    pub maxpwr:    f64,
+    pub maxpwr:       f64,
     /// Whether a movement is currently active.
-    pub active:    bool,
+    pub active:       bool,
     /// Target motor position in radians.
-    pub target:    f64,
+    pub target:       f64,
     /// Curvature offset for arc movements.
     ///
     /// - Positive values: curve right (left motors faster).
     /// - Negative values: curve left (right motors faster).
     /// - Zero: straight line movement.
-    pub offset:    f64,
+    pub offset:       f64,
+    /// Whether the loop keeps holding the target once the movement settles.
+    ///
+    /// Set from the motion's [`MotionOptions`]. When `false` the loop
+    /// releases the drivetrain after the movement.
+    pub hold:         bool,
+    /// Minimum and maximum output in volts of the current motion.
+    ///
+    /// Set from the motion's [`MotionOptions`]. The output is capped at the
+    /// smaller of this maximum and `maxpwr`.
+    pub motion_power: (f64, f64),
 }
 
 impl ArcPIDValues {
     /// Uses default ArcPID Values
     pub fn default() -> ArcPIDValues {
         ArcPIDValues {
-            kp:        0.5,
-            kd:        0.0,
-            tolerance: 0.1,
-            maxpwr:    12.0,
-            active:    true,
-            target:    0.0,
-            offset:    0.0,
+            kp:           0.5,
+            kd:           0.0,
+            tolerance:    0.1,
+            maxpwr:       12.0,
+            active:       true,
+            target:       0.0,
+            offset:       0.0,
+            hold:         false,
+            motion_power: NO_POWER_LIMITS,
         }
     }
 
@@ -362,28 +489,33 @@ impl ArcPIDValues {
             active: true,
             target: 0.0,
             offset: 0.0,
+            hold: false,
+            motion_power: NO_POWER_LIMITS,
         }
     }
 }
 
-async fn timeout_wait(arcpid_values: &Arc<Mutex<ArcPIDValues>>, timeout: u64) {
-    let start_time = user_uptime().as_millis();
+/// Output limits that leave the controller's own maximum power in charge.
+const NO_POWER_LIMITS: (f64, f64) = (0.0, 12.0);
 
-    loop {
-        {
-            let s = arcpid_values.lock().await;
-            if !s.active {
-                break;
-            }
-        }
+/// An arc or straight drive measured on the averaged drivetrain encoders.
+struct ArcMotion {
+    values:     Arc<Mutex<ArcPIDValues>>,
+    drivetrain: Differential,
+    ratio:      f64,
+}
 
-        if user_uptime().as_millis() >= start_time + timeout as u128 {
-            let mut s = arcpid_values.lock().await;
-            s.active = false;
-            break;
-        }
+impl Motion for ArcMotion {
+    async fn status(&self) -> (bool, f64) {
+        let position = drivetrain_position(&self.drivetrain);
+        let s = self.values.lock().await;
+        (s.active, (s.target - position) / self.ratio)
+    }
 
-        sleep(Duration::from_millis(LOOPRATE)).await;
+    async fn end(&self) {
+        let mut s = self.values.lock().await;
+        s.active = false;
+        s.motion_power = NO_POWER_LIMITS;
     }
 }
 
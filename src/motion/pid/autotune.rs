@@ -0,0 +1,500 @@
+//! Relay-feedback PID autotuning.
+//!
+//! This module finds PID gains automatically by running a relay (bang-bang)
+//! experiment: the output is switched between `+amplitude` and `-amplitude`
+//! whenever the measurement crosses the setpoint. The mechanism settles into a
+//! steady oscillation whose amplitude and period give the ultimate gain `Ku`
+//! and ultimate period `Tu` of the system. Gains are then proposed from these
+//! using a [`TuningRule`].
+//!
+//! # Supported Mechanisms
+//!
+//! - [`PIDMovement::autotune`]: Linear drivetrain movement (encoder radians).
+//! - [`PIDMovement::autotune_angular`]: Heading turns (degrees).
+//! - [`SinglePIDMovement::autotune`]: A single motor group (encoder radians).
+//!
+//! # Example
+//!
+//! ```ignore
+//! use antaeus::motion::pid::autotune::{AutotuneConfig, TuningRule};
+//!
+//! // Run before `init`, the experiment drives the motors directly.
+//! let config = AutotuneConfig {
+//!     rule: TuningRule::TyreusLuyben,
+//!     ..AutotuneConfig::default()
+//! };
+//! if let Some(gains) = pid.autotune(config, true).await {
+//!     info!("kp={} ki={} kd={}", gains.kp, gains.ki, gains.kd);
+//! }
+//! pid.init();
+//! ```
+
+use std::{cell::RefCell, f64::consts::PI, rc::Rc, time::Duration};
+
+use log::{info, warn};
+use vexide::{smart::motor::Motor, time::*};
+
+use crate::{
+    motion::pid::{
+        pid::{HeadingSource, PIDMovement, TurnDirection, heading_error, read_heading},
+        singlepid::SinglePIDMovement,
+    },
+    peripherals::drivetrain::{Differential, DriveOwner},
+};
+
+/// Rule used to turn the ultimate gain and period into PID gains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TuningRule {
+    /// Classic Ziegler–Nichols. Fast response with noticeable overshoot.
+    ZieglerNichols,
+    /// Tyreus–Luyben. More conservative than Ziegler–Nichols with less
+    /// oscillation, well suited to drivetrains.
+    TyreusLuyben,
+    /// Ziegler–Nichols "no overshoot" variant. Slowest, but avoids
+    /// overshooting the target.
+    NoOvershoot,
+}
+
+/// Configuration for a relay autotuning experiment.
+#[derive(Clone, Copy, Debug)]
+pub struct AutotuneConfig {
+    /// Relay output in volts. The motors are driven at `±amplitude`.
+    pub amplitude:  f64,
+    /// Hysteresis band around the setpoint, in the units of the measurement.
+    ///
+    /// A small band prevents sensor noise from switching the relay early.
+    pub hysteresis: f64,
+    /// Number of oscillation periods to measure after the first one.
+    pub cycles:     usize,
+    /// Maximum duration of the experiment in milliseconds.
+    pub timeout:    u64,
+    /// The rule used to propose gains.
+    pub rule:       TuningRule,
+}
+
+impl Default for AutotuneConfig {
+    fn default() -> Self {
+        Self {
+            amplitude:  6.0,
+            hysteresis: 0.01,
+            cycles:     4,
+            timeout:    10000,
+            rule:       TuningRule::TyreusLuyben,
+        }
+    }
+}
+
+/// The ultimate gain and period measured by a relay experiment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RelayResult {
+    /// Ultimate gain `Ku`, in volts per unit of measurement.
+    pub ultimate_gain:   f64,
+    /// Ultimate period `Tu` in seconds.
+    pub ultimate_period: f64,
+}
+
+/// PID gains proposed by the autotuner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TunedGains {
+    /// Proportional gain.
+    pub kp: f64,
+    /// Integral gain.
+    pub ki: f64,
+    /// Derivative gain.
+    pub kd: f64,
+}
+
+impl RelayResult {
+    /// Proposes PID gains for this result using the given rule.
+    pub fn gains(&self, rule: TuningRule) -> TunedGains {
+        let ku = self.ultimate_gain;
+        let tu = self.ultimate_period;
+        // (Kp, Ti, Td) for each rule, converted to parallel form below.
+        let (kp, ti, td) = match rule {
+            TuningRule::ZieglerNichols => (0.6 * ku, tu / 2.0, tu / 8.0),
+            TuningRule::TyreusLuyben => (ku / 2.2, 2.2 * tu, tu / 6.3),
+            TuningRule::NoOvershoot => (0.2 * ku, tu / 2.0, tu / 3.0),
+        };
+        TunedGains {
+            kp,
+            ki: kp / ti,
+            kd: kp * td,
+        }
+    }
+}
+
+/// The relay controller and oscillation analysis.
+///
+/// This holds no hardware and can be driven by any plant: call
+/// [`update`](RelayFeedback::update) once per tick with the current time and
+/// measurement, apply the returned output, and stop once
+/// [`is_finished`](RelayFeedback::is_finished) returns `true`.
+pub struct RelayFeedback {
+    setpoint:     f64,
+    amplitude:    f64,
+    hysteresis:   f64,
+    cycles:       usize,
+    output:       f64,
+    switch_times: Vec<f64>,
+    peak:         f64,
+    trough:       f64,
+    maxima:       Vec<f64>,
+    minima:       Vec<f64>,
+}
+
+impl RelayFeedback {
+    /// Creates a relay around `setpoint` with the given output amplitude,
+    /// hysteresis band and number of periods to measure.
+    pub fn new(setpoint: f64, amplitude: f64, hysteresis: f64, cycles: usize) -> Self {
+        Self {
+            setpoint,
+            amplitude: amplitude.abs(),
+            hysteresis: hysteresis.abs(),
+            cycles: cycles.max(1),
+            output: amplitude.abs(),
+            switch_times: Vec::new(),
+            peak: f64::MIN,
+            trough: f64::MAX,
+            maxima: Vec::new(),
+            minima: Vec::new(),
+        }
+    }
+
+    /// Feeds a measurement taken at `time` seconds and returns the relay output.
+    pub fn update(&mut self, time: f64, measurement: f64) -> f64 {
+        if self.output > 0.0 {
+            self.trough = self.trough.min(measurement);
+            if measurement > self.setpoint + self.hysteresis {
+                if !self.switch_times.is_empty() {
+                    self.minima.push(self.trough);
+                }
+                self.switch_times.push(time);
+                self.output = -self.amplitude;
+                self.peak = measurement;
+            }
+        } else {
+            self.peak = self.peak.max(measurement);
+            if measurement < self.setpoint - self.hysteresis {
+                self.maxima.push(self.peak);
+                self.output = self.amplitude;
+                self.trough = measurement;
+            }
+        }
+        self.output
+    }
+
+    /// Whether enough oscillation periods have been recorded.
+    ///
+    /// The first period is discarded as a transient.
+    pub fn is_finished(&self) -> bool { self.switch_times.len() >= self.cycles + 2 }
+
+    /// Computes the ultimate gain and period from the recorded oscillation.
+    ///
+    /// Returns `None` if fewer than two periods were recorded or the
+    /// oscillation did not leave the hysteresis band.
+    pub fn result(&self) -> Option<RelayResult> {
+        if self.switch_times.len() < 3 || self.maxima.len() < 2 || self.minima.len() < 2 {
+            return None;
+        }
+        let periods: Vec<f64> = self
+            .switch_times
+            .windows(2)
+            .skip(1)
+            .map(|w| w[1] - w[0])
+            .collect();
+        let ultimate_period = mean(&periods);
+        let amplitude = (mean(&self.maxima[1..]) - mean(&self.minima[1..])) / 2.0;
+        if amplitude <= self.hysteresis || ultimate_period <= 0.0 {
+            return None;
+        }
+        let ultimate_gain =
+            4.0 * self.amplitude / (PI * (amplitude.powi(2) - self.hysteresis.powi(2)).sqrt());
+        Some(RelayResult {
+            ultimate_gain,
+            ultimate_period,
+        })
+    }
+}
+
+impl PIDMovement {
+    /// Runs a relay experiment on straight-line drivetrain movement and
+    /// proposes gains for [`PIDValues`](super::pid::PIDValues).
+    ///
+    /// The robot rocks back and forth around its starting position. The
+    /// experiment drives the motors directly, so run it before
+    /// [`init`](PIDMovement::init).
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Relay amplitude, hysteresis (radians), cycles and rule.
+    /// * `apply` - Whether to write the proposed gains into the controller.
+    ///
+    /// # Returns
+    ///
+    /// The proposed gains, or `None` if no stable oscillation was measured.
+    pub async fn autotune(&self, config: AutotuneConfig, apply: bool) -> Option<TunedGains> {
+        let drivetrain = self.drivetrain.clone();
+        drivetrain.acquire(DriveOwner::Manual);
+        let setpoint = drivetrain_position(&drivetrain);
+        let result = run_relay(
+            config,
+            self.lifecycle.period(),
+            setpoint,
+            |u| set_drivetrain(&drivetrain, u, u),
+            || {
+                let position = drivetrain_position(&drivetrain);
+                async move { position }
+            },
+        )
+        .await;
+        drivetrain.release(DriveOwner::Manual);
+        let gains = result.map(|r| r.gains(config.rule));
+        if let Some(gains) = gains {
+            log_gains("PID", &gains);
+            if apply {
+                let mut s = self.pid_values.lock().await;
+                s.kp = gains.kp;
+                s.ki = gains.ki;
+                s.kd = gains.kd;
+            }
+        }
+        gains
+    }
+
+    /// Runs a relay experiment on turning in place and proposes gains for
+    /// [`AngularPIDValues`](super::pid::AngularPIDValues).
+    ///
+    /// The robot rocks left and right around its starting heading. The
+    /// experiment drives the motors directly, so run it before
+    /// [`init`](PIDMovement::init).
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Where the current heading is read from.
+    /// * `config` - Relay amplitude, hysteresis (degrees), cycles and rule.
+    /// * `apply` - Whether to write the proposed gains into the controller.
+    pub async fn autotune_angular(
+        &self,
+        source: &HeadingSource,
+        config: AutotuneConfig,
+        apply: bool,
+    ) -> Option<TunedGains> {
+        let drivetrain = self.drivetrain.clone();
+        drivetrain.acquire(DriveOwner::Manual);
+        let start = read_heading(source).await;
+        let result = run_relay(
+            config,
+            self.lifecycle.period(),
+            0.0,
+            |u| set_drivetrain(&drivetrain, u, -u),
+            || async { heading_error(read_heading(source).await, start, TurnDirection::Shortest) },
+        )
+        .await;
+        drivetrain.release(DriveOwner::Manual);
+        let gains = result.map(|r| r.gains(config.rule));
+        if let Some(gains) = gains {
+            log_gains("Angular PID", &gains);
+            if apply {
+                let mut s = self.angular_values.lock().await;
+                s.kp = gains.kp;
+                s.ki = gains.ki;
+                s.kd = gains.kd;
+            }
+        }
+        gains
+    }
+}
+
+impl SinglePIDMovement {
+    /// Runs a relay experiment on the motor group and proposes gains for
+    /// [`SinglePIDValues`](super::singlepid::SinglePIDValues).
+    ///
+    /// The mechanism oscillates around its current position, so make sure it
+    /// has room to move in both directions. The experiment drives the motors
+    /// directly, so run it before [`init`](SinglePIDMovement::init).
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Relay amplitude, hysteresis (radians), cycles and rule.
+    /// * `apply` - Whether to write the proposed gains into the controller.
+    pub async fn autotune(&self, config: AutotuneConfig, apply: bool) -> Option<TunedGains> {
+        let motorgroup = self.motorgroup.clone();
+        let setpoint = group_position(&motorgroup);
+        let result = run_relay(
+            config,
+            self.lifecycle.period(),
+            setpoint,
+            |u| set_group(&motorgroup, u),
+            || {
+                let position = group_position(&motorgroup);
+                async move { position }
+            },
+        )
+        .await;
+        let gains = result.map(|r| r.gains(config.rule));
+        if let Some(gains) = gains {
+            log_gains("Single PID", &gains);
+            if apply {
+                let mut s = self.pid_values.lock().await;
+                s.kp = gains.kp;
+                s.ki = gains.ki;
+                s.kd = gains.kd;
+            }
+        }
+        gains
+    }
+}
+
+/// Runs the relay experiment, taking a sample every `period` milliseconds.
+async fn run_relay<O, M, F>(
+    config: AutotuneConfig,
+    period: u64,
+    setpoint: f64,
+    mut output: O,
+    mut measure: M,
+) -> Option<RelayResult>
+where
+    O: FnMut(f64),
+    M: FnMut() -> F,
+    F: Future<Output = f64>,
+{
+    info!("Relay Autotune Started");
+    let mut relay =
+        RelayFeedback::new(setpoint, config.amplitude, config.hysteresis, config.cycles);
+    let start_time = user_uptime().as_millis();
+    loop {
+        let elapsed = user_uptime().as_millis() - start_time;
+        let u = relay.update(elapsed as f64 / 1000.0, measure().await);
+        output(u);
+        if relay.is_finished() {
+            break;
+        }
+        if elapsed >= config.timeout as u128 {
+            warn!("Relay Autotune timed out before the oscillation settled");
+            break;
+        }
+        sleep(Duration::from_millis(period)).await;
+    }
+    output(0.0);
+    let result = relay.result();
+    match result {
+        Some(r) => info!(
+            "Relay Autotune: Ku = {}, Tu = {}s",
+            r.ultimate_gain, r.ultimate_period
+        ),
+        None => warn!("Relay Autotune could not measure an oscillation"),
+    }
+    result
+}
+
+fn log_gains(name: &str, gains: &TunedGains) {
+    info!(
+        "{} Autotune: kp = {}, ki = {}, kd = {}",
+        name, gains.kp, gains.ki, gains.kd
+    );
+}
+
+pub(crate) fn drivetrain_position(drivetrain: &Differential) -> f64 {
+    (group_position(&drivetrain.left) + group_position(&drivetrain.right)) / 2.0
+}
+
+fn set_drivetrain(drivetrain: &Differential, left: f64, right: f64) {
+    set_group(&drivetrain.left, left);
+    set_group(&drivetrain.right, right);
+}
+
+pub(crate) fn group_position(motorgroup: &Rc<RefCell<dyn AsMut<[Motor]>>>) -> f64 {
+    let mut motors = motorgroup.borrow_mut();
+    let slice = motors.as_mut();
+    let sum: f64 = slice
+        .iter()
+        .map(|motor| motor.position().unwrap_or_default().as_radians())
+        .sum();
+    sum / slice.len() as f64
+}
+
+fn set_group(motorgroup: &Rc<RefCell<dyn AsMut<[Motor]>>>, voltage: f64) {
+    let mut motors = motorgroup.borrow_mut();
+    for motor in motors.as_mut().iter_mut() {
+        let _ = motor.set_voltage(voltage);
+    }
+}
+
+fn mean(values: &[f64]) -> f64 { values.iter().sum::<f64>() / values.len() as f64 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Integrating plant with a first-order velocity lag and a dead time,
+    /// similar to a motor driving a mechanism.
+    fn simulate(relay: &mut RelayFeedback, gain: f64, tau: f64, delay: f64) {
+        let dt = 0.005;
+        let mut buffer = vec![0.0; (delay / dt).round() as usize];
+        let (mut position, mut velocity) = (0.0, 0.0);
+        for i in 0..4000 {
+            buffer.push(relay.update(i as f64 * dt, position));
+            let u = buffer.remove(0);
+            velocity += (gain * u - velocity) / tau * dt;
+            position += velocity * dt;
+            if relay.is_finished() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn relay_simulated_plant_test() {
+        let (gain, tau, delay) = (3.0, 0.15, 0.02);
+        let mut relay = RelayFeedback::new(0.0, 6.0, 0.005, 4);
+        simulate(&mut relay, gain, tau, delay);
+        assert!(relay.is_finished());
+
+        // Phase crossover of K·e^(-Ls) / (s(τs + 1)): atan(ωτ) + ωL = π/2
+        let (mut lo, mut hi) = (0.01, 1000.0);
+        for _ in 0..200 {
+            let w = (lo + hi) / 2.0;
+            if (w * tau).atan() + w * delay - PI / 2.0 > 0.0 {
+                hi = w;
+            } else {
+                lo = w;
+            }
+        }
+        let ku = lo * (1.0 + (lo * tau).powi(2)).sqrt() / gain;
+        let tu = 2.0 * PI / lo;
+
+        let result = relay.result().unwrap();
+        assert!((result.ultimate_gain - ku).abs() / ku < 0.05);
+        assert!((result.ultimate_period - tu).abs() / tu < 0.05);
+    }
+
+    #[test]
+    fn relay_no_oscillation_test() {
+        let mut relay = RelayFeedback::new(0.0, 6.0, 0.01, 4);
+        for i in 0..100 {
+            relay.update(i as f64 * 0.005, -1.0);
+        }
+        assert!(!relay.is_finished());
+        assert_eq!(relay.result(), None);
+    }
+
+    #[test]
+    fn tuning_rule_test() {
+        let result = RelayResult {
+            ultimate_gain:   10.0,
+            ultimate_period: 0.5,
+        };
+        let zn = result.gains(TuningRule::ZieglerNichols);
+        assert!((zn.kp - 6.0).abs() < 1e-9);
+        assert!((zn.ki - 24.0).abs() < 1e-9);
+        assert!((zn.kd - 0.375).abs() < 1e-9);
+
+        let tl = result.gains(TuningRule::TyreusLuyben);
+        assert!((tl.kp - 10.0 / 2.2).abs() < 1e-9);
+        assert!((tl.ki - (10.0 / 2.2) / 1.1).abs() < 1e-9);
+
+        let no = result.gains(TuningRule::NoOvershoot);
+        assert!((no.kp - 2.0).abs() < 1e-9);
+        assert!(no.kp < tl.kp && tl.kp < zn.kp);
+    }
+}
@@ -0,0 +1,305 @@
+//! Homing routines for single motor group mechanisms.
+//!
+//! [`SinglePIDMovement`] measures positions relative to wherever the encoders
+//! were zeroed. If an arm is left raised when the program starts, every
+//! target is off by that amount. Homing drives the mechanism slowly into a
+//! known stop, detects it with a limit switch or a stall, and zeroes the
+//! encoders there (plus an offset).
+//!
+//! # Triggers
+//!
+//! - [`HomingTrigger::Switch`]: An ADI limit switch or bumper, which reads
+//!   high when pressed.
+//! - [`HomingTrigger::Stall`]: The motors draw high current while barely
+//!   moving for a set amount of time.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use antaeus::motion::pid::homing::{HomingConfig, HomingTrigger};
+//!
+//! let config = HomingConfig {
+//!     trigger: HomingTrigger::Switch(AdiDigitalIn::new(peripherals.adi_a)),
+//!     // The stop is 30 degrees below horizontal, so zero is horizontal
+//!     offset: -30.0,
+//!     ..HomingConfig::default()
+//! };
+//!
+//! // Home before starting the PID loop
+//! arm.home(&config).await?;
+//!
+//! // Angles from here on are measured from horizontal
+//! arm.set_gravity(GravityCompensation::Arm { kg: 1.2, zero_angle: 0.0 }).await;
+//! arm.set_soft_limits(Some((-30.0, 110.0))).await;
+//! arm.init();
+//! ```
+
+use std::time::Duration;
+
+use log::{info, warn};
+use vexide::{
+    adi::digital::{AdiDigitalIn, LogicLevel},
+    math::Angle,
+    smart::PortError,
+    time::*,
+};
+
+use super::singlepid::SinglePIDMovement;
+
+/// How a homing routine detects that the mechanism reached its stop.
+pub enum HomingTrigger {
+    /// An ADI limit switch or bumper, pressed when it reads high.
+    Switch(AdiDigitalIn),
+    /// A current/velocity stall.
+    Stall {
+        /// Average motor current in amps at or above which the motors count
+        /// as pushing against the stop.
+        current:  f64,
+        /// Average motor velocity in RPM at or below which the motors count
+        /// as stopped.
+        velocity: f64,
+        /// How long in milliseconds both conditions must hold.
+        duration: u64,
+    },
+}
+
+/// Configuration for [`SinglePIDMovement::home`].
+pub struct HomingConfig {
+    /// Voltage to drive toward the stop with. The sign sets the direction.
+    pub voltage: f64,
+    /// What detects the stop.
+    pub trigger: HomingTrigger,
+    /// Output-shaft angle in degrees of the mechanism at the stop.
+    ///
+    /// The encoders are set to this position once homed.
+    pub offset:  f64,
+    /// Time in milliseconds to ignore stalls for while the motors spin up.
+    pub grace:   u64,
+    /// Time in milliseconds to give up after.
+    pub timeout: u64,
+}
+
+impl Default for HomingConfig {
+    fn default() -> Self {
+        Self {
+            voltage: -3.0,
+            trigger: HomingTrigger::Stall {
+                current:  1.5,
+                velocity: 5.0,
+                duration: 150,
+            },
+            offset:  0.0,
+            grace:   300,
+            timeout: 3000,
+        }
+    }
+}
+
+/// Errors returned when homing fails.
+#[derive(Debug)]
+pub enum HomingError {
+    /// The stop was not found before the timeout.
+    Timeout,
+    /// The limit switch could not be read.
+    Switch(PortError),
+    /// The PID loop is running and would fight the homing routine for the
+    /// motors.
+    LoopRunning,
+    /// The motor group has no motors.
+    NoMotors,
+}
+
+impl core::fmt::Display for HomingError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            HomingError::Timeout => write!(f, "homing timed out before reaching the stop"),
+            HomingError::Switch(e) => write!(f, "failed to read homing switch: {}", e),
+            HomingError::LoopRunning => {
+                write!(f, "homing must run before the PID loop is started")
+            }
+            HomingError::NoMotors => write!(f, "no motors to home"),
+        }
+    }
+}
+
+impl core::error::Error for HomingError {}
+
+/// Detects a stall from current and velocity readings.
+///
+/// The motors are stalled once current stays at or above the threshold and
+/// velocity stays at or below its threshold for the configured duration,
+/// ignoring the first `grace` milliseconds.
+pub struct StallDetector {
+    current:  f64,
+    velocity: f64,
+    duration: u64,
+    grace:    u64,
+    since:    Option<u64>,
+}
+
+impl StallDetector {
+    /// Creates a stall detector.
+    pub fn new(current: f64, velocity: f64, duration: u64, grace: u64) -> Self {
+        Self {
+            current,
+            velocity,
+            duration,
+            grace,
+            since: None,
+        }
+    }
+
+    /// Feeds a reading taken `time` milliseconds after the motors started and
+    /// returns `true` once the stall has lasted long enough.
+    pub fn update(&mut self, time: u64, current: f64, velocity: f64) -> bool {
+        let stalled =
+            time >= self.grace && current >= self.current && velocity.abs() <= self.velocity;
+        if !stalled {
+            self.since = None;
+            return false;
+        }
+        let since = *self.since.get_or_insert(time);
+        time - since >= self.duration
+    }
+}
+
+/// Returns `true` if a limit switch or bumper reading `level` is pressed.
+///
+/// V5 switches read high while pressed and low while released.
+pub fn switch_pressed(level: LogicLevel) -> bool { level == LogicLevel::High }
+
+impl SinglePIDMovement {
+    /// Drives the motor group into a stop and zeroes the encoders there.
+    ///
+    /// The motors run at `config.voltage` until the trigger fires, then
+    /// stop and have their position set to `config.offset` (output-shaft
+    /// degrees, using the configured gear ratio). The controller target is
+    /// set to the homed position so the mechanism holds still once the loop
+    /// starts.
+    ///
+    /// This drives the motors directly, so it must run before
+    /// [`init`](SinglePIDMovement::init). Returns
+    /// [`HomingError::LoopRunning`] without moving if the loop is alive.
+    pub async fn home(&self, config: &HomingConfig) -> Result<(), HomingError> {
+        if self.lifecycle.is_alive() {
+            warn!("Homing Refused: {}", HomingError::LoopRunning);
+            return Err(HomingError::LoopRunning);
+        }
+        if self.motorgroup.borrow_mut().as_mut().is_empty() {
+            return Err(HomingError::NoMotors);
+        }
+        info!("Homing Started");
+        let mut stall = match config.trigger {
+            HomingTrigger::Stall {
+                current,
+                velocity,
+                duration,
+            } => Some(StallDetector::new(current, velocity, duration, config.grace)),
+            HomingTrigger::Switch(_) => None,
+        };
+        let start_time = user_uptime().as_millis();
+
+        let result = loop {
+            let elapsed = (user_uptime().as_millis() - start_time) as u64;
+            if elapsed >= config.timeout {
+                break Err(HomingError::Timeout);
+            }
+
+            let triggered = match (&config.trigger, stall.as_mut()) {
+                (HomingTrigger::Switch(switch), _) => match switch.level() {
+                    Ok(level) => switch_pressed(level),
+                    Err(e) => break Err(HomingError::Switch(e)),
+                },
+                (_, Some(detector)) => {
+                    let (current, velocity) = self.group_current_velocity();
+                    detector.update(elapsed, current, velocity)
+                }
+                (_, None) => false,
+            };
+            if triggered {
+                break Ok(());
+            }
+
+            self.set_group_voltage(config.voltage);
+            sleep(Duration::from_millis(self.lifecycle.period())).await;
+        };
+        self.set_group_voltage(0.0);
+
+        match result {
+            Ok(()) => {
+                let mut s = self.pid_values.lock().await;
+                let position = s.output_degrees_to_motor(config.offset);
+                {
+                    let mut motors = self.motorgroup.borrow_mut();
+                    for motor in motors.as_mut().iter_mut() {
+                        let _ = motor.set_position(Angle::from_radians(position));
+                    }
+                }
+                s.target = position;
+                s.active = false;
+                s.homed = true;
+                info!("Homing Finished at {} degrees", config.offset);
+            }
+            Err(ref e) => warn!("Homing Failed: {}", e),
+        }
+        result
+    }
+
+    /// Average current in amps and velocity in RPM of the group, or zero for
+    /// an empty group.
+    fn group_current_velocity(&self) -> (f64, f64) {
+        let mut motors = self.motorgroup.borrow_mut();
+        let slice = motors.as_mut();
+        if slice.is_empty() {
+            return (0.0, 0.0);
+        }
+        let count = slice.len() as f64;
+        let current: f64 = slice.iter().map(|m| m.current().unwrap_or_default()).sum();
+        let velocity: f64 = slice.iter().map(|m| m.velocity().unwrap_or_default()).sum();
+        (current / count, velocity / count)
+    }
+
+    fn set_group_voltage(&self, voltage: f64) {
+        let mut motors = self.motorgroup.borrow_mut();
+        for motor in motors.as_mut().iter_mut() {
+            let _ = motor.set_voltage(voltage);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stall_detector_test() {
+        let mut detector = StallDetector::new(1.5, 5.0, 100, 200);
+        // Spin-up is ignored during the grace period
+        assert!(!detector.update(0, 2.0, 0.0));
+        assert!(!detector.update(150, 2.0, 0.0));
+        // Moving freely
+        assert!(!detector.update(200, 0.5, 80.0));
+        // Stalled, but not for long enough yet
+        assert!(!detector.update(250, 2.0, 1.0));
+        assert!(!detector.update(300, 2.0, 1.0));
+        assert!(detector.update(350, 2.1, -2.0));
+    }
+
+    #[test]
+    fn switch_pressed_test() {
+        // A released switch must not count as the stop
+        assert!(!switch_pressed(LogicLevel::Low));
+        assert!(switch_pressed(LogicLevel::High));
+    }
+
+    #[test]
+    fn stall_detector_reset_test() {
+        let mut detector = StallDetector::new(1.5, 5.0, 100, 0);
+        assert!(!detector.update(0, 2.0, 0.0));
+        // A brief release restarts the timer
+        assert!(!detector.update(50, 0.2, 0.0));
+        assert!(!detector.update(100, 2.0, 0.0));
+        assert!(!detector.update(150, 2.0, 0.0));
+        assert!(detector.update(200, 2.0, 0.0));
+    }
+}
@@ -11,6 +11,14 @@
 //!   stop to turn.
 //! - `singlepid`: Standalone PID for controlling a single motor group
 //!   (e.g., an arm or lift).
+//! - `presets`: Named preset positions and transition guards on top of
+//!   `singlepid`.
+//! - `homing`: Limit switch and stall homing for `singlepid` mechanisms.
+//! - `telemetry`: Per-tick recording of the loops above, with CSV export
+//!   and step response summaries.
+//! - `tuner`: Live gain tuning from the controller screen and buttons.
+//! - `autotune`: Relay-feedback autotuner that proposes gains for the
+//!   controllers above.
 //!
 //! # How PID Works
 //!
@@ -26,7 +34,14 @@
 //!
 //! Start with Kp and increase until the robot reaches the target.
 //! Add Kd to reduce overshoot. Only add Ki if the robot consistently
-//! undershoots.
+//! undershoots. Alternatively, the `autotune` module can measure the
+//! mechanism and propose a starting set of gains.
+
+/// Relay-feedback autotuning for PID gains.
+///
+/// Oscillates a mechanism with a bang-bang relay to measure its ultimate
+/// gain and period, then proposes Kp, Ki and Kd from a tuning rule.
+pub mod autotune;
 
 /// Arc PID controller for curved movements.
 ///
@@ -34,21 +49,46 @@
 /// This is less precise than standard PID but faster for some maneuvers.
 pub mod arcpid;
 
+/// Homing routines for single motor group mechanisms.
+///
+/// Drives a mechanism into a stop, detected by a limit switch or a stall,
+/// and zeroes its encoders there before PID control starts.
+pub mod homing;
+
 /// Standard PID controller for differential drivetrains.
 ///
 /// Provides linear movement, rotation, and swing turn capabilities
 /// with configurable PID gains.
 pub mod pid;
 
+/// Named preset positions for single motor group mechanisms.
+///
+/// Moves an arm or lift between named positions, tracks which one it is
+/// at, and blocks moves that are unsafe for other mechanisms.
+pub mod presets;
+
 /// Standalone PID controller for single motor groups.
 ///
 /// Useful for controlling mechanisms like arms, lifts, or flywheels
 /// independently from the drivetrain.
 pub mod singlepid;
+
+/// Per-tick telemetry for PID control loops.
+///
+/// Records target, measurement, error, P/I/D contributions and output
+/// into a bounded buffer that can be summarized or written to CSV.
+pub mod telemetry;
+
+/// Live PID tuning from the controller.
+///
+/// Nudges gains with the D-pad, runs a test motion and shows the measured
+/// settle time and overshoot on the controller screen.
+pub mod tuner;
 /// Physical configuration of the drivetrain for distance calculations.
 ///
 /// These values are used to convert between motor rotations and
-/// linear distance traveled by the robot.#[derive(Clone, Copy)]
+/// linear distance traveled by the robot. The conversions themselves live
+/// in the [`kinematics`](crate::motion::kinematics) module.
 #[derive(Clone, Copy)]
 pub struct DrivetrainConfig {
     /// The wheel diameter in inches.
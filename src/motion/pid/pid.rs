@@ -24,11 +24,15 @@
 //! pid.tune(0.5, 0.0, 0.1, 0.02).await;
 //!
 //! // Execute movements
-//! pid.travel(24.0, 2000, 100).await;   // Move 24 inches forward
-//! pid.rotate(90.0, 2000, 100).await;   // Turn 90 degrees right
+//! pid.travel(24.0, pid.options()).await;   // Move 24 inches forward
+//! pid.rotate(90.0, pid.options()).await;   // Turn 90 degrees right
+//!
+//! // Turn to an absolute heading using the IMU
+//! let imu = HeadingSource::Imu(imu.clone());
+//! pid.turn_to_heading(180.0, &imu, pid.options().timeout(2000)).await;
 //! ```
 
-use std::{f64::consts::PI, marker::PhantomData, sync::Arc, time::Duration};
+use std::{marker::PhantomData, sync::Arc, time::Duration};
 
 use log::{info, warn};
 use vexide::{
@@ -40,15 +44,33 @@ use vexide::{
 };
 
 use crate::{
-    motion::pid::DrivetrainConfig,
-    peripherals::{drivetrain, drivetrain::Differential},
+    motion::{
+        feedforward::Feedforward,
+        lifecycle::{LoopCommand, LoopHandle},
+        odom::OdomValues,
+        options::{ExitTracker, Motion, MotionDefaults, MotionOptions, power_floor, run_motion},
+        pid::{
+            DrivetrainConfig,
+            autotune::group_position,
+            telemetry::{Telemetry, TickRecord},
+        },
+        profile::{MotionProfile, ProfileConstraints},
+        timing::LoopTimer,
+    },
+    peripherals::{
+        drivetrain,
+        drivetrain::{Differential, DriveOwner},
+    },
     to_mutex,
 };
 
-/// Loop rate for the PID control task in milliseconds.
-const LOOPRATE: u64 = 5;
-
-async fn pid_loop(pidvalues: &Arc<Mutex<PIDValues>>, drivetrain: drivetrain::Differential) {
+async fn pid_loop(
+    pidvalues: &Arc<Mutex<PIDValues>>,
+    angularvalues: &Arc<Mutex<AngularPIDValues>>,
+    drivetrain: drivetrain::Differential,
+    lifecycle: LoopHandle,
+    telemetry: Telemetry,
+) {
     info!("PID Control Loop Started");
     // Set brake mode and reset positions for left motors
     {
@@ -75,15 +97,105 @@ async fn pid_loop(pidvalues: &Arc<Mutex<PIDValues>>, drivetrain: drivetrain::Dif
     let mut ierror_left = 0.0;
     let mut ierror_right = 0.0;
 
-    // seconds per loop from configured looprate in ms
-    let dt = (LOOPRATE as f64) / 1000.0;
+    let mut perror_heading = 0.0;
+    let mut ierror_heading = 0.0;
+    let mut heading_running = false;
+    let mut settling = false;
+    let mut was_active = false;
+
+    let mut timer = LoopTimer::new("PID Control Loop", &lifecycle);
+    let mut paused = false;
 
     loop {
-        let (target_left, target_right, pwr, kp, kd, ki, tolerance) = {
+        // Seconds since the previous tick
+        let dt = timer.tick();
+        match lifecycle.poll() {
+            LoopCommand::Shutdown => break,
+            LoopCommand::Paused => {
+                if !paused {
+                    drivetrain.set_voltages(DriveOwner::Pid, 0.0, 0.0);
+                    paused = true;
+                }
+                timer.wait().await;
+                continue;
+            }
+            LoopCommand::Restart => {
+                info!("PID Control Loop Restarted");
+                drivetrain.reset_position();
+                {
+                    let mut s = pidvalues.lock().await;
+                    s.target_left = 0.0;
+                    s.target_right = 0.0;
+                    s.active = false;
+                }
+                angularvalues.lock().await.active = false;
+                perror_left = 0.0;
+                perror_right = 0.0;
+                ierror_left = 0.0;
+                ierror_right = 0.0;
+                heading_running = false;
+            }
+            LoopCommand::Run => {}
+        }
+        paused = false;
+
+        let (
+            target_left,
+            target_right,
+            (minpwr, pwr),
+            kp,
+            kd,
+            ki,
+            tolerance,
+            feedforward,
+            profiling,
+            hold,
+            active,
+        ) = {
             let s = pidvalues.lock().await;
-            (s.target_left, s.target_right, s.maxpwr, s.kp, s.kd, s.ki, s.tolerance)
+            (
+                s.target_left,
+                s.target_right,
+                (s.motion_power.0, s.maxpwr.abs().min(s.motion_power.1)),
+                s.kp,
+                s.kd,
+                s.ki,
+                s.tolerance,
+                s.feedforward,
+                s.profiling,
+                s.hold,
+                s.active,
+            )
+        };
+
+        let heading_motion = {
+            let s = angularvalues.lock().await;
+            if s.active {
+                s.source.clone().map(|source| {
+                    (
+                        source,
+                        s.target,
+                        s.direction,
+                        s.swing,
+                        (s.motion_power.0, s.maxpwr.abs().min(s.motion_power.1)),
+                        s.kp,
+                        s.kd,
+                        s.ki,
+                        s.tolerance,
+                    )
+                })
+            } else {
+                None
+            }
         };
 
+        let now = user_uptime().as_millis() as u64;
+        let motion_active = active || heading_motion.is_some();
+        if motion_active && !was_active {
+            telemetry.begin_motion(now);
+        }
+        was_active = motion_active;
+
         let currs_left = {
             let mut left_motors = drivetrain.left.borrow_mut();
             let left_slice = left_motors.as_mut();
@@ -103,6 +215,153 @@ async fn pid_loop(pidvalues: &Arc<Mutex<PIDValues>>, drivetrain: drivetrain::Dif
                 .sum();
             sum / right_slice.len() as f64
         };
+
+        // While another owner drives, don't write to the motors and keep the
+        // targets on the current position so later moves start from here.
+        if !drivetrain.is_owner(DriveOwner::Pid) {
+            {
+                let mut s = pidvalues.lock().await;
+                s.target_left = currs_left;
+                s.target_right = currs_right;
+            }
+            perror_left = 0.0;
+            perror_right = 0.0;
+            ierror_left = 0.0;
+            ierror_right = 0.0;
+            heading_running = false;
+            timer.wait().await;
+            continue;
+        }
+
+        // Once the movement has settled, hand the drivetrain back unless it
+        // asked to hold its position.
+        if !motion_active && !hold {
+            drivetrain.release(DriveOwner::Pid);
+            timer.wait().await;
+            continue;
+        }
+
+        if let Some((
+            source,
+            target,
+            direction,
+            swing,
+            (h_min, h_pwr),
+            h_kp,
+            h_kd,
+            h_ki,
+            h_tolerance,
+        )) = heading_motion
+        {
+            if !heading_running {
+                heading_running = true;
+                settling = false;
+                ierror_heading = 0.0;
+                perror_heading = heading_error(target, read_heading(&source).await, direction);
+            }
+
+            let current = read_heading(&source).await;
+            let mut error_heading = heading_error(
+                target,
+                current,
+                if settling {
+                    TurnDirection::Shortest
+                } else {
+                    direction
+                },
+            );
+            // A jump of more than half a turn means the target was crossed, so the
+            // forced direction no longer applies and the robot settles the short way.
+            if !settling && (error_heading - perror_heading).abs() > 180.0 {
+                settling = true;
+                error_heading = heading_error(target, current, TurnDirection::Shortest);
+                perror_heading = error_heading;
+            }
+
+            ierror_heading += error_heading * dt;
+            if h_ki != 0.0 {
+                let i_max = h_pwr.abs() / h_ki.abs();
+                ierror_heading = ierror_heading.clamp(-i_max, i_max);
+            }
+            let derror_heading = (error_heading - perror_heading) / dt;
+            let mut u_heading = abscap(
+                h_kp * error_heading + h_ki * ierror_heading + h_kd * derror_heading,
+                h_pwr,
+            );
+            if error_heading.abs() >= h_tolerance {
+                u_heading = power_floor(u_heading, h_min, h_pwr);
+            }
+            telemetry.record(TickRecord {
+                time: now,
+                channel: "heading",
+                target,
+                measurement: current,
+                error: error_heading,
+                p: h_kp * error_heading,
+                i: h_ki * ierror_heading,
+                d: h_kd * derror_heading,
+                output: u_heading,
+                tolerance: h_tolerance,
+            });
+
+            // The side that is not driven by the heading loop holds its encoder target.
+            let u_left;
+            let u_right;
+            match swing {
+                None => {
+                    u_left = u_heading;
+                    u_right = -u_heading;
+                }
+                Some(SwingSide::Left) => {
+                    u_left = u_heading;
+                    u_right = abscap(kp * (target_right - currs_right), pwr.abs());
+                }
+                Some(SwingSide::Right) => {
+                    u_left = abscap(kp * (target_left - currs_left), pwr.abs());
+                    u_right = -u_heading;
+                }
+            }
+
+            {
+                let mut left_motors = drivetrain.left.borrow_mut();
+                for motor in left_motors.as_mut().iter_mut() {
+                    let _ = motor.set_voltage(u_left);
+                }
+            }
+            {
+                let mut right_motors = drivetrain.right.borrow_mut();
+                for motor in right_motors.as_mut().iter_mut() {
+                    let _ = motor.set_voltage(u_right);
+                }
+            }
+
+            // Keep the encoder targets of the moving side(s) on the current position
+            // so the linear loop holds wherever the heading motion stops.
+            {
+                let mut s = pidvalues.lock().await;
+                if swing != Some(SwingSide::Right) {
+                    s.target_left = currs_left;
+                }
+                if swing != Some(SwingSide::Left) {
+                    s.target_right = currs_right;
+                }
+            }
+
+            if error_heading.abs() < h_tolerance {
+                let mut s = angularvalues.lock().await;
+                s.active = false;
+            }
+
+            perror_heading = error_heading;
+            perror_left = 0.0;
+            perror_right = 0.0;
+            ierror_left = 0.0;
+            ierror_right = 0.0;
+            timer.wait().await;
+            continue;
+        }
+        heading_running = false;
+
         let error_left = target_left - currs_left;
         let error_right = target_right - currs_right;
 
@@ -120,11 +379,16 @@ async fn pid_loop(pidvalues: &Arc<Mutex<PIDValues>>, drivetrain: drivetrain::Dif
         let derror_left = (error_left - perror_left) / dt;
         let derror_right = (error_right - perror_right) / dt;
 
-        u_left = kp * error_left + ki * ierror_left + kd * derror_left;
-        u_right = kp * error_right + ki * ierror_right + kd * derror_right;
+        u_left = kp * error_left + ki * ierror_left + kd * derror_left + feedforward;
+        u_right = kp * error_right + ki * ierror_right + kd * derror_right + feedforward;
 
-        u_left = abscap(u_left, pwr.abs());
-        u_right = abscap(u_right, pwr.abs());
+        (u_left, u_right) = drivetrain.desaturate(u_left, u_right, pwr);
+        if error_left.abs() >= tolerance {
+            u_left = power_floor(u_left, minpwr, pwr);
+        }
+        if error_right.abs() >= tolerance {
+            u_right = power_floor(u_right, minpwr, pwr);
+        }
 
         // Set voltage for left motors
         {
@@ -143,8 +407,9 @@ async fn pid_loop(pidvalues: &Arc<Mutex<PIDValues>>, drivetrain: drivetrain::Dif
                 let _ = motor.set_voltage(u_right);
             }
         }
-        let in_band;
-        in_band = error_left.abs() < tolerance && error_right.abs() < tolerance;
+        // While a profile is running the targets are still moving,
+        // so being inside the tolerance does not mean the movement is done.
+        let in_band = error_left.abs() < tolerance && error_right.abs() < tolerance && !profiling;
 
         if in_band {
             let mut s = pidvalues.lock().await;
@@ -171,10 +436,48 @@ async fn pid_loop(pidvalues: &Arc<Mutex<PIDValues>>, drivetrain: drivetrain::Dif
             ierror_left = 0.0;
             ierror_right = 0.0;
         }
+
+        for (channel, target, current, error, ierror, derror, u) in [
+            (
+                "left",
+                target_left,
+                currs_left,
+                error_left,
+                ierror_left,
+                derror_left,
+                u_left,
+            ),
+            (
+                "right",
+                target_right,
+                currs_right,
+                error_right,
+                ierror_right,
+                derror_right,
+                u_right,
+            ),
+        ] {
+            telemetry.record(TickRecord {
+                time: now,
+                channel,
+                target,
+                measurement: current,
+                error,
+                p: kp * error,
+                i: ki * ierror,
+                d: kd * derror,
+                output: if in_band { 0.0 } else { u },
+                tolerance,
+            });
+        }
         perror_left = error_left;
         perror_right = error_right;
-        sleep(Duration::from_millis(LOOPRATE)).await;
+        timer.wait().await;
     }
+
+    drivetrain.release(DriveOwner::Pid);
+    lifecycle.stopped();
+    info!("PID Control Loop Stopped");
 }
 
 impl PIDMovement {
@@ -182,21 +485,33 @@ impl PIDMovement {
     /// The PID movements will require a PID loop to run as a seperate task or thread.
     /// It is necessary to initialize the PID before running any movements.
     ///
+    /// Returns a [`LoopHandle`] for pausing, restarting or shutting down the
+    /// loop. Calling `init` again while the loop is running returns the same
+    /// handle without spawning a second loop.
+    ///
     /// # Examples
     /// ```
     /// async fn auton(pid: PIDMovement) {
     ///     pid.init(); // Initialize the PID before any movements
     ///     pid.set_maximum_power(12.0).await;
-    ///     pid.travel(100.0, 1000, 10).await;
+    ///     pid.travel(100.0, pid.options().timeout(1000)).await;
     /// }
     /// ```
-    pub fn init(&self) {
+    pub fn init(&self) -> LoopHandle {
+        if !self.lifecycle.start() {
+            info!("PID Control Loop Already Running");
+            return self.lifecycle.clone();
+        }
         let mutex_clone = self.pid_values.clone();
+        let angular_clone = self.angular_values.clone();
         let drivetrain = self.drivetrain.clone();
+        let lifecycle = self.lifecycle.clone();
+        let telemetry = self.telemetry.clone();
         let mainloop = spawn(async move {
-            pid_loop(&mutex_clone, drivetrain).await;
+            pid_loop(&mutex_clone, &angular_clone, drivetrain, lifecycle, telemetry).await;
         });
         mainloop.detach();
+        self.lifecycle.clone()
     }
 
     /// Set the tolerance, Kp, Ki and Kd Values for PID. The values are in radians.
@@ -208,6 +523,17 @@ impl PIDMovement {
         pid_values.tolerance = tolerance;
     }
 
+    /// Set the tolerance, Kp, Ki and Kd Values for the heading PID used by
+    /// [`turn_to_heading`](PIDMovement::turn_to_heading) and
+    /// [`swing_to_heading`](PIDMovement::swing_to_heading). The values are in degrees.
+    pub async fn tune_angular(&self, kp: f64, ki: f64, kd: f64, tolerance: f64) {
+        let mut angular_values = self.angular_values.lock().await;
+        angular_values.kp = kp;
+        angular_values.ki = ki;
+        angular_values.kd = kd;
+        angular_values.tolerance = tolerance;
+    }
+
     /// Sets the maximum power the robot should move at. The maximum value is 12.0
     /// while the minimum value is -12.0 (reverse).
     pub async fn set_maximum_power(&self, maximum_power: f64) {
@@ -215,168 +541,336 @@ impl PIDMovement {
         pid_values.maxpwr = maximum_power;
     }
 
-    /// Makes the robot travel in a straight line
-    pub async fn travel(&self, distance: f64, timeout: u64, afterdelay: u64) {
-        let r = (distance *
-            (self.drivetrain_config.driving_gear / self.drivetrain_config.driven_gear) *
-            2.0 *
-            PI) /
-            self.drivetrain_config.wheel_diameter;
-        let mut s = self.pid_values.lock().await;
-        s.active = true;
-        s.target_right += r;
-        s.target_left += r;
-        timeout_wait(&self.pid_values, timeout).await;
-        {
-            let mut s = self.pid_values.lock().await;
-            s.active = false;
-        }
-        sleep(Duration::from_millis(afterdelay)).await;
-    }
+    /// Returns the controller's default [`MotionOptions`], to pass to a
+    /// motion as they are or adjusted with the builder methods.
+    pub fn options(&self) -> MotionOptions { self.defaults.get() }
 
-    /// Rotates the robot by a certain number for degrees
-    pub async fn rotate(&self, degrees: f64, timeout: u64, afterdelay: u64) {
-        let target = PI * self.drivetrain_config.track_width / (360.0 / degrees);
-        self.rotate_raw(target, timeout, afterdelay).await;
-    }
+    /// Radians of motor rotation per inch of travel.
+    fn radians_per_inch(&self) -> f64 { self.drivetrain_config.distance_to_angle(1.0) }
 
-    /// Rotates the robot. The value should be in the number of inches
-    /// one side of the robot must rotate.
-    pub async fn rotate_raw(&self, distance: f64, timeout: u64, afterdelay: u64) {
-        let r = (distance *
-            (self.drivetrain_config.driving_gear / self.drivetrain_config.driven_gear) *
-            2.0 *
-            PI) /
-            self.drivetrain_config.wheel_diameter;
+    /// Moves the encoder targets by `left` and `right` radians and runs the
+    /// motion until it ends.
+    async fn encoder_motion(&self, left: f64, right: f64, options: MotionOptions) {
         {
             let mut s = self.pid_values.lock().await;
+            self.drivetrain.acquire(DriveOwner::Pid);
             s.active = true;
-            s.target_left += r;
-            s.target_right += -r;
-        }
-        timeout_wait(&self.pid_values, timeout).await;
-        {
-            let mut s = self.pid_values.lock().await;
-            s.active = false;
+            s.hold = options.hold;
+            s.motion_power = options.power();
+            s.target_left += left;
+            s.target_right += right;
         }
-        sleep(Duration::from_millis(afterdelay)).await;
+        let motion = EncoderMotion {
+            values:     self.pid_values.clone(),
+            drivetrain: self.drivetrain.clone(),
+            ratio:      self.radians_per_inch(),
+        };
+        run_motion(motion, &self.defaults, &self.lifecycle, options).await;
     }
 
-    /// Rotates the robot using the IMU (Inertial Sensor) for more accurate
-    /// and precise turning.
-    pub async fn rotate_imu(
+    /// Makes the robot travel in a straight line
+    pub async fn travel(&self, distance: f64, options: MotionOptions) {
+        let r = distance * self.radians_per_inch();
+        self.encoder_motion(r, r, options).await;
+    }
+
+    /// Makes the robot travel in a straight line following a motion profile.
+    ///
+    /// Instead of jumping the target by the whole distance, the target moves
+    /// along a trapezoidal or S-curve profile. The profile's velocity and
+    /// acceleration are fed forward through `feedforward` while the PID
+    /// corrects the remaining position error. Once the profile ends, the
+    /// movement settles on the final target like [`travel`](PIDMovement::travel).
+    ///
+    /// The call always waits for the profile to finish. With
+    /// [`no_wait`](MotionOptions::no_wait) it returns before the final settle.
+    ///
+    /// # Arguments
+    ///
+    /// * `distance` - Distance to travel in inches.
+    /// * `constraints` - Velocity (in/s), acceleration (in/s²) and optional jerk
+    ///   (in/s³) limits.
+    /// * `feedforward` - Feedforward gains in volts per in/s and in/s².
+    /// * `options` - Timeout, power limits and exit conditions of the motion.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let constraints = ProfileConstraints::new(50.0, 100.0, None)?;
+    /// let ff = Feedforward::new(0.8, 0.18, 0.02);
+    /// pid.travel_profiled(48.0, constraints, ff, pid.options().timeout(4000)).await;
+    /// ```
+    pub async fn travel_profiled(
         &self,
-        degrees: f64,
-        imu: &InertialSensor,
-        timeout: u64,
-        afterdelay: u64,
+        distance: f64,
+        constraints: ProfileConstraints,
+        feedforward: Feedforward,
+        options: MotionOptions,
     ) {
+        let ratio = self.radians_per_inch();
+        let profile = MotionProfile::new(distance, constraints);
+        let (start_left, start_right) = {
+            let mut s = self.pid_values.lock().await;
+            self.drivetrain.acquire(DriveOwner::Pid);
+            s.active = true;
+            s.profiling = true;
+            s.hold = options.hold;
+            s.motion_power = options.power();
+            (s.target_left, s.target_right)
+        };
         let start_time = user_uptime().as_millis();
-        let mut prev_angle = 0.0;
-        let mut delta_angle;
-        let mut angle;
-        let mut s = self.pid_values.lock().await;
-        s.active = true;
         loop {
-            angle = degrees - get_heading(&imu);
-            delta_angle = angle - prev_angle;
-            prev_angle = angle;
-            let distance = PI * self.drivetrain_config.track_width / (360.0 / delta_angle);
-            let r = (distance *
-                (self.drivetrain_config.driving_gear / self.drivetrain_config.driven_gear) *
-                2.0 *
-                PI) /
-                self.drivetrain_config.wheel_diameter;
+            let elapsed = user_uptime().as_millis() - start_time;
+            let t = elapsed as f64 / 1000.0;
+            let state = profile.sample(t);
             {
                 let mut s = self.pid_values.lock().await;
-                s.target_left += r;
-                s.target_right += -r;
-                if !s.active {
-                    break;
-                }
+                s.active = true;
+                s.target_left = start_left + state.position * ratio;
+                s.target_right = start_right + state.position * ratio;
+                s.feedforward = feedforward.calculate(state.velocity, state.acceleration);
             }
-            if user_uptime().as_millis() >= start_time + timeout as u128 {
-                let mut s = self.pid_values.lock().await;
-                s.active = false;
+            if profile.is_finished(t) || elapsed >= options.timeout as u128 {
                 break;
             }
-            sleep(Duration::from_millis(LOOPRATE)).await;
+            sleep(Duration::from_millis(self.lifecycle.period())).await;
         }
-        sleep(Duration::from_millis(afterdelay)).await;
+        {
+            let mut s = self.pid_values.lock().await;
+            s.feedforward = 0.0;
+            s.profiling = false;
+        }
+        let remaining =
+            (options.timeout as u128).saturating_sub(user_uptime().as_millis() - start_time);
+        self.encoder_motion(0.0, 0.0, options.timeout(remaining as u64))
+            .await;
+    }
+
+    /// Rotates the robot by a certain number for degrees
+    pub async fn rotate(&self, degrees: f64, options: MotionOptions) {
+        let target = self.drivetrain_config.turn_distance(degrees);
+        self.rotate_raw(target, options).await;
+    }
+
+    /// Rotates the robot. The value should be in the number of inches
+    /// one side of the robot must rotate.
+    pub async fn rotate_raw(&self, distance: f64, options: MotionOptions) {
+        let r = distance * self.radians_per_inch();
+        self.encoder_motion(r, -r, options).await;
+    }
+
+    /// Rotates the robot using the IMU (Inertial Sensor) for more accurate
+    /// and precise turning.
+    ///
+    /// The call always waits, since the targets are updated from the IMU
+    /// until the turn ends. Exit ranges are in degrees.
+    pub async fn rotate_imu(&self, degrees: f64, imu: &InertialSensor, options: MotionOptions) {
+        self.imu_motion(degrees, imu, 1.0, -1.0, options).await;
     }
 
     /// Swings the robot by moving only one side of the robot forward or backward
-    pub async fn swing(&self, degrees: f64, right: bool, timeout: u64, afterdelay: u64) {
-        let target = 2.0 * PI * self.drivetrain_config.track_width / (360.0 / degrees);
-        self.swing_raw(target.abs(), right, timeout, afterdelay)
-            .await;
+    pub async fn swing(&self, degrees: f64, right: bool, options: MotionOptions) {
+        let target = self.drivetrain_config.swing_distance(degrees);
+        self.swing_raw(target.abs(), right, options).await;
     }
 
     /// Swings the robot by moving only one side of the robot forward or backward with values in inches
     /// The value should be in the number of inches the side of the robot must rotate.
-    pub async fn swing_raw(&self, distance: f64, right: bool, timeout: u64, afterdelay: u64) {
-        let r = (distance *
-            (self.drivetrain_config.driving_gear / self.drivetrain_config.driven_gear) *
-            2.0 *
-            PI) /
-            self.drivetrain_config.wheel_diameter;
-        {
-            let mut s = self.pid_values.lock().await;
-            s.active = true;
-            s.target_left += r * if right { 1.0 } else { 0.0 };
-            s.target_right += r * if right { 0.0 } else { 1.0 };
-        }
-        timeout_wait(&self.pid_values, timeout).await;
-        {
-            let mut s = self.pid_values.lock().await;
-            s.active = false;
-        }
-        sleep(Duration::from_millis(afterdelay)).await;
+    pub async fn swing_raw(&self, distance: f64, right: bool, options: MotionOptions) {
+        let r = distance * self.radians_per_inch();
+        self.encoder_motion(
+            r * if right { 1.0 } else { 0.0 },
+            r * if right { 0.0 } else { 1.0 },
+            options,
+        )
+        .await;
     }
 
     /// Swings the robot by moving only one side of the robot forward or backward
     /// using the IMU (Inertial Sensor) for more accurate
     /// and precise turning.
+    ///
+    /// The call always waits, since the targets are updated from the IMU
+    /// until the swing ends. Exit ranges are in degrees.
     pub async fn swing_imu(
         &self,
         degrees: f64,
         imu: &InertialSensor,
         right: bool,
-        timeout: u64,
-        afterdelay: u64,
+        options: MotionOptions,
     ) {
+        let (left, right) = if right { (1.0, 0.0) } else { (0.0, 1.0) };
+        self.imu_motion(degrees, imu, left, right, options).await;
+    }
+
+    /// Moves the encoder targets by the remaining IMU angle every tick, scaled
+    /// by `left` and `right` for each side, until the motion ends.
+    async fn imu_motion(
+        &self,
+        degrees: f64,
+        imu: &InertialSensor,
+        left: f64,
+        right: f64,
+        options: MotionOptions,
+    ) {
+        self.defaults.begin();
         let start_time = user_uptime().as_millis();
+        let mut tracker = ExitTracker::new(options);
         let mut prev_angle = 0.0;
-        let mut delta_angle;
-        let mut angle;
-        let mut s = self.pid_values.lock().await;
-        s.active = true;
+        {
+            let mut s = self.pid_values.lock().await;
+            self.drivetrain.acquire(DriveOwner::Pid);
+            s.active = true;
+            s.hold = options.hold;
+            s.motion_power = options.power();
+        }
         loop {
-            angle = degrees - get_heading(&imu);
-            delta_angle = angle - prev_angle;
+            let angle = degrees - get_heading(imu);
+            let delta_angle = angle - prev_angle;
             prev_angle = angle;
-            let distance = PI * self.drivetrain_config.track_width / (360.0 / delta_angle);
-            let r = (distance *
-                (self.drivetrain_config.driving_gear / self.drivetrain_config.driven_gear) *
-                2.0 *
-                PI) /
-                self.drivetrain_config.wheel_diameter;
-            {
+            let distance = self.drivetrain_config.turn_distance(delta_angle);
+            let r = distance * self.radians_per_inch();
+            let active = {
                 let mut s = self.pid_values.lock().await;
-                s.target_left += r * if right { 1.0 } else { 0.0 };
-                s.target_right += r * if right { 0.0 } else { 1.0 };
-                if !s.active {
-                    break;
-                }
-            }
-            if user_uptime().as_millis() >= start_time + timeout as u128 {
-                let mut s = self.pid_values.lock().await;
-                s.active = false;
+                s.target_left += r * left;
+                s.target_right += r * right;
+                s.active
+            };
+            let time = (user_uptime().as_millis() - start_time) as u64;
+            if tracker.update(time, active, angle).is_some() {
                 break;
             }
-            sleep(Duration::from_millis(LOOPRATE)).await;
+            sleep(Duration::from_millis(self.lifecycle.period())).await;
         }
-        sleep(Duration::from_millis(afterdelay)).await;
+        {
+            let mut s = self.pid_values.lock().await;
+            s.active = false;
+            s.motion_power = NO_POWER_LIMITS;
+        }
+        sleep(Duration::from_millis(options.after_delay)).await;
+    }
+
+    /// Turns the robot in place to an absolute heading in degrees.
+    ///
+    /// Unlike [`rotate_imu`](PIDMovement::rotate_imu), the heading is closed-loop
+    /// on the given source and the error is wrapped, so a target of 170° from
+    /// -170° turns 20° instead of 340°. Uses the gains set by
+    /// [`tune_angular`](PIDMovement::tune_angular).
+    ///
+    /// # Arguments
+    ///
+    /// * `heading` - The absolute heading to turn to, in degrees.
+    /// * `source` - Where the current heading is read from.
+    /// * `options` - Turn direction, timeout, power limits and exit
+    ///   conditions of the motion. Exit ranges are in degrees.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let source = HeadingSource::Imu(imu.clone());
+    /// pid.turn_to_heading(90.0, &source, pid.options().timeout(2000)).await;
+    /// ```
+    pub async fn turn_to_heading(
+        &self,
+        heading: f64,
+        source: &HeadingSource,
+        options: MotionOptions,
+    ) {
+        self.heading_motion(heading, source, None, options).await;
+    }
+
+    /// Swings the robot to an absolute heading in degrees by driving only one
+    /// side of the drivetrain while the other side holds its position.
+    ///
+    /// # Arguments
+    ///
+    /// * `heading` - The absolute heading to swing to, in degrees.
+    /// * `source` - Where the current heading is read from.
+    /// * `side` - The side of the drivetrain that moves.
+    /// * `options` - Turn direction, timeout, power limits and exit
+    ///   conditions of the motion. Exit ranges are in degrees.
+    pub async fn swing_to_heading(
+        &self,
+        heading: f64,
+        source: &HeadingSource,
+        side: SwingSide,
+        options: MotionOptions,
+    ) {
+        self.heading_motion(heading, source, Some(side), options)
+            .await;
+    }
+
+    async fn heading_motion(
+        &self,
+        heading: f64,
+        source: &HeadingSource,
+        swing: Option<SwingSide>,
+        options: MotionOptions,
+    ) {
+        self.pid_values.lock().await.hold = options.hold;
+        {
+            let mut s = self.angular_values.lock().await;
+            s.target = heading;
+            s.direction = options.direction;
+            s.swing = swing;
+            s.source = Some(source.clone());
+            s.motion_power = options.power();
+            self.drivetrain.acquire(DriveOwner::Pid);
+            s.active = true;
+        }
+        let motion = HeadingMotion {
+            values: self.angular_values.clone(),
+            source: source.clone(),
+        };
+        run_motion(motion, &self.defaults, &self.lifecycle, options).await;
+    }
+}
+
+/// Output limits that leave the controller's own maximum power in charge.
+const NO_POWER_LIMITS: (f64, f64) = (0.0, 12.0);
+
+/// A drive, turn or swing measured on the drivetrain encoders.
+struct EncoderMotion {
+    values:     Arc<Mutex<PIDValues>>,
+    drivetrain: Differential,
+    ratio:      f64,
+}
+
+impl Motion for EncoderMotion {
+    async fn status(&self) -> (bool, f64) {
+        let left = group_position(&self.drivetrain.left);
+        let right = group_position(&self.drivetrain.right);
+        let s = self.values.lock().await;
+        let error = (s.target_left - left)
+            .abs()
+            .max((s.target_right - right).abs());
+        (s.active, error / self.ratio)
+    }
+
+    async fn end(&self) {
+        let mut s = self.values.lock().await;
+        s.active = false;
+        s.motion_power = NO_POWER_LIMITS;
+    }
+}
+
+/// A turn or swing to an absolute heading.
+struct HeadingMotion {
+    values: Arc<Mutex<AngularPIDValues>>,
+    source: HeadingSource,
+}
+
+impl Motion for HeadingMotion {
+    async fn status(&self) -> (bool, f64) {
+        let current = read_heading(&self.source).await;
+        let s = self.values.lock().await;
+        (s.active, heading_error(s.target, current, TurnDirection::Shortest))
+    }
+
+    async fn end(&self) {
+        let mut s = self.values.lock().await;
+        s.active = false;
+        s.motion_power = NO_POWER_LIMITS;
     }
 }
 
@@ -414,11 +908,19 @@ impl PIDMovement {
 ///         active:       true,
 ///         target_left:  0.0,
 ///         target_right: 0.0,
+///         feedforward:  0.0,
+///         profiling:    false,
+///         hold:         false,
+///         motion_power: (0.0, 12.0),
 ///     };
 ///     let PID_controller = PIDMovement {
 ///         drivetrain:        dt,
 ///         drivetrain_config: config,
 ///         pid_values:        Arc::new(Mutex::new(values)),
+///         angular_values:    Arc::new(Mutex::new(AngularPIDValues::default())),
+///         lifecycle:         LoopHandle::default(),
+///         telemetry:         Telemetry::default(),
+///         defaults:          MotionDefaults::default(),
 ///     };
 /// }
 /// ```
@@ -426,6 +928,14 @@ pub struct PIDMovement {
     pub drivetrain:        Differential,
     pub drivetrain_config: DrivetrainConfig,
     pub pid_values:        Arc<Mutex<PIDValues>>,
+    /// Gains and targets for absolute heading turns and swings.
+    pub angular_values:    Arc<Mutex<AngularPIDValues>>,
+    /// Handle to the background loop started by [`init`](PIDMovement::init).
+    pub lifecycle:         LoopHandle,
+    /// Per-tick recording of the control loop, disabled by default.
+    pub telemetry:         Telemetry,
+    /// Default options for motions, see [`options`](PIDMovement::options).
+    pub defaults:          MotionDefaults,
 }
 
 impl PIDMovement {
@@ -438,6 +948,10 @@ impl PIDMovement {
             drivetrain:        dt,
             drivetrain_config: dt_config,
             pid_values:        to_mutex(pid_values),
+            angular_values:    to_mutex(AngularPIDValues::default()),
+            lifecycle:         LoopHandle::default(),
+            telemetry:         Telemetry::default(),
+            defaults:          MotionDefaults::default(),
         }
     }
 }
@@ -477,6 +991,25 @@ pub struct PIDValues {
     pub target_left:  f64,
     /// Target position for right motors in radians.
     pub target_right: f64,
+    /// Voltage added to both sides on top of the PID output.
+    ///
+    /// Set by profiled movements and zero otherwise.
+    pub feedforward:  f64,
+    /// Whether a profiled movement is still moving the targets.
+    ///
+    /// The movement can't settle until the profile has finished, even when
+    /// the error is inside the tolerance.
+    pub profiling:    bool,
+    /// Whether the loop keeps holding the targets once the movement settles.
+    ///
+    /// Set from the motion's [`MotionOptions`]. When `false` the loop
+    /// releases the drivetrain after the movement.
+    pub hold:         bool,
+    /// Minimum and maximum output in volts of the current motion.
+    ///
+    /// Set from the motion's [`MotionOptions`]. The output is capped at the
+    /// smaller of this maximum and `maxpwr`.
+    pub motion_power: (f64, f64),
 }
 
 impl PIDValues {
@@ -491,6 +1024,10 @@ impl PIDValues {
             active:       true,
             target_left:  0.0,
             target_right: 0.0,
+            feedforward:  0.0,
+            profiling:    false,
+            hold:         false,
+            motion_power: NO_POWER_LIMITS,
         }
     }
 
@@ -504,31 +1041,95 @@ impl PIDValues {
             active: true,
             target_left: 0.0,
             target_right: 0.0,
+            feedforward: 0.0,
+            profiling: false,
+            hold: false,
+            motion_power: NO_POWER_LIMITS,
         }
     }
 }
 
-async fn timeout_wait(pid_values: &Arc<Mutex<PIDValues>>, timeout: u64) {
-    let start_time = user_uptime().as_millis();
+/// Runtime values for the heading PID used by absolute turns and swings.
+///
+/// These gains are kept separate from [`PIDValues`] because heading error is
+/// measured in degrees rather than radians of wheel rotation.
+pub struct AngularPIDValues {
+    /// Proportional gain.
+    pub kp:           f64,
+    /// Integral gain.
+    pub ki:           f64,
+    /// Derivative gain.
+    pub kd:           f64,
+    /// Error tolerance in degrees.
+    pub tolerance:    f64,
+    /// Maximum motor voltage (0-12 volts).
+    pub maxpwr:       f64,
+    /// Whether a heading movement is currently active.
+    pub active:       bool,
+    /// Target heading in degrees.
+    pub target:       f64,
+    /// Which way the robot may turn to reach the target.
+    pub direction:    TurnDirection,
+    /// The moving side for swing turns, or `None` to turn in place.
+    pub swing:        Option<SwingSide>,
+    /// Where the current heading is read from.
+    pub source:       Option<HeadingSource>,
+    /// Minimum and maximum output in volts of the current motion.
+    pub motion_power: (f64, f64),
+}
 
-    loop {
-        {
-            let s = pid_values.lock().await;
-            if !s.active {
-                break;
-            }
-        }
+impl Default for AngularPIDValues {
+    /// Uses default AngularPID Values
+    fn default() -> AngularPIDValues { AngularPIDValues::new(0.2, 0.0, 0.0, 1.0, 12.0) }
+}
 
-        if user_uptime().as_millis() >= start_time + timeout as u128 {
-            let mut s = pid_values.lock().await;
-            s.active = false;
-            break;
+impl AngularPIDValues {
+    pub fn new(kp: f64, ki: f64, kd: f64, tolerance: f64, maxpwr: f64) -> AngularPIDValues {
+        AngularPIDValues {
+            kp,
+            ki,
+            kd,
+            tolerance,
+            maxpwr,
+            active: false,
+            target: 0.0,
+            direction: TurnDirection::Shortest,
+            swing: None,
+            source: None,
+            motion_power: NO_POWER_LIMITS,
         }
-
-        sleep(Duration::from_millis(LOOPRATE)).await;
     }
 }
 
+/// The sensor a heading movement reads the robot's heading from.
+#[derive(Clone)]
+pub enum HeadingSource {
+    /// An inertial sensor.
+    Imu(Arc<Mutex<InertialSensor>>),
+    /// The global heading of a running odometry tracker.
+    Odometry(Arc<Mutex<OdomValues>>),
+}
+
+/// The direction a heading movement is allowed to turn in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TurnDirection {
+    /// Take whichever direction is shorter.
+    Shortest,
+    /// Always turn clockwise (increasing heading).
+    Clockwise,
+    /// Always turn counter-clockwise (decreasing heading).
+    CounterClockwise,
+}
+
+/// The side of the drivetrain that moves during a swing turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwingSide {
+    /// The left side moves while the right side holds.
+    Left,
+    /// The right side moves while the left side holds.
+    Right,
+}
+
 fn get_heading(imu: &InertialSensor) -> f64 {
     let is_calibrating = imu.is_calibrating().unwrap_or_else(|e| {
         warn!("IMU Calibration State Error: {}", e);
@@ -554,6 +1155,32 @@ fn get_heading(imu: &InertialSensor) -> f64 {
     }
 }
 
+pub(crate) async fn read_heading(source: &HeadingSource) -> f64 {
+    match source {
+        HeadingSource::Imu(imu) => get_heading(&*imu.lock().await),
+        HeadingSource::Odometry(odom) => odom.lock().await.global_heading,
+    }
+}
+
+/// Wraps an angle in degrees to the range (-180, 180].
+fn wrap_degrees(angle: f64) -> f64 {
+    let angle = angle.rem_euclid(360.0);
+    if angle > 180.0 { angle - 360.0 } else { angle }
+}
+
+/// Signed heading error in degrees from `current` to `target`.
+///
+/// Positive errors are turned clockwise and negative errors counter-clockwise.
+pub(crate) fn heading_error(target: f64, current: f64, direction: TurnDirection) -> f64 {
+    let error = wrap_degrees(target - current);
+    match direction {
+        TurnDirection::Shortest => error,
+        TurnDirection::Clockwise if error < 0.0 => error + 360.0,
+        TurnDirection::CounterClockwise if error > 0.0 => error - 360.0,
+        _ => error,
+    }
+}
+
 fn abscap(val: f64, cap: f64) -> f64 {
     let result: f64;
     if val > cap {
@@ -565,3 +1192,30 @@ fn abscap(val: f64, cap: f64) -> f64 {
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_degrees_test() {
+        assert_eq!(wrap_degrees(190.0), -170.0);
+        assert_eq!(wrap_degrees(-190.0), 170.0);
+        assert_eq!(wrap_degrees(180.0), 180.0);
+        assert_eq!(wrap_degrees(720.0), 0.0);
+    }
+
+    #[test]
+    fn heading_error_shortest_test() {
+        assert_eq!(heading_error(170.0, -170.0, TurnDirection::Shortest), -20.0);
+        assert_eq!(heading_error(-170.0, 170.0, TurnDirection::Shortest), 20.0);
+        assert_eq!(heading_error(90.0, 0.0, TurnDirection::Shortest), 90.0);
+    }
+
+    #[test]
+    fn heading_error_forced_test() {
+        assert_eq!(heading_error(170.0, -170.0, TurnDirection::Clockwise), 340.0);
+        assert_eq!(heading_error(90.0, 0.0, TurnDirection::CounterClockwise), -270.0);
+        assert_eq!(heading_error(-90.0, 0.0, TurnDirection::CounterClockwise), -90.0);
+    }
+}
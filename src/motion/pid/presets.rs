@@ -0,0 +1,368 @@
+//! Named preset positions for single motor group mechanisms.
+//!
+//! Lifts and arms usually move between a handful of fixed positions. A
+//! [`PresetController`] wraps a [`SinglePIDMovement`] with a table of named
+//! positions (in output-shaft degrees), tracks which preset the mechanism is
+//! at, and refuses moves that are blocked by a [transition guard](PresetTable::guard).
+//!
+//! # Example
+//!
+//! ```ignore
+//! use antaeus::motion::pid::presets::{PresetController, PresetState};
+//!
+//! #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+//! enum Lift { Stow, Intake, ScoreLow, ScoreHigh }
+//!
+//! let mut lift = PresetController::new(lift_pid);
+//! lift.table.add(Lift::Stow, 0.0);
+//! lift.table.add(Lift::Intake, 15.0);
+//! lift.table.add(Lift::ScoreLow, 60.0);
+//! lift.table.add(Lift::ScoreHigh, 110.0);
+//!
+//! // Only raise the lift all the way once the claw is closed
+//! let claw_state = claw.state_handle();
+//! lift.table.guard(Lift::ScoreHigh, "claw closed", move || {
+//!     claw_state.get() == PresetState::At(Claw::Closed)
+//! });
+//!
+//! lift.movement.init();
+//! lift.goto_preset(Lift::ScoreLow, 2000).await?;
+//!
+//! // In driver control
+//! if controller.button_r1.is_now_pressed() {
+//!     let _ = lift.cycle_next().await;
+//! }
+//! ```
+
+use std::{cell::Cell, fmt::Debug, rc::Rc, time::Duration};
+
+use log::warn;
+use vexide::{task::*, time::*};
+
+use super::singlepid::SinglePIDMovement;
+
+/// Where a preset mechanism currently is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresetState<K> {
+    /// No preset has been reached yet, or the last move timed out.
+    Unknown,
+    /// Moving toward the given preset.
+    Moving(K),
+    /// Settled at the given preset.
+    At(K),
+}
+
+/// Errors returned when a preset move can't be made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresetError<K> {
+    /// The preset is not in the table.
+    UnknownPreset(K),
+    /// A transition guard refused the move.
+    Blocked {
+        /// The preset that was requested.
+        to:    K,
+        /// The name of the guard that refused it.
+        guard: &'static str,
+    },
+    /// The mechanism did not settle at the preset before the timeout.
+    Timeout(K),
+}
+
+impl<K: Debug> core::fmt::Display for PresetError<K> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PresetError::UnknownPreset(key) => write!(f, "unknown preset {:?}", key),
+            PresetError::Blocked { to, guard } => {
+                write!(f, "move to {:?} blocked by guard \"{}\"", to, guard)
+            }
+            PresetError::Timeout(key) => write!(f, "timed out moving to {:?}", key),
+        }
+    }
+}
+
+impl<K: Debug> core::error::Error for PresetError<K> {}
+
+/// A condition that must hold before moving into a preset.
+struct TransitionGuard<K> {
+    from:  Option<K>,
+    to:    K,
+    name:  &'static str,
+    check: Box<dyn Fn() -> bool>,
+}
+
+/// An ordered table of preset positions and the guards between them.
+///
+/// Presets are kept in the order they were added, which is also the order
+/// used by [`next`](PresetTable::next) and [`previous`](PresetTable::previous).
+pub struct PresetTable<K> {
+    presets: Vec<(K, f64)>,
+    guards:  Vec<TransitionGuard<K>>,
+}
+
+impl<K: Copy + PartialEq> Default for PresetTable<K> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<K: Copy + PartialEq> PresetTable<K> {
+    /// Creates an empty preset table.
+    pub fn new() -> Self {
+        Self {
+            presets: Vec::new(),
+            guards:  Vec::new(),
+        }
+    }
+
+    /// Adds a preset at the given output-shaft angle in degrees, replacing
+    /// the position if the preset already exists.
+    pub fn add(&mut self, key: K, degrees: f64) {
+        match self.presets.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = degrees,
+            None => self.presets.push((key, degrees)),
+        }
+    }
+
+    /// Forbids moving into `to` unless `check` returns `true`.
+    pub fn guard(&mut self, to: K, name: &'static str, check: impl Fn() -> bool + 'static) {
+        self.guards.push(TransitionGuard {
+            from: None,
+            to,
+            name,
+            check: Box::new(check),
+        });
+    }
+
+    /// Forbids moving from `from` directly into `to` unless `check` returns `true`.
+    pub fn guard_transition(
+        &mut self,
+        from: K,
+        to: K,
+        name: &'static str,
+        check: impl Fn() -> bool + 'static,
+    ) {
+        self.guards.push(TransitionGuard {
+            from: Some(from),
+            to,
+            name,
+            check: Box::new(check),
+        });
+    }
+
+    /// Returns the position of a preset in output-shaft degrees.
+    pub fn position(&self, key: K) -> Option<f64> {
+        self.presets
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, d)| *d)
+    }
+
+    /// Checks whether a move from `from` to `to` is allowed and returns the
+    /// target position in degrees.
+    pub fn check(&self, from: Option<K>, to: K) -> Result<f64, PresetError<K>> {
+        let position = self.position(to).ok_or(PresetError::UnknownPreset(to))?;
+        for guard in &self.guards {
+            let applies = guard.to == to && (guard.from.is_none() || guard.from == from);
+            if applies && !(guard.check)() {
+                return Err(PresetError::Blocked {
+                    to,
+                    guard: guard.name,
+                });
+            }
+        }
+        Ok(position)
+    }
+
+    /// Returns the preset after `current`, or the first preset when
+    /// `current` is `None`. Stops at the last preset.
+    pub fn next(&self, current: Option<K>) -> Option<K> {
+        let index = match current.and_then(|c| self.index(c)) {
+            Some(i) => (i + 1).min(self.presets.len().saturating_sub(1)),
+            None => 0,
+        };
+        self.presets.get(index).map(|(k, _)| *k)
+    }
+
+    /// Returns the preset before `current`, or the first preset when
+    /// `current` is `None`. Stops at the first preset.
+    pub fn previous(&self, current: Option<K>) -> Option<K> {
+        let index = match current.and_then(|c| self.index(c)) {
+            Some(i) => i.saturating_sub(1),
+            None => 0,
+        };
+        self.presets.get(index).map(|(k, _)| *k)
+    }
+
+    fn index(&self, key: K) -> Option<usize> { self.presets.iter().position(|(k, _)| *k == key) }
+}
+
+/// A [`SinglePIDMovement`] that moves between named presets.
+///
+/// The PID loop must still be started with
+/// [`movement.init()`](SinglePIDMovement::init).
+pub struct PresetController<K> {
+    /// The controller driving the mechanism.
+    pub movement: SinglePIDMovement,
+    /// The preset positions and transition guards.
+    pub table:    PresetTable<K>,
+    state:        Rc<Cell<PresetState<K>>>,
+    generation:   Rc<Cell<u64>>,
+}
+
+impl<K: Copy + PartialEq + Debug + 'static> PresetController<K> {
+    /// Creates a preset controller with an empty preset table.
+    pub fn new(movement: SinglePIDMovement) -> Self {
+        Self {
+            movement,
+            table: PresetTable::new(),
+            state: Rc::new(Cell::new(PresetState::Unknown)),
+            generation: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// Returns the current state of the mechanism.
+    pub fn state(&self) -> PresetState<K> { self.state.get() }
+
+    /// Returns a shared handle to the state, for use in the transition
+    /// guards of other mechanisms.
+    pub fn state_handle(&self) -> Rc<Cell<PresetState<K>>> { self.state.clone() }
+
+    /// Starts moving to a preset without waiting for it to settle.
+    ///
+    /// The state becomes [`PresetState::Moving`] and changes to
+    /// [`PresetState::At`] once the PID loop settles.
+    pub async fn start_preset(&self, key: K) -> Result<(), PresetError<K>> {
+        let degrees = self.table.check(self.current(), key)?;
+        let generation = self.generation.get() + 1;
+        self.generation.set(generation);
+        self.state.set(PresetState::Moving(key));
+        self.movement.set_target_degrees(degrees).await;
+
+        let pid_values = self.movement.pid_values.clone();
+        let state = self.state.clone();
+        let current = self.generation.clone();
+        let lifecycle = self.movement.lifecycle.clone();
+        spawn(async move {
+            while current.get() == generation {
+                if !pid_values.lock().await.active {
+                    state.set(PresetState::At(key));
+                    break;
+                }
+                sleep(Duration::from_millis(lifecycle.period())).await;
+            }
+        })
+        .detach();
+        Ok(())
+    }
+
+    /// Moves to a preset and waits until it settles or the timeout (in
+    /// milliseconds) passes.
+    pub async fn goto_preset(&self, key: K, timeout: u64) -> Result<(), PresetError<K>> {
+        self.start_preset(key).await?;
+        let generation = self.generation.get();
+        let start_time = user_uptime().as_millis();
+        loop {
+            if self.generation.get() != generation {
+                // A newer move replaced this one
+                return Ok(());
+            }
+            if self.state.get() == PresetState::At(key) {
+                return Ok(());
+            }
+            if user_uptime().as_millis() >= start_time + timeout as u128 {
+                warn!("Timed out moving to preset {:?}", key);
+                self.generation.set(generation + 1);
+                self.state.set(PresetState::Unknown);
+                return Err(PresetError::Timeout(key));
+            }
+            sleep(Duration::from_millis(self.movement.lifecycle.period())).await;
+        }
+    }
+
+    /// Starts moving to the next preset in the table.
+    pub async fn cycle_next(&self) -> Result<(), PresetError<K>> {
+        match self.table.next(self.current()) {
+            Some(key) => self.start_preset(key).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Starts moving to the previous preset in the table.
+    pub async fn cycle_previous(&self) -> Result<(), PresetError<K>> {
+        match self.table.previous(self.current()) {
+            Some(key) => self.start_preset(key).await,
+            None => Ok(()),
+        }
+    }
+
+    /// The preset the mechanism is at or moving to.
+    fn current(&self) -> Option<K> {
+        match self.state.get() {
+            PresetState::Unknown => None,
+            PresetState::Moving(key) | PresetState::At(key) => Some(key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Lift {
+        Stow,
+        Intake,
+        Score,
+    }
+
+    fn table() -> PresetTable<Lift> {
+        let mut table = PresetTable::new();
+        table.add(Lift::Stow, 0.0);
+        table.add(Lift::Intake, 15.0);
+        table.add(Lift::Score, 90.0);
+        table
+    }
+
+    #[test]
+    fn cycle_test() {
+        let table = table();
+        assert_eq!(table.next(None), Some(Lift::Stow));
+        assert_eq!(table.next(Some(Lift::Stow)), Some(Lift::Intake));
+        assert_eq!(table.next(Some(Lift::Score)), Some(Lift::Score));
+        assert_eq!(table.previous(Some(Lift::Intake)), Some(Lift::Stow));
+        assert_eq!(table.previous(Some(Lift::Stow)), Some(Lift::Stow));
+    }
+
+    #[test]
+    fn guard_test() {
+        let safe = Rc::new(Cell::new(false));
+        let mut table = table();
+        let check = safe.clone();
+        table.guard(Lift::Score, "claw closed", move || check.get());
+        table.guard_transition(Lift::Score, Lift::Stow, "never", || false);
+
+        assert_eq!(
+            table.check(Some(Lift::Stow), Lift::Score),
+            Err(PresetError::Blocked {
+                to:    Lift::Score,
+                guard: "claw closed",
+            })
+        );
+        safe.set(true);
+        assert_eq!(table.check(Some(Lift::Stow), Lift::Score), Ok(90.0));
+
+        // Transition guards only apply to their starting preset
+        assert!(table.check(Some(Lift::Score), Lift::Stow).is_err());
+        assert_eq!(table.check(Some(Lift::Intake), Lift::Stow), Ok(0.0));
+    }
+
+    #[test]
+    fn unknown_preset_test() {
+        let mut table = PresetTable::new();
+        table.add(Lift::Stow, 0.0);
+        assert_eq!(
+            table.check(None, Lift::Score),
+            Err(PresetError::UnknownPreset(Lift::Score))
+        );
+        table.add(Lift::Stow, 5.0);
+        assert_eq!(table.position(Lift::Stow), Some(5.0));
+    }
+}
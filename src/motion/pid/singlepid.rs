@@ -6,7 +6,7 @@
 //! # Use Cases
 //!
 //! - **Arms/Lifts**: Position control for mechanisms that move to set heights.
-//! - **Flywheels**: Speed control for shooting mechanisms (using velocity as position).
+//! - **Flywheels**: Speed control for shooting mechanisms using the velocity mode.
 //! - **Intakes**: Position control for deploy/retract sequences.
 //!
 //! # Example
@@ -23,8 +23,45 @@
 //! // Move to target position (in radians)
 //! arm_pid.set_target(3.14).await;
 //! ```
+//!
+//! # Velocity Mode
+//!
+//! Calling [`set_velocity`](SinglePIDMovement::set_velocity) switches the loop
+//! from position control to velocity control, using one of the
+//! [`VelocityController`]s. This is meant for flywheels and rollers:
+//!
+//! ```ignore
+//! flywheel.set_velocity_controller(VelocityController::TakeBackHalf { gain: 0.1 }).await;
+//! flywheel.set_velocity(2400.0).await;
+//!
+//! // Only shoot once the flywheel is up to speed
+//! if flywheel.at_speed().await {
+//!     indexer.set_voltage(12.0);
+//! }
+//! ```
+//!
+//! # Mechanism Configuration
+//!
+//! Arms and lifts can describe their gearing, gravity load and travel limits
+//! so targets can be given in output-shaft degrees.
+//!
+//! All angles are measured from where the encoders read zero. Homing (see
+//! the [`homing`](super::homing) module) against a stop 30 degrees below
+//! horizontal with an `offset` of `-30.0` puts zero at horizontal:
+//!
+//! ```ignore
+//! arm.set_gear_ratio(5.0).await; // 5 motor turns per arm turn
+//! arm.home(&HomingConfig { offset: -30.0, ..HomingConfig::default() }).await?;
+//!
+//! // Zero is horizontal, and the arm travels from the stop up to 110 degrees
+//! arm.set_gravity(GravityCompensation::Arm { kg: 1.2, zero_angle: 0.0 }).await;
+//! arm.set_soft_limits(Some((-30.0, 110.0))).await;
+//! arm.init();
+//!
+//! arm.set_target_degrees(90.0).await;
+//! ```
 
-use std::{cell::RefCell, rc::Rc, sync::Arc, time::Duration};
+use std::{cell::RefCell, rc::Rc, sync::Arc};
 
 use log::info;
 use vexide::{
@@ -34,36 +71,128 @@ use vexide::{
     time::*,
 };
 
-/// Loop rate for the PID control task in milliseconds.
-const LOOPRATE: u64 = 5;
+use crate::motion::{
+    lifecycle::{LoopCommand, LoopHandle},
+    pid::telemetry::{Telemetry, TickRecord},
+    timing::LoopTimer,
+};
 
 async fn single_pid_loop(
     pidvalues: &Arc<Mutex<SinglePIDValues>>,
     motorgroup: Rc<RefCell<dyn AsMut<[Motor]>>>,
+    lifecycle: LoopHandle,
+    telemetry: Telemetry,
 ) {
     info!("PID Control Loop Started");
-    // Set brake mode and reset positions for motors
+    // Set brake mode and reset positions for motors, unless homing
+    // already zeroed them
+    let homed = pidvalues.lock().await.homed;
     {
         let mut motors = motorgroup.borrow_mut();
         let slice = motors.as_mut();
         for motor in slice.iter_mut() {
             let _ = motor.brake(BrakeMode::Brake);
-            let _ = motor.reset_position();
+            if !homed {
+                let _ = motor.reset_position();
+            }
         }
     }
 
     let mut perror = 0.0;
     let mut ierror = 0.0;
+    let mut velocity_state = VelocityState::default();
+    let mut was_active = false;
 
-    // seconds per loop from configured looprate in ms
-    let dt = (LOOPRATE as f64) / 1000.0;
+    let mut timer = LoopTimer::new("Single PID Control Loop", &lifecycle);
+    let mut paused = false;
 
     loop {
-        let (target, pwr, kp, kd, ki, tolerance) = {
+        // Seconds since the previous tick
+        let dt = timer.tick();
+        match lifecycle.poll() {
+            LoopCommand::Shutdown => break,
+            LoopCommand::Paused => {
+                if !paused {
+                    set_voltage(&motorgroup, 0.0);
+                    paused = true;
+                }
+                timer.wait().await;
+                continue;
+            }
+            LoopCommand::Restart => {
+                info!("PID Control Loop Restarted");
+                {
+                    let mut motors = motorgroup.borrow_mut();
+                    for motor in motors.as_mut().iter_mut() {
+                        let _ = motor.reset_position();
+                    }
+                }
+                {
+                    let mut s = pidvalues.lock().await;
+                    s.target = 0.0;
+                    s.active = false;
+                    s.homed = false;
+                    s.mode = ControlMode::Position;
+                }
+                perror = 0.0;
+                ierror = 0.0;
+                velocity_state = VelocityState::default();
+            }
+            LoopCommand::Run => {}
+        }
+        paused = false;
+
+        let (target, pwr, kp, kd, ki, tolerance, active) = {
             let s = pidvalues.lock().await;
-            (s.target, s.maxpwr, s.kp, s.kd, s.ki, s.tolerance)
+            (s.target, s.maxpwr, s.kp, s.kd, s.ki, s.tolerance, s.active)
+        };
+
+        let now = user_uptime().as_millis() as u64;
+        if active && !was_active {
+            telemetry.begin_motion(now);
+        }
+        was_active = active;
+
+        let raw_velocity = {
+            let mut motors = motorgroup.borrow_mut();
+            let slice = motors.as_mut();
+            let sum: f64 = slice
+                .iter()
+                .map(|motor| motor.velocity().unwrap_or_default())
+                .sum();
+            sum / slice.len() as f64
+        };
+
+        let velocity_mode = {
+            let mut s = pidvalues.lock().await;
+            let alpha = s.velocity_filter.clamp(0.0, 1.0);
+            s.velocity += alpha * (raw_velocity - s.velocity);
+            match s.mode {
+                ControlMode::Velocity => {
+                    Some((s.velocity_controller, s.target_velocity, s.velocity))
+                }
+                ControlMode::Position => None,
+            }
         };
 
+        if let Some((controller, target_velocity, velocity)) = velocity_mode {
+            let u = abscap(
+                velocity_state.update(controller, target_velocity, velocity, pwr.abs(), dt),
+                pwr.abs(),
+            );
+            {
+                let mut motors = motorgroup.borrow_mut();
+                for motor in motors.as_mut().iter_mut() {
+                    let _ = motor.set_voltage(u);
+                }
+            }
+            perror = 0.0;
+            ierror = 0.0;
+            timer.wait().await;
+            continue;
+        }
+        velocity_state = VelocityState::default();
+
         let currs = {
             let mut motors = motorgroup.borrow_mut();
             let slice = motors.as_mut();
@@ -74,6 +203,18 @@ async fn single_pid_loop(
             sum / slice.len() as f64
         };
 
+        let (target, feedforward, limits) = {
+            let s = pidvalues.lock().await;
+            let limits = s.soft_limits.map(|(min, max)| {
+                (
+                    s.output_degrees_to_motor(min),
+                    s.output_degrees_to_motor(max),
+                    s.output_degrees_to_motor(s.limit_margin) - s.output_degrees_to_motor(0.0),
+                )
+            });
+            (s.clamp_target(target), s.gravity_feedforward(currs), limits)
+        };
+
         let error = target - currs;
 
         ierror += error * dt;
@@ -86,9 +227,12 @@ async fn single_pid_loop(
 
         let derror = (error - perror) / dt;
 
-        u = kp * error + ki * ierror + kd * derror;
+        u = kp * error + ki * ierror + kd * derror + feedforward;
 
         u = abscap(u, pwr.abs());
+        if let Some((min, max, margin)) = limits {
+            u = soft_limit_output(u, feedforward, currs, min, max, margin);
+        }
 
         // Set voltage for motors
         {
@@ -106,19 +250,42 @@ async fn single_pid_loop(
             let mut s = pidvalues.lock().await;
             s.active = false;
 
-            // Stop motors
+            // Stop motors, holding only against gravity
             {
                 let mut motors = motorgroup.borrow_mut();
                 let slice = motors.as_mut();
                 for motor in slice.iter_mut() {
-                    let _ = motor.set_voltage(0.0);
+                    let _ = motor.set_voltage(feedforward);
                 }
             }
 
             ierror = 0.0;
         }
+        telemetry.record(TickRecord {
+            time: now,
+            channel: "single",
+            target,
+            measurement: currs,
+            error,
+            p: kp * error,
+            i: ki * ierror,
+            d: kd * derror,
+            output: if in_band { feedforward } else { u },
+            tolerance,
+        });
         perror = error;
-        sleep(Duration::from_millis(LOOPRATE)).await;
+        timer.wait().await;
+    }
+
+    set_voltage(&motorgroup, 0.0);
+    lifecycle.stopped();
+    info!("PID Control Loop Stopped");
+}
+
+fn set_voltage(motorgroup: &Rc<RefCell<dyn AsMut<[Motor]>>>, voltage: f64) {
+    let mut motors = motorgroup.borrow_mut();
+    for motor in motors.as_mut().iter_mut() {
+        let _ = motor.set_voltage(voltage);
     }
 }
 
@@ -126,14 +293,24 @@ impl SinglePIDMovement {
     /// Initializes a PID Loop.
     /// The PID movements will require a PID loop to run as a seperate task or thread.
     /// It is necessary to initialize the PID before running any movements.
-
-    pub fn init(&self) {
+    ///
+    /// Returns a [`LoopHandle`] for pausing, restarting or shutting down the
+    /// loop. Calling `init` again while the loop is running returns the same
+    /// handle without spawning a second loop.
+    pub fn init(&self) -> LoopHandle {
+        if !self.lifecycle.start() {
+            info!("PID Control Loop Already Running");
+            return self.lifecycle.clone();
+        }
         let mutex_clone = self.pid_values.clone();
         let motorgroup = self.motorgroup.clone();
+        let lifecycle = self.lifecycle.clone();
+        let telemetry = self.telemetry.clone();
         let mainloop = spawn(async move {
-            single_pid_loop(&mutex_clone, motorgroup).await;
+            single_pid_loop(&mutex_clone, motorgroup, lifecycle, telemetry).await;
         });
         mainloop.detach();
+        self.lifecycle.clone()
     }
 
     /// Set the tolerance, Kp, Ki and Kd Values for PID. The values are in radians.
@@ -155,7 +332,8 @@ impl SinglePIDMovement {
     /// Sets the target position for the motor group.
     ///
     /// The motors will move to this position and hold. The target
-    /// is specified in radians of motor rotation.
+    /// is specified in radians of motor rotation. This switches the
+    /// controller back to position mode if it was in velocity mode.
     ///
     /// # Arguments
     ///
@@ -165,8 +343,97 @@ impl SinglePIDMovement {
             let mut s = self.pid_values.lock().await;
             s.active = true;
             s.target = target;
+            s.mode = ControlMode::Position;
         }
     }
+
+    /// Sets the target velocity for the motor group and switches the
+    /// controller to velocity mode.
+    ///
+    /// The velocity is held with the configured [`VelocityController`]
+    /// until a new velocity or position target is set. Setting a velocity
+    /// of zero lets the motor group spin down.
+    ///
+    /// # Arguments
+    ///
+    /// * `rpm` - Target velocity in motor RPM.
+    pub async fn set_velocity(&self, rpm: f64) {
+        let mut s = self.pid_values.lock().await;
+        s.active = true;
+        s.target_velocity = rpm;
+        s.mode = ControlMode::Velocity;
+    }
+
+    /// Selects the controller used in velocity mode.
+    pub async fn set_velocity_controller(&self, controller: VelocityController) {
+        let mut s = self.pid_values.lock().await;
+        s.velocity_controller = controller;
+    }
+
+    /// Sets how far (in RPM) the filtered velocity may be from the target
+    /// for [`at_speed`](SinglePIDMovement::at_speed) to report ready.
+    pub async fn set_velocity_tolerance(&self, tolerance: f64) {
+        let mut s = self.pid_values.lock().await;
+        s.velocity_tolerance = tolerance;
+    }
+
+    /// Sets the target position of the output shaft in degrees.
+    ///
+    /// The angle is converted to motor radians using the configured gear
+    /// ratio and clamped to the soft limits.
+    pub async fn set_target_degrees(&self, degrees: f64) {
+        let mut s = self.pid_values.lock().await;
+        s.active = true;
+        s.target = s.output_degrees_to_motor(degrees);
+        s.mode = ControlMode::Position;
+    }
+
+    /// Returns the current position of the output shaft in degrees.
+    pub async fn position_degrees(&self) -> f64 {
+        let currs = {
+            let mut motors = self.motorgroup.borrow_mut();
+            let slice = motors.as_mut();
+            let sum: f64 = slice
+                .iter()
+                .map(|motor| motor.position().unwrap_or_default().as_radians())
+                .sum();
+            sum / slice.len() as f64
+        };
+        self.pid_values.lock().await.motor_to_output_degrees(currs)
+    }
+
+    /// Sets the number of motor rotations per rotation of the output shaft.
+    pub async fn set_gear_ratio(&self, gear_ratio: f64) {
+        let mut s = self.pid_values.lock().await;
+        s.gear_ratio = gear_ratio;
+    }
+
+    /// Sets the gravity feedforward for the mechanism.
+    pub async fn set_gravity(&self, gravity: GravityCompensation) {
+        let mut s = self.pid_values.lock().await;
+        s.gravity = gravity;
+    }
+
+    /// Sets the minimum and maximum output-shaft angle in degrees, measured
+    /// from where the encoders read zero, or `None` to remove the limits.
+    pub async fn set_soft_limits(&self, limits: Option<(f64, f64)>) {
+        let mut s = self.pid_values.lock().await;
+        s.soft_limits = limits;
+    }
+
+    /// Returns the filtered velocity of the motor group in RPM.
+    pub async fn velocity(&self) -> f64 { self.pid_values.lock().await.velocity }
+
+    /// Returns `true` when the controller is in velocity mode and the
+    /// filtered velocity is within the velocity tolerance of the target.
+    ///
+    /// Use this to gate actions that need the mechanism up to speed, such
+    /// as firing a shot from a flywheel.
+    pub async fn at_speed(&self) -> bool {
+        let s = self.pid_values.lock().await;
+        s.mode == ControlMode::Velocity &&
+            (s.velocity - s.target_velocity).abs() <= s.velocity_tolerance
+    }
 }
 
 /// PID controller for a single motor group.
@@ -181,6 +448,8 @@ impl SinglePIDMovement {
 /// let arm = SinglePIDMovement {
 ///     motorgroup: Rc::new(RefCell::new([arm_motor_1, arm_motor_2])),
 ///     pid_values: Arc::new(Mutex::new(SinglePIDValues { /* ... */ })),
+///     lifecycle:  LoopHandle::default(),
+///     telemetry:  Telemetry::default(),
 /// };
 /// arm.init();
 /// ```
@@ -189,6 +458,10 @@ pub struct SinglePIDMovement {
     pub motorgroup: Rc<RefCell<dyn AsMut<[Motor]>>>,
     /// Thread-safe container for PID runtime values.
     pub pid_values: Arc<Mutex<SinglePIDValues>>,
+    /// Handle to the background loop started by [`init`](SinglePIDMovement::init).
+    pub lifecycle:  LoopHandle,
+    /// Per-tick recording of the position loop, disabled by default.
+    pub telemetry:  Telemetry,
 }
 
 impl SinglePIDMovement {
@@ -196,6 +469,8 @@ impl SinglePIDMovement {
         SinglePIDMovement {
             motorgroup: Rc::new(RefCell::new(motorgroup)),
             pid_values: Arc::new(Mutex::new(pid_values)),
+            lifecycle:  LoopHandle::default(),
+            telemetry:  Telemetry::default(),
         }
     }
 }
@@ -205,34 +480,54 @@ impl SinglePIDMovement {
 /// These values control the PID behavior and are updated during operation.
 pub struct SinglePIDValues {
     /// Proportional gain.
-    pub kp:        f64,
+    pub kp:                  f64,
     /// Integral gain.
-    pub ki:        f64,
+    pub ki:                  f64,
     /// Derivative gain.
-    pub kd:        f64,
+    pub kd:                  f64,
     /// Error tolerance in radians.
-    pub tolerance: f64,
+    pub tolerance:           f64,
     /// Maximum motor voltage (0-12 volts).
-    pub maxpwr:    f64,
+    pub maxpwr:              f64,
     /// Whether a movement is currently active.
-    pub active:    bool,
+    pub active:              bool,
     /// Target motor position in radians.
-    pub target:    f64,
+    pub target:              f64,
+    /// Whether the loop controls position or velocity.
+    pub mode:                ControlMode,
+    /// The controller used in velocity mode.
+    pub velocity_controller: VelocityController,
+    /// Target velocity in RPM, used in velocity mode.
+    pub target_velocity:     f64,
+    /// Velocity tolerance in RPM used by `at_speed`.
+    pub velocity_tolerance:  f64,
+    /// Smoothing factor for the measured velocity, from 0 to 1.
+    ///
+    /// Each loop the filtered velocity moves this fraction of the way to
+    /// the latest reading. Lower values are smoother but lag more.
+    pub velocity_filter:     f64,
+    /// Filtered measured velocity in RPM, updated by the control loop.
+    pub velocity:            f64,
+    /// Motor rotations per rotation of the output shaft.
+    pub gear_ratio:          f64,
+    /// Feedforward that holds the mechanism against gravity.
+    pub gravity:             GravityCompensation,
+    /// Minimum and maximum output-shaft angle in degrees.
+    ///
+    /// Targets are clamped to this range and output pushing further into
+    /// a stop is cut once within `limit_margin` of it.
+    pub soft_limits:         Option<(f64, f64)>,
+    /// Distance in output-shaft degrees from a soft limit where output
+    /// toward that limit is cut.
+    pub limit_margin:        f64,
+    /// Whether the encoders were zeroed by homing. When set, the control
+    /// loop keeps the homed position instead of resetting it on start.
+    pub homed:               bool,
 }
 
 impl SinglePIDValues {
     /// Uses default ArcPID Values
-    pub fn default() -> SinglePIDValues {
-        SinglePIDValues {
-            kp:        0.5,
-            ki:        0.0,
-            kd:        0.0,
-            tolerance: 0.1,
-            maxpwr:    12.0,
-            active:    true,
-            target:    0.0,
-        }
-    }
+    pub fn default() -> SinglePIDValues { SinglePIDValues::new(0.5, 0.0, 0.0, 0.1, 12.0) }
 
     pub fn new(kp: f64, ki: f64, kd: f64, tolerance: f64, maxpwr: f64) -> SinglePIDValues {
         SinglePIDValues {
@@ -243,8 +538,199 @@ impl SinglePIDValues {
             maxpwr,
             active: true,
             target: 0.0,
+            mode: ControlMode::Position,
+            velocity_controller: VelocityController::BangBang {
+                high:       maxpwr,
+                low:        0.0,
+                hysteresis: 50.0,
+            },
+            target_velocity: 0.0,
+            velocity_tolerance: 50.0,
+            velocity_filter: 0.2,
+            velocity: 0.0,
+            gear_ratio: 1.0,
+            gravity: GravityCompensation::None,
+            soft_limits: None,
+            limit_margin: 2.0,
+            homed: false,
+        }
+    }
+
+    /// Converts a motor position in radians to an output-shaft angle in degrees.
+    pub fn motor_to_output_degrees(&self, radians: f64) -> f64 {
+        radians.to_degrees() / self.gear_ratio
+    }
+
+    /// Converts an output-shaft angle in degrees to a motor position in radians.
+    pub fn output_degrees_to_motor(&self, degrees: f64) -> f64 {
+        (degrees * self.gear_ratio).to_radians()
+    }
+
+    /// Clamps a motor target in radians to the soft limits.
+    pub fn clamp_target(&self, target: f64) -> f64 {
+        match self.soft_limits {
+            Some((min, max)) => {
+                let (a, b) = (self.output_degrees_to_motor(min), self.output_degrees_to_motor(max));
+                target.clamp(a.min(b), a.max(b))
+            }
+            None => target,
         }
     }
+
+    /// Returns the voltage needed to hold the mechanism against gravity
+    /// at the given motor position in radians.
+    pub fn gravity_feedforward(&self, position: f64) -> f64 {
+        match self.gravity {
+            GravityCompensation::None => 0.0,
+            GravityCompensation::Lift { kg } => kg,
+            GravityCompensation::Arm { kg, zero_angle } => {
+                let angle = self.motor_to_output_degrees(position) + zero_angle;
+                kg * angle.to_radians().cos()
+            }
+        }
+    }
+}
+
+/// Gravity feedforward models for arms and lifts.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GravityCompensation {
+    /// No gravity compensation.
+    #[default]
+    None,
+    /// A rotating arm, needing `kg * cos(angle)` volts to hold.
+    Arm {
+        /// Voltage needed to hold the arm horizontal.
+        kg:         f64,
+        /// Arm angle above horizontal in degrees when the encoder reads zero.
+        zero_angle: f64,
+    },
+    /// A linear lift, needing a constant `kg` volts to hold.
+    Lift {
+        /// Voltage needed to hold the lift.
+        kg: f64,
+    },
+}
+
+/// Cuts output that pushes further into a soft limit.
+///
+/// Within `margin` of a limit, output toward it is capped at the gravity
+/// feedforward so the mechanism is still held but not driven into the stop.
+fn soft_limit_output(
+    u: f64,
+    feedforward: f64,
+    position: f64,
+    min: f64,
+    max: f64,
+    margin: f64,
+) -> f64 {
+    let (min, max) = (min.min(max), min.max(max));
+    let margin = margin.abs();
+    let into_max = position >= max - margin && u > feedforward;
+    let into_min = position <= min + margin && u < feedforward;
+    if into_max || into_min { feedforward } else { u }
+}
+
+/// What the single motor PID loop is controlling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlMode {
+    /// Hold a target position in radians.
+    Position,
+    /// Hold a target velocity in RPM.
+    Velocity,
+}
+
+/// Controllers available in velocity mode.
+///
+/// All gains work on velocity in RPM and output volts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VelocityController {
+    /// PID on the velocity error plus a `kv` feedforward (volts per RPM).
+    Pid {
+        /// Proportional gain.
+        kp: f64,
+        /// Integral gain.
+        ki: f64,
+        /// Derivative gain.
+        kd: f64,
+        /// Feedforward voltage per RPM of target velocity.
+        kv: f64,
+    },
+    /// Take-back-half: integrates the error and halves the output back
+    /// toward the last crossing whenever the target is crossed. Converges
+    /// without overshoot and needs only one gain.
+    TakeBackHalf {
+        /// Integration gain in volts per RPM of error per second.
+        ///
+        /// Scaled by the time between ticks, so changing the loop period
+        /// does not change the tune.
+        gain: f64,
+    },
+    /// Bang-bang with hysteresis: full `high` voltage below the band,
+    /// `low` voltage above it, and keep the last output inside it.
+    BangBang {
+        /// Voltage applied below the target.
+        high:       f64,
+        /// Voltage applied above the target.
+        low:        f64,
+        /// Half-width of the band around the target in RPM.
+        hysteresis: f64,
+    },
+}
+
+/// Internal state of the velocity controllers between loop iterations.
+#[derive(Default)]
+struct VelocityState {
+    perror: f64,
+    ierror: f64,
+    output: f64,
+    tbh:    f64,
+}
+
+impl VelocityState {
+    /// Returns the output in volts, up to `max`, for the measured `velocity`
+    /// `dt` seconds after the previous update.
+    fn update(
+        &mut self,
+        controller: VelocityController,
+        target: f64,
+        velocity: f64,
+        max: f64,
+        dt: f64,
+    ) -> f64 {
+        let error = target - velocity;
+        match controller {
+            VelocityController::Pid { kp, ki, kd, kv } => {
+                self.ierror += error * dt;
+                // Keep the integral from winding up during a long spin-up
+                if ki != 0.0 {
+                    let i_max = max / ki.abs();
+                    self.ierror = self.ierror.clamp(-i_max, i_max);
+                }
+                let derror = (error - self.perror) / dt;
+                self.output = kv * target + kp * error + ki * self.ierror + kd * derror;
+            }
+            VelocityController::TakeBackHalf { gain } => {
+                self.output = (self.output + gain * error * dt).clamp(-max, max);
+                if error.signum() != self.perror.signum() {
+                    self.output = (self.output + self.tbh) / 2.0;
+                    self.tbh = self.output;
+                }
+            }
+            VelocityController::BangBang {
+                high,
+                low,
+                hysteresis,
+            } => {
+                if error > hysteresis {
+                    self.output = high;
+                } else if error < -hysteresis {
+                    self.output = low;
+                }
+            }
+        }
+        self.perror = error;
+        self.output
+    }
 }
 
 /// Clamps a value to the range [-cap, cap].
@@ -259,3 +745,95 @@ fn abscap(val: f64, cap: f64) -> f64 {
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bang_bang_hysteresis_test() {
+        let controller = VelocityController::BangBang {
+            high:       12.0,
+            low:        2.0,
+            hysteresis: 50.0,
+        };
+        let mut state = VelocityState::default();
+        assert_eq!(state.update(controller, 3000.0, 0.0, 12.0, 0.005), 12.0);
+        // Inside the band the previous output is kept
+        assert_eq!(state.update(controller, 3000.0, 3020.0, 12.0, 0.005), 12.0);
+        assert_eq!(state.update(controller, 3000.0, 3060.0, 12.0, 0.005), 2.0);
+        assert_eq!(state.update(controller, 3000.0, 2980.0, 12.0, 0.005), 2.0);
+    }
+
+    #[test]
+    fn take_back_half_converges_test() {
+        // First-order flywheel: rpm' = (k * volts - rpm) / tau
+        let controller = VelocityController::TakeBackHalf { gain: 0.1 };
+        // The same tune settles at any loop period
+        for dt in [0.005, 0.01] {
+            let mut state = VelocityState::default();
+            let (k, tau) = (250.0, 0.4);
+            let mut rpm = 0.0;
+            for _ in 0..(20.0 / dt) as usize {
+                let volts = state.update(controller, 2000.0, rpm, 12.0, dt);
+                rpm += (k * volts - rpm) / tau * dt;
+            }
+            assert!((rpm - 2000.0).abs() < 20.0);
+            assert!((state.output - 8.0).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn velocity_pid_windup_test() {
+        let controller = VelocityController::Pid {
+            kp: 0.0,
+            ki: 0.01,
+            kd: 0.0,
+            kv: 0.0,
+        };
+        let mut state = VelocityState::default();
+        // Three seconds of spin-up far below the target
+        for _ in 0..600 {
+            state.update(controller, 3000.0, 0.0, 12.0, 0.005);
+        }
+        assert!((state.ierror - 1200.0).abs() < 1e-9);
+        // Once past the target the output drops at once instead of unwinding
+        assert!(state.update(controller, 3000.0, 3100.0, 12.0, 0.005) < 12.0);
+    }
+
+    #[test]
+    fn gear_ratio_conversion_test() {
+        let mut values = SinglePIDValues::new(0.5, 0.0, 0.0, 0.1, 12.0);
+        values.gear_ratio = 5.0;
+        let motor = values.output_degrees_to_motor(90.0);
+        assert!((motor - 5.0 * std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+        assert!((values.motor_to_output_degrees(motor) - 90.0).abs() < 1e-9);
+
+        values.soft_limits = Some((0.0, 60.0));
+        let clamped = values.clamp_target(motor);
+        assert!((values.motor_to_output_degrees(clamped) - 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn arm_gravity_test() {
+        let mut values = SinglePIDValues::new(0.5, 0.0, 0.0, 0.1, 12.0);
+        values.gravity = GravityCompensation::Arm {
+            kg:         2.0,
+            zero_angle: -90.0,
+        };
+        // Hanging straight down at zero needs no holding voltage
+        assert!(values.gravity_feedforward(0.0).abs() < 1e-9);
+        let horizontal = values.output_degrees_to_motor(90.0);
+        assert!((values.gravity_feedforward(horizontal) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn soft_limit_output_test() {
+        // Near the top limit, upward output is cut to the feedforward
+        assert_eq!(soft_limit_output(8.0, 1.0, 9.9, 0.0, 10.0, 0.5), 1.0);
+        // Moving away from the limit is allowed
+        assert_eq!(soft_limit_output(-4.0, 1.0, 9.9, 0.0, 10.0, 0.5), -4.0);
+        assert_eq!(soft_limit_output(-4.0, 0.0, 0.2, 0.0, 10.0, 0.5), 0.0);
+        assert_eq!(soft_limit_output(6.0, 0.0, 5.0, 0.0, 10.0, 0.5), 6.0);
+    }
+}
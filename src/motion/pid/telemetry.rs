@@ -0,0 +1,352 @@
+//! Per-tick recording of PID control loops.
+//!
+//! When a movement misbehaves it is hard to tell from the outside whether
+//! the gains, the sensors or the mechanism are at fault. Enabling telemetry
+//! on a controller makes its loop record every tick: the target, the
+//! measurement, the error, each of the P, I and D contributions and the
+//! voltage sent to the motors.
+//!
+//! Records are kept in memory up to a fixed capacity, so recording never
+//! allocates without bound on the Brain. The buffer is cleared when a new
+//! motion starts, and recording carries on after the controller settles so
+//! late overshoot still shows up, until the buffer is full. Once the motion
+//! is done, it can be summarized or written to the SD card as CSV.
+//!
+//! # Channels
+//!
+//! Each record is tagged with the part of the controller it belongs to:
+//!
+//! - `left` and `right`: The encoder loops of [`PIDMovement`](super::pid::PIDMovement),
+//!   in radians.
+//! - `heading`: The IMU heading loop of [`PIDMovement`](super::pid::PIDMovement),
+//!   in degrees.
+//! - `arc`: The averaged encoder loop of [`ArcPIDMovement`](super::arcpid::ArcPIDMovement),
+//!   in radians.
+//! - `single`: The position loop of [`SinglePIDMovement`](super::singlepid::SinglePIDMovement),
+//!   in motor radians. Velocity mode is not recorded.
+//! - `test`: Test motions sampled by the [`LiveTuner`](super::tuner::LiveTuner),
+//!   relative to where the motion started.
+//!
+//! # Example
+//!
+//! ```ignore
+//! pid.telemetry.enable(2000);
+//! pid.travel(24.0, pid.options()).await;
+//!
+//! if let Some(summary) = pid.telemetry.summary("left") {
+//!     info!("{}", summary);
+//! }
+//! pid.telemetry.flush("travel.csv")?;
+//! ```
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{BufWriter, Write},
+    rc::Rc,
+};
+
+/// Default number of records kept when telemetry is enabled.
+///
+/// At the default 5 ms loop period this covers 10 seconds of one channel.
+pub const DEFAULT_CAPACITY: usize = 2000;
+
+/// A single control loop tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TickRecord {
+    /// Milliseconds since the motion started.
+    pub time:        u64,
+    /// The part of the controller this tick belongs to.
+    pub channel:     &'static str,
+    /// The setpoint.
+    pub target:      f64,
+    /// The measured position.
+    pub measurement: f64,
+    /// `target - measurement`.
+    pub error:       f64,
+    /// Proportional contribution to the output in volts.
+    pub p:           f64,
+    /// Integral contribution to the output in volts.
+    pub i:           f64,
+    /// Derivative contribution to the output in volts.
+    pub d:           f64,
+    /// Voltage sent to the motors after capping.
+    pub output:      f64,
+    /// The tolerance the controller settles within.
+    pub tolerance:   f64,
+}
+
+/// Step response measurements of one recorded motion.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MotionSummary {
+    /// Milliseconds taken to go from 10% to 90% of the step, or `None` if
+    /// the measurement never reached 90%.
+    pub rise_time:          Option<u64>,
+    /// How far the measurement went past the target, as a percentage of the
+    /// step.
+    pub overshoot:          f64,
+    /// Milliseconds after which the measurement stayed within tolerance, or
+    /// `None` if the last record was still outside it.
+    pub settle_time:        Option<u64>,
+    /// Error of the last record.
+    pub steady_state_error: f64,
+}
+
+impl MotionSummary {
+    /// Summarizes the records of a single channel.
+    ///
+    /// The step runs from the first measurement to the last target. Returns
+    /// `None` if there are no records.
+    pub fn from_records(records: &[TickRecord]) -> Option<Self> {
+        let first = records.first()?;
+        let last = records.last()?;
+        let start = first.measurement;
+        let span = last.target - start;
+
+        // Fraction of the step covered by each record
+        let progress = |r: &TickRecord| {
+            if span == 0.0 {
+                1.0
+            } else {
+                (r.measurement - start) / span
+            }
+        };
+
+        let rise_start = records.iter().find(|r| progress(r) >= 0.1);
+        let rise_end = records.iter().find(|r| progress(r) >= 0.9);
+        let rise_time = match (rise_start, rise_end) {
+            (Some(a), Some(b)) => Some(b.time - a.time),
+            _ => None,
+        };
+
+        let peak = records.iter().map(progress).fold(0.0, f64::max);
+        let overshoot = if span == 0.0 {
+            0.0
+        } else {
+            ((peak - 1.0) * 100.0).max(0.0)
+        };
+
+        let settle_time = if last.error.abs() > last.tolerance {
+            None
+        } else {
+            Some(
+                records
+                    .iter()
+                    .rev()
+                    .find(|r| r.error.abs() > r.tolerance)
+                    .map_or(first.time, |r| r.time),
+            )
+        };
+
+        Some(Self {
+            rise_time,
+            overshoot,
+            settle_time,
+            steady_state_error: last.error,
+        })
+    }
+}
+
+impl core::fmt::Display for MotionSummary {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let ms = |t: Option<u64>| t.map_or("-".to_string(), |t| format!("{} ms", t));
+        write!(
+            f,
+            "rise {}, overshoot {:.1}%, settle {}, steady-state error {:.4}",
+            ms(self.rise_time),
+            self.overshoot,
+            ms(self.settle_time),
+            self.steady_state_error
+        )
+    }
+}
+
+#[derive(Default)]
+struct Buffer {
+    enabled:  bool,
+    capacity: usize,
+    records:  Vec<TickRecord>,
+    start:    u64,
+    dropped:  usize,
+}
+
+/// A bounded recorder shared between a controller and its loop.
+///
+/// Disabled by default. Clones share the same buffer.
+#[derive(Clone, Default)]
+pub struct Telemetry {
+    buffer: Rc<RefCell<Buffer>>,
+}
+
+impl Telemetry {
+    /// Starts recording, keeping at most `capacity` records per motion.
+    ///
+    /// Ticks past the capacity are dropped and counted. See
+    /// [`DEFAULT_CAPACITY`] for a reasonable size.
+    pub fn enable(&self, capacity: usize) {
+        let mut b = self.buffer.borrow_mut();
+        b.enabled = true;
+        b.capacity = capacity;
+        b.records = Vec::with_capacity(capacity);
+        b.dropped = 0;
+    }
+
+    /// Stops recording. Records already taken are kept.
+    pub fn disable(&self) { self.buffer.borrow_mut().enabled = false; }
+
+    /// Returns `true` if ticks are being recorded.
+    pub fn is_enabled(&self) -> bool { self.buffer.borrow().enabled }
+
+    /// Returns a copy of the records of the current motion.
+    pub fn records(&self) -> Vec<TickRecord> { self.buffer.borrow().records.clone() }
+
+    /// Returns the number of ticks dropped because the buffer was full.
+    pub fn dropped(&self) -> usize { self.buffer.borrow().dropped }
+
+    /// Removes all records.
+    pub fn clear(&self) {
+        let mut b = self.buffer.borrow_mut();
+        b.records.clear();
+        b.dropped = 0;
+    }
+
+    /// Summarizes the records of one channel of the current motion.
+    pub fn summary(&self, channel: &str) -> Option<MotionSummary> {
+        let b = self.buffer.borrow();
+        let records: Vec<TickRecord> = b
+            .records
+            .iter()
+            .filter(|r| r.channel == channel)
+            .copied()
+            .collect();
+        MotionSummary::from_records(&records)
+    }
+
+    /// Writes the records of the current motion to a CSV file.
+    pub fn write_csv(&self, path: &str) -> std::io::Result<()> {
+        let b = self.buffer.borrow();
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "time,channel,target,measurement,error,p,i,d,output,tolerance")?;
+        for r in &b.records {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{}",
+                r.time,
+                r.channel,
+                r.target,
+                r.measurement,
+                r.error,
+                r.p,
+                r.i,
+                r.d,
+                r.output,
+                r.tolerance
+            )?;
+        }
+        writer.flush()
+    }
+
+    /// Writes the records to a CSV file, then clears them.
+    pub fn flush(&self, path: &str) -> std::io::Result<()> {
+        self.write_csv(path)?;
+        self.clear();
+        Ok(())
+    }
+
+    /// Clears the buffer for a motion starting at `time` milliseconds of
+    /// uptime.
+    pub(crate) fn begin_motion(&self, time: u64) {
+        let mut b = self.buffer.borrow_mut();
+        if !b.enabled {
+            return;
+        }
+        b.records.clear();
+        b.dropped = 0;
+        b.start = time;
+    }
+
+    /// Records a tick. `record.time` is milliseconds of uptime and is made
+    /// relative to the start of the motion.
+    pub(crate) fn record(&self, mut record: TickRecord) {
+        let mut b = self.buffer.borrow_mut();
+        if !b.enabled {
+            return;
+        }
+        if b.records.len() >= b.capacity {
+            b.dropped += 1;
+            return;
+        }
+        record.time = record.time.saturating_sub(b.start);
+        b.records.push(record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(time: u64, target: f64, measurement: f64) -> TickRecord {
+        TickRecord {
+            time,
+            channel: "single",
+            target,
+            measurement,
+            error: target - measurement,
+            p: 0.0,
+            i: 0.0,
+            d: 0.0,
+            output: 0.0,
+            tolerance: 0.05,
+        }
+    }
+
+    #[test]
+    fn summary_test() {
+        let measurements = [0.0, 0.05, 0.3, 0.7, 0.95, 1.1, 1.04, 0.98, 1.0, 1.0];
+        let records: Vec<TickRecord> = measurements
+            .iter()
+            .enumerate()
+            .map(|(n, m)| tick(n as u64 * 10, 1.0, *m))
+            .collect();
+        let summary = MotionSummary::from_records(&records).unwrap();
+        // 10% is first reached at 20 ms and 90% at 40 ms
+        assert_eq!(summary.rise_time, Some(20));
+        assert!((summary.overshoot - 10.0).abs() < 1e-9);
+        // Last outside the tolerance at 50 ms
+        assert_eq!(summary.settle_time, Some(50));
+        assert_eq!(summary.steady_state_error, 0.0);
+    }
+
+    #[test]
+    fn summary_unsettled_test() {
+        let records = [tick(0, -2.0, 0.0), tick(5, -2.0, -1.0)];
+        let summary = MotionSummary::from_records(&records).unwrap();
+        assert_eq!(summary.rise_time, None);
+        assert_eq!(summary.overshoot, 0.0);
+        assert_eq!(summary.settle_time, None);
+        assert_eq!(summary.steady_state_error, -1.0);
+        assert!(MotionSummary::from_records(&[]).is_none());
+    }
+
+    #[test]
+    fn bounded_buffer_test() {
+        let telemetry = Telemetry::default();
+        // Nothing is recorded until enabled
+        telemetry.record(tick(0, 1.0, 0.0));
+        assert!(telemetry.records().is_empty());
+
+        telemetry.enable(2);
+        telemetry.begin_motion(100);
+        for t in [100, 105, 110] {
+            telemetry.record(tick(t, 1.0, 0.0));
+        }
+        let records = telemetry.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].time, 5);
+        assert_eq!(telemetry.dropped(), 1);
+
+        telemetry.begin_motion(200);
+        assert!(telemetry.records().is_empty());
+        assert_eq!(telemetry.dropped(), 0);
+    }
+}
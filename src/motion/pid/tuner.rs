@@ -0,0 +1,612 @@
+//! Live PID tuning from the controller.
+//!
+//! Instead of editing gains in code and re-uploading after every change, the
+//! [`LiveTuner`] lets gains be adjusted during practice with the controller's
+//! buttons. After each change a test motion can be run, and the measured
+//! settle time and overshoot are shown on the controller screen.
+//!
+//! # Controls
+//!
+//! | Button       | Action                                           |
+//! |--------------|--------------------------------------------------|
+//! | Left / Right | Select the gain (kp, ki, kd, tol, max)           |
+//! | Up / Down    | Nudge the selected gain by the step size         |
+//! | L1 / L2      | Multiply / divide the step size by 10            |
+//! | Y            | Switch controller (linear, angular, arc, single) |
+//! | A            | Run the test motion and measure it               |
+//! | X            | Log the final gains and exit                     |
+//!
+//! Test motions alternate direction so the robot drives back and forth
+//! instead of wandering off the field. The final gains are logged with
+//! [`log`], so with [`fs::logger`](crate::fs::logger) initialized they end up
+//! in `log.txt` on the SD card.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use antaeus::motion::pid::tuner::LiveTuner;
+//!
+//! pid.init();
+//! let mut tuner = LiveTuner::new();
+//! tuner.linear = Some(&pid);
+//! tuner.heading = Some(HeadingSource::Imu(imu.clone()));
+//! tuner.run(&mut controller).await;
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
+use log::info;
+use vexide::{controller::Controller, smart::motor::Motor, task::*, time::*};
+
+use super::{
+    arcpid::ArcPIDMovement,
+    autotune::{drivetrain_position, group_position},
+    pid::{HeadingSource, PIDMovement, read_heading},
+    singlepid::SinglePIDMovement,
+    telemetry::{DEFAULT_CAPACITY, MotionSummary, Telemetry, TickRecord},
+};
+use crate::{
+    motion::{lifecycle::LoopHandle, options::MotionOptions, timing::DEFAULT_LOOPRATE},
+    peripherals::drivetrain::Differential,
+};
+
+/// The controller being tuned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TunedController {
+    /// [`PIDMovement`] linear gains, tested with `travel`.
+    Linear,
+    /// [`PIDMovement`] angular gains, tested with `turn_to_heading`.
+    Angular,
+    /// [`ArcPIDMovement`] gains, tested with a straight `travel`.
+    Arc,
+    /// [`SinglePIDMovement`] gains, tested with `set_target`.
+    Single,
+}
+
+impl TunedController {
+    const ALL: [TunedController; 4] = [
+        TunedController::Linear,
+        TunedController::Angular,
+        TunedController::Arc,
+        TunedController::Single,
+    ];
+
+    /// A short label for the controller screen.
+    pub fn label(self) -> &'static str {
+        match self {
+            TunedController::Linear => "LIN",
+            TunedController::Angular => "ANG",
+            TunedController::Arc => "ARC",
+            TunedController::Single => "SGL",
+        }
+    }
+}
+
+/// The gain being tuned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TunedGain {
+    /// Proportional gain.
+    Kp,
+    /// Integral gain.
+    Ki,
+    /// Derivative gain.
+    Kd,
+    /// Error tolerance.
+    Tolerance,
+    /// Maximum voltage.
+    MaxPwr,
+}
+
+impl TunedGain {
+    const ALL: [TunedGain; 5] = [
+        TunedGain::Kp,
+        TunedGain::Ki,
+        TunedGain::Kd,
+        TunedGain::Tolerance,
+        TunedGain::MaxPwr,
+    ];
+
+    /// A short label for the controller screen.
+    pub fn label(self) -> &'static str {
+        match self {
+            TunedGain::Kp => "kp",
+            TunedGain::Ki => "ki",
+            TunedGain::Kd => "kd",
+            TunedGain::Tolerance => "tol",
+            TunedGain::MaxPwr => "max",
+        }
+    }
+
+    /// Applies a nudge of `delta` to `value`, keeping it in range.
+    pub fn nudge(self, value: f64, delta: f64) -> f64 {
+        let value = value + delta;
+        match self {
+            TunedGain::MaxPwr => value.clamp(0.0, 12.0),
+            _ => value.max(0.0),
+        }
+    }
+}
+
+/// Distances used for the test motions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TestMotions {
+    /// Linear and arc test distance in inches.
+    pub distance: f64,
+    /// Angular test turn in degrees.
+    pub angle:    f64,
+    /// Single motor group test move in radians.
+    pub single:   f64,
+    /// Timeout for each test motion in milliseconds.
+    pub timeout:  u64,
+}
+
+impl Default for TestMotions {
+    fn default() -> Self {
+        Self {
+            distance: 24.0,
+            angle:    90.0,
+            single:   1.0,
+            timeout:  3000,
+        }
+    }
+}
+
+/// Telemetry channel the test motion samples are recorded on.
+const TEST_CHANNEL: &str = "test";
+
+/// A sample of a test motion `value` at `time` milliseconds of uptime, on
+/// the way from zero to `target`.
+fn test_record(time: u64, value: f64, target: f64, tolerance: f64) -> TickRecord {
+    TickRecord {
+        time,
+        channel: TEST_CHANNEL,
+        target,
+        measurement: value,
+        error: target - value,
+        p: 0.0,
+        i: 0.0,
+        d: 0.0,
+        output: 0.0,
+        tolerance: tolerance.abs(),
+    }
+}
+
+/// What a test motion is measured with.
+enum Probe {
+    Drivetrain(Differential),
+    Heading(HeadingSource),
+    Group(Rc<RefCell<dyn AsMut<[Motor]>>>),
+}
+
+impl Probe {
+    async fn read(&self) -> f64 {
+        match self {
+            Probe::Drivetrain(drivetrain) => drivetrain_position(drivetrain),
+            Probe::Heading(source) => read_heading(source).await,
+            Probe::Group(group) => group_position(group),
+        }
+    }
+}
+
+/// Adjusts PID gains from the controller and measures test motions.
+///
+/// Set the controllers that should be tunable, then call
+/// [`run`](LiveTuner::run). The controllers must already be initialized.
+pub struct LiveTuner<'a> {
+    /// Drivetrain PID, for linear and (with `heading`) angular tuning.
+    pub linear:  Option<&'a PIDMovement>,
+    /// Arc PID.
+    pub arc:     Option<&'a ArcPIDMovement>,
+    /// Single motor group PID.
+    pub single:  Option<&'a SinglePIDMovement>,
+    /// Heading source for angular tuning.
+    pub heading: Option<HeadingSource>,
+    /// Sizes of the test motions.
+    pub test:    TestMotions,
+    kind:        TunedController,
+    gain:        TunedGain,
+    step:        f64,
+    reverse:     bool,
+    result:      String,
+}
+
+impl Default for LiveTuner<'_> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<'a> LiveTuner<'a> {
+    /// Creates a tuner with no controllers attached.
+    pub fn new() -> Self {
+        Self {
+            linear:  None,
+            arc:     None,
+            single:  None,
+            heading: None,
+            test:    TestMotions::default(),
+            kind:    TunedController::Linear,
+            gain:    TunedGain::Kp,
+            step:    0.01,
+            reverse: false,
+            result:  String::new(),
+        }
+    }
+
+    /// Runs the tuner until X is pressed, then logs the final gains.
+    pub async fn run(&mut self, controller: &mut Controller) {
+        if !self.available(self.kind) {
+            self.kind = self.next_controller();
+        }
+        info!("Live Tuner Started");
+        let mut redraw = true;
+        loop {
+            let state = controller.state().unwrap_or_default();
+            if state.button_x.is_now_pressed() {
+                break;
+            }
+            if state.button_y.is_now_pressed() {
+                self.kind = self.next_controller();
+                redraw = true;
+            }
+            if state.button_right.is_now_pressed() {
+                self.gain = self.cycle_gain(1);
+                redraw = true;
+            }
+            if state.button_left.is_now_pressed() {
+                self.gain = self.cycle_gain(TunedGain::ALL.len() - 1);
+                redraw = true;
+            }
+            if state.button_l1.is_now_pressed() {
+                self.step *= 10.0;
+                redraw = true;
+            }
+            if state.button_l2.is_now_pressed() {
+                self.step /= 10.0;
+                redraw = true;
+            }
+            let delta = if state.button_up.is_now_pressed() {
+                self.step
+            } else if state.button_down.is_now_pressed() {
+                -self.step
+            } else {
+                0.0
+            };
+            if delta != 0.0 &&
+                let Some(value) = self.read_gain(self.kind, self.gain).await
+            {
+                self.write_gain(self.kind, self.gain, self.gain.nudge(value, delta))
+                    .await;
+                redraw = true;
+            }
+            if state.button_a.is_now_pressed() {
+                let _ = controller.set_text("Testing...          ", 3, 1).await;
+                self.result = self.test_motion().await;
+                redraw = true;
+            }
+
+            if redraw {
+                self.draw(controller).await;
+                redraw = false;
+            }
+            sleep(Duration::from_millis(self.period())).await;
+        }
+        self.log_gains().await;
+        let _ = controller.clear_screen().await;
+    }
+
+    async fn draw(&self, controller: &mut Controller) {
+        let value = match self.read_gain(self.kind, self.gain).await {
+            Some(value) => format!("{:.4}", value),
+            None => "n/a".to_string(),
+        };
+        let lines = [
+            format!("{} {} {}", self.kind.label(), self.gain.label(), value),
+            format!("step {}", self.step),
+            self.result.clone(),
+        ];
+        for (line, text) in lines.iter().enumerate() {
+            // Pad to overwrite whatever was on the line before
+            let _ = controller
+                .set_text(format!("{:<19}", text), line as u8 + 1, 1)
+                .await;
+        }
+    }
+
+    /// Runs the test motion for the current controller and formats the
+    /// measured result for the screen.
+    async fn test_motion(&mut self) -> String {
+        let sign = if self.reverse { -1.0 } else { 1.0 };
+        self.reverse = !self.reverse;
+
+        let (probe, step, tolerance) = match self.kind {
+            TunedController::Linear => match self.linear {
+                Some(pid) => {
+                    let config = pid.drivetrain_config;
+                    let r = sign * config.distance_to_angle(self.test.distance);
+                    let tolerance = pid.pid_values.lock().await.tolerance;
+                    (Probe::Drivetrain(pid.drivetrain.clone()), r, tolerance)
+                }
+                None => return "no linear PID".to_string(),
+            },
+            TunedController::Angular => match (self.linear, &self.heading) {
+                (Some(pid), Some(source)) => {
+                    let tolerance = pid.angular_values.lock().await.tolerance;
+                    (Probe::Heading(source.clone()), sign * self.test.angle, tolerance)
+                }
+                _ => return "no heading".to_string(),
+            },
+            TunedController::Arc => match self.arc {
+                Some(arc) => {
+                    let config = arc.drivetrain_config;
+                    let r = sign * config.distance_to_angle(self.test.distance);
+                    let tolerance = arc.arcpid_values.lock().await.tolerance;
+                    (Probe::Drivetrain(arc.drivetrain.clone()), r, tolerance)
+                }
+                None => return "no arc PID".to_string(),
+            },
+            TunedController::Single => match self.single {
+                Some(single) => {
+                    let tolerance = single.pid_values.lock().await.tolerance;
+                    (
+                        Probe::Group(single.motorgroup.clone()),
+                        sign * self.test.single,
+                        tolerance,
+                    )
+                }
+                None => return "no single PID".to_string(),
+            },
+        };
+
+        // Headings wrap, so measure turns relative to the start
+        let start = probe.read().await;
+        let telemetry = Telemetry::default();
+        telemetry.enable(DEFAULT_CAPACITY);
+        telemetry.begin_motion(user_uptime().as_millis() as u64);
+        let running = Rc::new(Cell::new(true));
+        let period = self.period();
+        let sampler = {
+            let telemetry = telemetry.clone();
+            let running = running.clone();
+            spawn(async move {
+                while running.get() {
+                    let mut value = probe.read().await - start;
+                    if let Probe::Heading(_) = probe {
+                        value = (value + 180.0).rem_euclid(360.0) - 180.0;
+                    }
+                    let now = user_uptime().as_millis() as u64;
+                    telemetry.record(test_record(now, value, step, tolerance));
+                    sleep(Duration::from_millis(period)).await;
+                }
+            })
+        };
+
+        let timeout = self.test.timeout;
+        // Plain options so the controller defaults don't skew the measurement
+        let options = MotionOptions::default().timeout(timeout).after_delay(0);
+        match self.kind {
+            TunedController::Linear => {
+                if let Some(pid) = self.linear {
+                    pid.travel(sign * self.test.distance, options).await;
+                }
+            }
+            TunedController::Angular => {
+                if let (Some(pid), Some(source)) = (self.linear, &self.heading) {
+                    let heading = read_heading(source).await + step;
+                    pid.turn_to_heading(heading, source, options).await;
+                }
+            }
+            TunedController::Arc => {
+                if let Some(arc) = self.arc {
+                    arc.travel(sign * self.test.distance, 0.0, options).await;
+                }
+            }
+            TunedController::Single => {
+                if let Some(single) = self.single {
+                    let target = single.pid_values.lock().await.target + step;
+                    single.set_target(target).await;
+                    let start_time = user_uptime().as_millis();
+                    while single.pid_values.lock().await.active &&
+                        user_uptime().as_millis() < start_time + timeout as u128
+                    {
+                        sleep(Duration::from_millis(period)).await;
+                    }
+                }
+            }
+        }
+        // Keep sampling briefly to catch any late overshoot
+        sleep(Duration::from_millis(250)).await;
+        running.set(false);
+        sampler.await;
+
+        let Some(summary) = telemetry.summary(TEST_CHANNEL) else {
+            return "no samples".to_string();
+        };
+        info!("Tuner {} test: {}", self.kind.label(), summary);
+        format_result(&summary)
+    }
+
+    /// Loop period of the controller being tuned in milliseconds, used for
+    /// reading buttons and sampling test motions.
+    fn period(&self) -> u64 {
+        let lifecycle: Option<&LoopHandle> = match self.kind {
+            TunedController::Linear | TunedController::Angular => {
+                self.linear.map(|pid| &pid.lifecycle)
+            }
+            TunedController::Arc => self.arc.map(|arc| &arc.lifecycle),
+            TunedController::Single => self.single.map(|single| &single.lifecycle),
+        };
+        lifecycle.map_or(DEFAULT_LOOPRATE, LoopHandle::period)
+    }
+
+    fn available(&self, kind: TunedController) -> bool {
+        match kind {
+            TunedController::Linear => self.linear.is_some(),
+            TunedController::Angular => self.linear.is_some() && self.heading.is_some(),
+            TunedController::Arc => self.arc.is_some(),
+            TunedController::Single => self.single.is_some(),
+        }
+    }
+
+    fn next_controller(&self) -> TunedController {
+        let index = TunedController::ALL
+            .iter()
+            .position(|k| *k == self.kind)
+            .unwrap_or_default();
+        (1..=TunedController::ALL.len())
+            .map(|offset| TunedController::ALL[(index + offset) % TunedController::ALL.len()])
+            .find(|kind| self.available(*kind))
+            .unwrap_or(self.kind)
+    }
+
+    fn cycle_gain(&self, offset: usize) -> TunedGain {
+        let index = TunedGain::ALL
+            .iter()
+            .position(|g| *g == self.gain)
+            .unwrap_or_default();
+        TunedGain::ALL[(index + offset) % TunedGain::ALL.len()]
+    }
+
+    async fn read_gain(&self, kind: TunedController, gain: TunedGain) -> Option<f64> {
+        let values = self.gains(kind).await?;
+        let index = TunedGain::ALL.iter().position(|g| *g == gain)?;
+        values[index]
+    }
+
+    /// Returns `[kp, ki, kd, tolerance, maxpwr]` for a controller, with `ki`
+    /// as `None` when the controller has no integral term.
+    async fn gains(&self, kind: TunedController) -> Option<[Option<f64>; 5]> {
+        match kind {
+            TunedController::Linear => {
+                let s = self.linear?.pid_values.lock().await;
+                Some([Some(s.kp), Some(s.ki), Some(s.kd), Some(s.tolerance), Some(s.maxpwr)])
+            }
+            TunedController::Angular => {
+                let s = self.linear?.angular_values.lock().await;
+                Some([Some(s.kp), Some(s.ki), Some(s.kd), Some(s.tolerance), Some(s.maxpwr)])
+            }
+            TunedController::Arc => {
+                let s = self.arc?.arcpid_values.lock().await;
+                Some([Some(s.kp), None, Some(s.kd), Some(s.tolerance), Some(s.maxpwr)])
+            }
+            TunedController::Single => {
+                let s = self.single?.pid_values.lock().await;
+                Some([Some(s.kp), Some(s.ki), Some(s.kd), Some(s.tolerance), Some(s.maxpwr)])
+            }
+        }
+    }
+
+    async fn write_gain(&self, kind: TunedController, gain: TunedGain, value: f64) {
+        macro_rules! set_gain {
+            ($s:expr) => {
+                match gain {
+                    TunedGain::Kp => $s.kp = value,
+                    TunedGain::Ki => $s.ki = value,
+                    TunedGain::Kd => $s.kd = value,
+                    TunedGain::Tolerance => $s.tolerance = value,
+                    TunedGain::MaxPwr => $s.maxpwr = value,
+                }
+            };
+        }
+        match kind {
+            TunedController::Linear => {
+                if let Some(pid) = self.linear {
+                    set_gain!(pid.pid_values.lock().await);
+                }
+            }
+            TunedController::Angular => {
+                if let Some(pid) = self.linear {
+                    set_gain!(pid.angular_values.lock().await);
+                }
+            }
+            TunedController::Arc => {
+                if let Some(arc) = self.arc {
+                    let mut s = arc.arcpid_values.lock().await;
+                    match gain {
+                        TunedGain::Kp => s.kp = value,
+                        TunedGain::Ki => {}
+                        TunedGain::Kd => s.kd = value,
+                        TunedGain::Tolerance => s.tolerance = value,
+                        TunedGain::MaxPwr => s.maxpwr = value,
+                    }
+                }
+            }
+            TunedController::Single => {
+                if let Some(single) = self.single {
+                    set_gain!(single.pid_values.lock().await);
+                }
+            }
+        }
+    }
+
+    async fn log_gains(&self) {
+        for kind in TunedController::ALL {
+            if !self.available(kind) {
+                continue;
+            }
+            if let Some([kp, ki, kd, tolerance, maxpwr]) = self.gains(kind).await {
+                info!(
+                    "Tuned {:?}: kp={} ki={} kd={} tolerance={} maxpwr={}",
+                    kind,
+                    kp.unwrap_or_default(),
+                    ki.map_or("n/a".to_string(), |v| v.to_string()),
+                    kd.unwrap_or_default(),
+                    tolerance.unwrap_or_default(),
+                    maxpwr.unwrap_or_default()
+                );
+            }
+        }
+    }
+}
+
+/// Formats a test motion's settle time and overshoot for the screen.
+fn format_result(summary: &MotionSummary) -> String {
+    let settle = match summary.settle_time {
+        Some(ms) => format!("{}ms", ms),
+        None => "none".to_string(),
+    };
+    format!("ST {} OS {:.1}%", settle, summary.overshoot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summarize(target: f64, tolerance: f64, samples: &[(u64, f64)]) -> MotionSummary {
+        let records: Vec<TickRecord> = samples
+            .iter()
+            .map(|&(time, value)| test_record(time, value, target, tolerance))
+            .collect();
+        MotionSummary::from_records(&records).unwrap()
+    }
+
+    #[test]
+    fn test_motion_summary_test() {
+        let summary = summarize(
+            10.0,
+            0.5,
+            &[(0, 0.0), (100, 6.0), (200, 11.2), (300, 10.3), (400, 10.1)],
+        );
+        assert_eq!(summary.settle_time, Some(200));
+        assert!((summary.overshoot - 12.0).abs() < 1e-9);
+        assert_eq!(format_result(&summary), "ST 200ms OS 12.0%");
+    }
+
+    #[test]
+    fn test_motion_summary_reverse_test() {
+        // Steps in the negative direction measure overshoot past the target too
+        let summary = summarize(-90.0, -1.0, &[(0, 0.0), (100, -60.0), (200, -94.5), (300, -92.0)]);
+        assert_eq!(summary.settle_time, None);
+        assert!((summary.overshoot - 5.0).abs() < 1e-9);
+        assert_eq!(format_result(&summary), "ST none OS 5.0%");
+    }
+
+    #[test]
+    fn nudge_test() {
+        assert_eq!(TunedGain::Kp.nudge(0.005, -0.01), 0.0);
+        assert_eq!(TunedGain::MaxPwr.nudge(11.5, 1.0), 12.0);
+        assert!((TunedGain::Kd.nudge(0.1, 0.01) - 0.11).abs() < 1e-12);
+    }
+}
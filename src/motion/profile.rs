@@ -0,0 +1,445 @@
+//! Motion profile generation.
+//!
+//! A motion profile describes how a mechanism should move from rest to rest
+//! over a given distance without exceeding a maximum velocity, acceleration
+//! and (optionally) jerk. Sampling the profile at a time `t` gives the
+//! position, velocity and acceleration setpoints a controller should track.
+//!
+//! # Profile Shapes
+//!
+//! - **Trapezoidal**: Constant acceleration up to the maximum velocity,
+//!   cruise, then constant deceleration. Used when no jerk limit is given.
+//! - **S-curve**: Acceleration itself ramps up and down at the jerk limit,
+//!   which produces smoother starts and stops.
+//!
+//! If the distance is too short to reach the maximum velocity (or
+//! acceleration), the peak is lowered so the profile still ends at rest.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use antaeus::motion::profile::{MotionProfile, ProfileConstraints};
+//!
+//! let constraints = ProfileConstraints::new(60.0, 120.0, Some(600.0))?;
+//! let profile = MotionProfile::new(48.0, constraints);
+//!
+//! let state = profile.sample(0.5);
+//! println!("{} in, {} in/s, {} in/s²", state.position, state.velocity, state.acceleration);
+//! ```
+
+/// Errors from creating [`ProfileConstraints`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProfileError {
+    /// A limit is zero, negative or not finite.
+    InvalidLimit {
+        /// The limit that was rejected.
+        name:  &'static str,
+        /// The rejected value.
+        value: f64,
+    },
+}
+
+impl core::fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ProfileError::InvalidLimit { name, value } => {
+                write!(f, "{} must be positive and finite, got {}", name, value)
+            }
+        }
+    }
+}
+
+impl core::error::Error for ProfileError {}
+
+/// Limits used to generate a [`MotionProfile`].
+///
+/// Units are inches and seconds for drivetrain profiles, but any consistent
+/// unit can be used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProfileConstraints {
+    /// Maximum velocity.
+    pub max_velocity:     f64,
+    /// Maximum acceleration (and deceleration).
+    pub max_acceleration: f64,
+    /// Maximum jerk. `None` generates a trapezoidal profile.
+    pub max_jerk:         Option<f64>,
+}
+
+impl ProfileConstraints {
+    /// Creates a new set of profile constraints.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_velocity` - Maximum velocity.
+    /// * `max_acceleration` - Maximum acceleration.
+    /// * `max_jerk` - Maximum jerk, or `None` for a trapezoidal profile.
+    ///
+    /// Returns an error if a limit is zero, negative or not finite, since no
+    /// profile could ever finish with it.
+    pub fn new(
+        max_velocity: f64,
+        max_acceleration: f64,
+        max_jerk: Option<f64>,
+    ) -> Result<Self, ProfileError> {
+        let constraints = Self {
+            max_velocity,
+            max_acceleration,
+            max_jerk,
+        };
+        constraints.validate()?;
+        Ok(constraints)
+    }
+
+    /// Checks that every limit is positive and finite.
+    pub fn validate(&self) -> Result<(), ProfileError> {
+        let limits = [
+            ("max_velocity", Some(self.max_velocity)),
+            ("max_acceleration", Some(self.max_acceleration)),
+            ("max_jerk", self.max_jerk),
+        ];
+        for (name, value) in limits {
+            if let Some(value) = value &&
+                !(value.is_finite() && value > 0.0)
+            {
+                return Err(ProfileError::InvalidLimit { name, value });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A setpoint sampled from a [`MotionProfile`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProfileState {
+    /// Position along the profile.
+    pub position:     f64,
+    /// Velocity at this point.
+    pub velocity:     f64,
+    /// Acceleration at this point.
+    pub acceleration: f64,
+}
+
+/// A section of a profile with constant jerk.
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    duration: f64,
+    start:    ProfileState,
+    jerk:     f64,
+}
+
+impl Segment {
+    fn state_at(&self, t: f64) -> ProfileState {
+        let s = self.start;
+        ProfileState {
+            position:     s.position +
+                s.velocity * t +
+                s.acceleration * t.powi(2) / 2.0 +
+                self.jerk * t.powi(3) / 6.0,
+            velocity:     s.velocity + s.acceleration * t + self.jerk * t.powi(2) / 2.0,
+            acceleration: s.acceleration + self.jerk * t,
+        }
+    }
+}
+
+/// A rest-to-rest motion profile over a fixed distance.
+///
+/// Profiles are generated once and are cheap to sample, so they can be
+/// evaluated every control loop tick.
+#[derive(Clone, Debug)]
+pub struct MotionProfile {
+    segments:  Vec<Segment>,
+    direction: f64,
+    distance:  f64,
+}
+
+impl MotionProfile {
+    /// Generates a profile covering `distance` within `constraints`.
+    ///
+    /// Negative distances produce a profile moving in reverse. An S-curve is
+    /// generated when a jerk limit is present, otherwise a trapezoid.
+    ///
+    /// Constraints that fail [`ProfileConstraints::validate`] give an empty
+    /// profile that is finished at once and samples as the end state.
+    pub fn new(distance: f64, constraints: ProfileConstraints) -> Self {
+        if constraints.validate().is_err() {
+            return Self::from_phases(distance, &[]);
+        }
+        match constraints.max_jerk {
+            Some(jerk) => Self::s_curve(distance, constraints, jerk),
+            None => Self::trapezoidal(distance, constraints),
+        }
+    }
+
+    /// Generates a trapezoidal profile, ignoring any jerk limit.
+    pub fn trapezoidal(distance: f64, constraints: ProfileConstraints) -> Self {
+        if constraints.validate().is_err() {
+            return Self::from_phases(distance, &[]);
+        }
+        let d = distance.abs();
+        let acceleration = constraints.max_acceleration.abs();
+        let mut velocity = constraints.max_velocity.abs();
+        // Not enough room to reach max velocity: peak where the ramps meet
+        if velocity.powi(2) / acceleration > d {
+            velocity = (d * acceleration).sqrt();
+        }
+        let ramp_time = velocity / acceleration;
+        let ramp_distance = velocity * ramp_time / 2.0;
+        let cruise_time = if velocity > 0.0 {
+            (d - 2.0 * ramp_distance).max(0.0) / velocity
+        } else {
+            0.0
+        };
+        Self::from_phases(
+            distance,
+            &[
+                (ramp_time, acceleration, 0.0),
+                (cruise_time, 0.0, 0.0),
+                (ramp_time, -acceleration, 0.0),
+            ],
+        )
+    }
+
+    /// Generates a jerk-limited S-curve profile.
+    pub fn s_curve(distance: f64, constraints: ProfileConstraints, max_jerk: f64) -> Self {
+        if constraints.validate().is_err() || !(max_jerk.is_finite() && max_jerk != 0.0) {
+            return Self::from_phases(distance, &[]);
+        }
+        let d = distance.abs();
+        let jerk = max_jerk.abs();
+        let max_accel = constraints.max_acceleration.abs();
+        let mut velocity = constraints.max_velocity.abs();
+        // Shrink the peak velocity until both ramps fit inside the distance
+        if 2.0 * s_curve_ramp_distance(velocity, max_accel, jerk) > d {
+            let (mut lo, mut hi) = (0.0, velocity);
+            for _ in 0..100 {
+                let mid = (lo + hi) / 2.0;
+                if 2.0 * s_curve_ramp_distance(mid, max_accel, jerk) > d {
+                    hi = mid;
+                } else {
+                    lo = mid;
+                }
+            }
+            velocity = lo;
+        }
+        let (peak_accel, jerk_time, accel_time) = s_curve_ramp(velocity, max_accel, jerk);
+        let ramp_distance = s_curve_ramp_distance(velocity, max_accel, jerk);
+        let cruise_time = if velocity > 0.0 {
+            (d - 2.0 * ramp_distance).max(0.0) / velocity
+        } else {
+            0.0
+        };
+        Self::from_phases(
+            distance,
+            &[
+                (jerk_time, 0.0, jerk),
+                (accel_time, peak_accel, 0.0),
+                (jerk_time, peak_accel, -jerk),
+                (cruise_time, 0.0, 0.0),
+                (jerk_time, 0.0, -jerk),
+                (accel_time, -peak_accel, 0.0),
+                (jerk_time, -peak_accel, jerk),
+            ],
+        )
+    }
+
+    /// Builds the segments from `(duration, starting acceleration, jerk)` phases.
+    fn from_phases(distance: f64, phases: &[(f64, f64, f64)]) -> Self {
+        let mut segments = Vec::new();
+        let mut state = ProfileState::default();
+        for &(duration, acceleration, jerk) in phases {
+            if duration <= 0.0 {
+                continue;
+            }
+            state.acceleration = acceleration;
+            let segment = Segment {
+                duration,
+                start: state,
+                jerk,
+            };
+            state = segment.state_at(duration);
+            segments.push(segment);
+        }
+        Self {
+            segments,
+            direction: if distance < 0.0 { -1.0 } else { 1.0 },
+            distance,
+        }
+    }
+
+    /// The total duration of the profile in seconds.
+    pub fn duration(&self) -> f64 { self.segments.iter().map(|s| s.duration).sum() }
+
+    /// The distance covered by the profile.
+    pub fn distance(&self) -> f64 { self.distance }
+
+    /// Samples the profile `t` seconds after it starts.
+    ///
+    /// Times before the start return the initial state, and times after the
+    /// end return the final state at rest.
+    pub fn sample(&self, t: f64) -> ProfileState {
+        if t <= 0.0 {
+            return ProfileState::default();
+        }
+        let mut elapsed = 0.0;
+        for segment in &self.segments {
+            if t < elapsed + segment.duration {
+                let state = segment.state_at(t - elapsed);
+                return ProfileState {
+                    position:     state.position * self.direction,
+                    velocity:     state.velocity * self.direction,
+                    acceleration: state.acceleration * self.direction,
+                };
+            }
+            elapsed += segment.duration;
+        }
+        ProfileState {
+            position:     self.distance,
+            velocity:     0.0,
+            acceleration: 0.0,
+        }
+    }
+
+    /// Whether the profile has finished `t` seconds after it starts.
+    pub fn is_finished(&self, t: f64) -> bool { t >= self.duration() }
+}
+
+/// Peak acceleration, jerk phase duration and constant acceleration phase
+/// duration of an S-curve ramp from rest to `velocity`.
+fn s_curve_ramp(velocity: f64, max_accel: f64, jerk: f64) -> (f64, f64, f64) {
+    if velocity * jerk >= max_accel.powi(2) {
+        let jerk_time = max_accel / jerk;
+        (max_accel, jerk_time, velocity / max_accel - jerk_time)
+    } else {
+        let peak = (velocity * jerk).sqrt();
+        (peak, peak / jerk, 0.0)
+    }
+}
+
+/// Distance covered by an S-curve ramp from rest to `velocity`.
+fn s_curve_ramp_distance(velocity: f64, max_accel: f64, jerk: f64) -> f64 {
+    let (_, jerk_time, accel_time) = s_curve_ramp(velocity, max_accel, jerk);
+    // The ramp is symmetric, so the average velocity is half the peak
+    velocity * (2.0 * jerk_time + accel_time) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f64 = 1e-6;
+
+    fn check_limits(profile: &MotionProfile, constraints: ProfileConstraints) {
+        let dt = 0.001;
+        let steps = (profile.duration() / dt) as usize + 2;
+        for i in 0..steps {
+            let s = profile.sample(i as f64 * dt);
+            assert!(s.velocity.abs() <= constraints.max_velocity + EPS);
+            assert!(s.acceleration.abs() <= constraints.max_acceleration + EPS);
+        }
+    }
+
+    #[test]
+    fn trapezoidal_reaches_distance_test() {
+        let c = ProfileConstraints::new(60.0, 120.0, None).unwrap();
+        let profile = MotionProfile::new(48.0, c);
+        // 0.5s ramps covering 15in each, 18in cruise at 60in/s
+        assert!((profile.duration() - 1.3).abs() < EPS);
+        let end = profile.sample(profile.duration() - EPS);
+        assert!((end.position - 48.0).abs() < 1e-3);
+        assert!((profile.sample(0.75).velocity - 60.0).abs() < EPS);
+        assert_eq!(
+            profile.sample(10.0),
+            ProfileState {
+                position:     48.0,
+                velocity:     0.0,
+                acceleration: 0.0,
+            }
+        );
+        check_limits(&profile, c);
+    }
+
+    #[test]
+    fn trapezoidal_triangle_test() {
+        let c = ProfileConstraints::new(60.0, 100.0, None).unwrap();
+        let profile = MotionProfile::new(4.0, c);
+        // Peak velocity sqrt(4 * 100) = 20in/s, reached at 0.2s
+        assert!((profile.duration() - 0.4).abs() < EPS);
+        assert!((profile.sample(0.2).velocity - 20.0).abs() < 1e-3);
+        check_limits(&profile, c);
+    }
+
+    #[test]
+    fn reverse_profile_test() {
+        let c = ProfileConstraints::new(60.0, 120.0, None).unwrap();
+        let profile = MotionProfile::new(-48.0, c);
+        assert!(profile.sample(0.75).velocity < 0.0);
+        assert!((profile.sample(profile.duration()).position + 48.0).abs() < EPS);
+    }
+
+    #[test]
+    fn s_curve_reaches_distance_test() {
+        let c = ProfileConstraints::new(60.0, 120.0, Some(600.0)).unwrap();
+        let profile = MotionProfile::new(48.0, c);
+        let end = profile.sample(profile.duration() - EPS);
+        assert!((end.position - 48.0).abs() < 1e-3);
+        assert!(end.velocity.abs() < 1e-3);
+        // An S-curve takes longer than the trapezoid with the same limits
+        assert!(profile.duration() > MotionProfile::trapezoidal(48.0, c).duration());
+        check_limits(&profile, c);
+    }
+
+    #[test]
+    fn s_curve_continuity_test() {
+        let c = ProfileConstraints::new(60.0, 120.0, Some(600.0)).unwrap();
+        let profile = MotionProfile::new(48.0, c);
+        let dt = 0.0005;
+        let mut prev = profile.sample(0.0);
+        let mut t = dt;
+        while t < profile.duration() + dt {
+            let s = profile.sample(t);
+            // Acceleration may only change at the jerk limit
+            assert!((s.acceleration - prev.acceleration).abs() <= 600.0 * dt + EPS);
+            assert!((s.velocity - prev.velocity).abs() <= 120.0 * dt + EPS);
+            prev = s;
+            t += dt;
+        }
+    }
+
+    #[test]
+    fn invalid_constraints_test() {
+        assert!(matches!(
+            ProfileConstraints::new(60.0, 0.0, None),
+            Err(ProfileError::InvalidLimit {
+                name: "max_acceleration",
+                ..
+            })
+        ));
+        assert!(ProfileConstraints::new(f64::NAN, 120.0, None).is_err());
+        assert!(ProfileConstraints::new(60.0, 120.0, Some(-1.0)).is_err());
+
+        // Built by hand, a zero limit still gives a profile that finishes
+        let c = ProfileConstraints {
+            max_velocity:     60.0,
+            max_acceleration: 0.0,
+            max_jerk:         None,
+        };
+        let profile = MotionProfile::new(12.0, c);
+        assert_eq!(profile.duration(), 0.0);
+        assert!(profile.is_finished(0.0));
+        assert_eq!(profile.sample(0.1).position, 12.0);
+        let profile =
+            MotionProfile::s_curve(12.0, ProfileConstraints::new(60.0, 120.0, None).unwrap(), 0.0);
+        assert!(profile.is_finished(0.0));
+    }
+
+    #[test]
+    fn s_curve_short_distance_test() {
+        let c = ProfileConstraints::new(60.0, 120.0, Some(600.0)).unwrap();
+        let profile = MotionProfile::new(1.0, c);
+        let end = profile.sample(profile.duration());
+        assert!((end.position - 1.0).abs() < EPS);
+        let mid = profile.sample(profile.duration() / 2.0);
+        assert!((mid.position - 0.5).abs() < 1e-3);
+        check_limits(&profile, c);
+    }
+}
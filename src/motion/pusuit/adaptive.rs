@@ -0,0 +1,603 @@
+//! Path generation for adaptive pure pursuit.
+//!
+//! A hand-written [`Path`] is only a few waypoints with sharp corners
+//! between them. Before following it, [`AdaptivePath::generate`] turns it
+//! into something the robot can drive smoothly:
+//!
+//! 1. **Injection**: Points are added along each segment at a fixed
+//!    spacing, so the later steps have enough points to work with.
+//! 2. **Smoothing**: Gradient smoothing pulls every point towards its
+//!    neighbours, rounding off the corners while staying close to the
+//!    original waypoints.
+//! 3. **Curvature**: The curvature at each point is that of the circle
+//!    through it and its two neighbours.
+//! 4. **Velocities**: Each point gets the lowest of the maximum velocity
+//!    and `turn_constant / curvature`, so the robot slows for turns. A
+//!    backward pass then limits each velocity to what the robot can still
+//!    brake from before the next point, ending the path at rest.
+//!
+//! While following, a [`RateLimiter`] keeps the commanded velocity from
+//! rising faster than the maximum acceleration.
+//!
+//! # Lookahead
+//!
+//! An [`AdaptiveLookahead`] varies the lookahead distance while following:
+//! it grows with speed, so straights don't oscillate, and shrinks before
+//! curves, so corners aren't cut. Waypoints can override it with
+//! [`Waypoint::lookahead`] when the path is built with
+//! [`AdaptivePath::from_waypoints`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! let path = Path::from_vec(vec![
+//!     Point::new(0.0, 0.0),
+//!     Point::new(0.0, 48.0),
+//!     Point::new(48.0, 48.0),
+//! ]);
+//! let config = AdaptiveConfig { max_velocity: 40.0, ..Default::default() };
+//! let path = AdaptivePath::generate(&path, &config);
+//!
+//! pursuit.follow_adaptive(&odom, &velocity, &path, odom.options()).await;
+//! ```
+
+use super::geo::{Path, Point, Waypoint};
+
+/// Most smoothing iterations run before giving up on converging.
+const MAX_SMOOTH_ITERATIONS: usize = 1000;
+
+/// Settings for [`AdaptivePath::generate`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveConfig {
+    /// Distance between injected points in inches.
+    pub spacing:          f64,
+    /// How strongly points are pulled towards their neighbours, between 0
+    /// and 1. Higher values give rounder paths that stray further from the
+    /// waypoints.
+    pub smoothing:        f64,
+    /// Smoothing stops once a pass moves the points less than this in
+    /// total, in inches.
+    pub smooth_tolerance: f64,
+    /// The fastest the robot drives in inches per second.
+    pub max_velocity:     f64,
+    /// The slowest the robot drives in inches per second, so it still
+    /// reaches the end of the path.
+    pub min_velocity:     f64,
+    /// The fastest the robot speeds up or slows down in inches per second
+    /// squared.
+    pub max_acceleration: f64,
+    /// How fast the robot takes turns: the velocity at a point is at most
+    /// `turn_constant / curvature`. Usually between 1 and 5.
+    pub turn_constant:    f64,
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        Self {
+            spacing:          6.0,
+            smoothing:        0.8,
+            smooth_tolerance: 0.001,
+            max_velocity:     48.0,
+            min_velocity:     4.0,
+            max_acceleration: 72.0,
+            turn_constant:    3.0,
+        }
+    }
+}
+
+/// A path with a curvature and target velocity at each point.
+#[derive(Clone)]
+pub struct AdaptivePath {
+    /// The injected and smoothed path.
+    pub path:             Path,
+    /// Curvature at each point in radians per inch, positive turning right.
+    pub curvatures:       Vec<f64>,
+    /// Target velocity at each point in inches per second.
+    pub velocities:       Vec<f64>,
+    /// The fastest the commanded velocity may rise while following, in
+    /// inches per second squared.
+    pub max_acceleration: f64,
+    /// Lookahead distance override in inches at each point, if any.
+    pub lookaheads:       Vec<Option<f64>>,
+}
+
+impl AdaptivePath {
+    /// Injects, smooths and assigns velocities to `path`.
+    pub fn generate(path: &Path, config: &AdaptiveConfig) -> Self {
+        let path = smooth(
+            &inject(path, config.spacing),
+            config.smoothing,
+            config.smooth_tolerance,
+        );
+        let curvatures = curvatures(&path);
+        let unset = vec![None; path.waypoints.len()];
+        let velocities = velocities(&path, &curvatures, &unset, config);
+        Self {
+            path,
+            curvatures,
+            velocities,
+            max_acceleration: config.max_acceleration,
+            lookaheads: unset,
+        }
+    }
+
+    /// Like [`generate`](AdaptivePath::generate), but keeps each waypoint's
+    /// speed and lookahead override on the points injected after it.
+    ///
+    /// A waypoint's [`speed`](Waypoint::speed) caps the velocity up to the
+    /// next waypoint, and the robot brakes ahead of it like it does for
+    /// turns. [`heading`](Waypoint::heading) is not used for following.
+    pub fn from_waypoints(waypoints: &[Waypoint], config: &AdaptiveConfig) -> Self {
+        let path = Path::from_vec(waypoints.iter().map(|w| w.point).collect());
+        let mut adaptive = Self::generate(&path, config);
+        let points = adaptive.path.waypoints.len();
+        let per_point = |value: fn(&Waypoint) -> Option<f64>| {
+            let mut values = Vec::with_capacity(points);
+            for (pair, waypoint) in path.waypoints.windows(2).zip(waypoints) {
+                let length = (pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y);
+                let count = segment_points(length, config.spacing);
+                values.extend(std::iter::repeat_n(value(waypoint), count));
+            }
+            if let Some(last) = waypoints.last() {
+                values.push(value(last));
+            }
+            values
+        };
+        let speeds = per_point(|w| w.speed);
+        adaptive.lookaheads = per_point(|w| w.lookahead);
+        adaptive.velocities = velocities(&adaptive.path, &adaptive.curvatures, &speeds, config);
+        adaptive
+    }
+
+    /// Uses `path` as it is, at a constant `velocity` and without
+    /// acceleration limits.
+    pub fn unprofiled(path: Path, velocity: f64) -> Self {
+        let n = path.waypoints.len();
+        Self {
+            curvatures: curvatures(&path),
+            velocities: vec![velocity; n],
+            lookaheads: vec![None; n],
+            path,
+            max_acceleration: f64::INFINITY,
+        }
+    }
+
+    /// Returns the index of the point closest to `point`, searching from
+    /// index `from` onwards so the robot never goes back along the path.
+    pub fn closest_index(&self, from: usize, point: Point) -> usize {
+        let waypoints = &self.path.waypoints;
+        let mut closest = from.min(waypoints.len().saturating_sub(1));
+        let mut best = f64::MAX;
+        for (i, p) in waypoints.iter().enumerate().skip(closest) {
+            let d = (p.x - point.x).hypot(p.y - point.y);
+            if d < best {
+                best = d;
+                closest = i;
+            }
+        }
+        closest
+    }
+
+    /// Returns the sharpest curvature, ignoring direction, over the points
+    /// within `distance` inches along the path after index `from`.
+    pub fn upcoming_curvature(&self, from: usize, distance: f64) -> f64 {
+        let waypoints = &self.path.waypoints;
+        let mut sharpest = 0.0_f64;
+        let mut travelled = 0.0;
+        for i in from..waypoints.len() {
+            if i > from {
+                let (a, b) = (waypoints[i - 1], waypoints[i]);
+                travelled += (b.x - a.x).hypot(b.y - a.y);
+                if travelled > distance {
+                    break;
+                }
+            }
+            sharpest = sharpest.max(self.curvatures[i].abs());
+        }
+        sharpest
+    }
+}
+
+/// Settings for a lookahead distance that changes while following.
+///
+/// Starting from [`Pursuit::lookahead`](super::Pursuit::lookahead), the
+/// lookahead is
+///
+/// ```text
+/// (lookahead + speed_gain * speed) / (1 + curvature_gain * curvature)
+/// ```
+///
+/// where `curvature` is the sharpest within [`preview`](Self::preview)
+/// inches ahead, or a waypoint's override, clamped to
+/// [`min`](Self::min)..[`max`](Self::max).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveLookahead {
+    /// The shortest lookahead in inches.
+    pub min:            f64,
+    /// The longest lookahead in inches.
+    pub max:            f64,
+    /// Inches of lookahead added per inch per second of speed.
+    pub speed_gain:     f64,
+    /// How much curves shorten the lookahead, in inches. A curvature of
+    /// `1 / curvature_gain` halves it.
+    pub curvature_gain: f64,
+    /// How far ahead along the path to look for curves, in inches.
+    pub preview:        f64,
+}
+
+impl Default for AdaptiveLookahead {
+    fn default() -> Self {
+        Self {
+            min:            6.0,
+            max:            24.0,
+            speed_gain:     0.2,
+            curvature_gain: 10.0,
+            preview:        12.0,
+        }
+    }
+}
+
+impl AdaptiveLookahead {
+    /// A lookahead that stays at `base` whatever the speed and curvature.
+    ///
+    /// Waypoint overrides are still clamped, to the default bounds widened
+    /// to include `base`.
+    pub fn fixed(base: f64) -> Self {
+        let defaults = Self::default();
+        Self {
+            min: defaults.min.min(base),
+            max: defaults.max.max(base),
+            speed_gain: 0.0,
+            curvature_gain: 0.0,
+            ..defaults
+        }
+    }
+
+    /// Returns the lookahead for a robot driving at `speed` towards
+    /// `curvature`, from `base` or the waypoint's `override_distance`.
+    pub fn distance(
+        &self,
+        base: f64,
+        speed: f64,
+        curvature: f64,
+        override_distance: Option<f64>,
+    ) -> f64 {
+        let lookahead = override_distance.unwrap_or_else(|| {
+            (base + self.speed_gain * speed.abs()) / (1.0 + self.curvature_gain * curvature.abs())
+        });
+        // Not `clamp`, which panics on a bad configuration
+        lookahead.max(self.min).min(self.max)
+    }
+}
+
+/// Adds points along each segment of `path` every `spacing` inches.
+///
+/// The original waypoints are kept.
+pub fn inject(path: &Path, spacing: f64) -> Path {
+    let waypoints = &path.waypoints;
+    let mut injected = Vec::new();
+    for pair in waypoints.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let (dx, dy) = (end.x - start.x, end.y - start.y);
+        let length = dx.hypot(dy);
+        let count = segment_points(length, spacing);
+        for i in 0..count {
+            let t = i as f64 / count as f64;
+            injected.push(Point::new(start.x + dx * t, start.y + dy * t));
+        }
+    }
+    if let Some(last) = waypoints.last() {
+        injected.push(*last);
+    }
+    Path::from_vec(injected)
+}
+
+/// Number of points [`inject`] puts on a segment of `length`, including
+/// its start but not its end.
+fn segment_points(length: f64, spacing: f64) -> usize {
+    if spacing > 0.0 {
+        (length / spacing).ceil().max(1.0) as usize
+    } else {
+        1
+    }
+}
+
+/// Rounds off the corners of `path` with gradient smoothing.
+///
+/// Each pass moves every point except the ends towards its original
+/// position, weighted `1 - smoothing`, and towards the midpoint of its
+/// neighbours, weighted `smoothing`. Passes repeat until the points move
+/// less than `tolerance` in total.
+pub fn smooth(path: &Path, smoothing: f64, tolerance: f64) -> Path {
+    let original = &path.waypoints;
+    let mut smoothed = original.clone();
+    let weight_data = 1.0 - smoothing;
+    for _ in 0..MAX_SMOOTH_ITERATIONS {
+        let mut change = 0.0;
+        for i in 1..smoothed.len().saturating_sub(1) {
+            let (prev, next) = (smoothed[i - 1], smoothed[i + 1]);
+            let p = &mut smoothed[i];
+            let old = *p;
+            p.x += weight_data * (original[i].x - p.x) + smoothing * (prev.x + next.x - 2.0 * p.x);
+            p.y += weight_data * (original[i].y - p.y) + smoothing * (prev.y + next.y - 2.0 * p.y);
+            change += (p.x - old.x).abs() + (p.y - old.y).abs();
+        }
+        if change < tolerance {
+            break;
+        }
+    }
+    Path::from_vec(smoothed)
+}
+
+/// Returns the curvature at each point of `path` in radians per inch,
+/// positive turning right.
+///
+/// The curvature is that of the circle through a point and its
+/// neighbours, and zero at the ends and on straight sections.
+pub fn curvatures(path: &Path) -> Vec<f64> {
+    let waypoints = &path.waypoints;
+    let mut curvatures = vec![0.0; waypoints.len()];
+    for i in 1..waypoints.len().saturating_sub(1) {
+        curvatures[i] = three_point_curvature(waypoints[i - 1], waypoints[i], waypoints[i + 1]);
+    }
+    curvatures
+}
+
+fn three_point_curvature(a: Point, b: Point, c: Point) -> f64 {
+    let ab = (b.x - a.x).hypot(b.y - a.y);
+    let bc = (c.x - b.x).hypot(c.y - b.y);
+    let ca = (a.x - c.x).hypot(a.y - c.y);
+    let product = ab * bc * ca;
+    if product < f64::EPSILON {
+        return 0.0;
+    }
+    // Twice the signed area of the triangle, positive for a left turn
+    let cross = (b.x - a.x) * (c.y - b.y) - (b.y - a.y) * (c.x - b.x);
+    -2.0 * cross / product
+}
+
+/// Returns the target velocity at each point of `path`, capped at the
+/// point's speed in `speeds` if it has one.
+fn velocities(
+    path: &Path,
+    curvatures: &[f64],
+    speeds: &[Option<f64>],
+    config: &AdaptiveConfig,
+) -> Vec<f64> {
+    let waypoints = &path.waypoints;
+    let mut velocities: Vec<f64> = curvatures
+        .iter()
+        .zip(speeds)
+        .map(|(k, speed)| {
+            let v = if k.abs() < f64::EPSILON {
+                config.max_velocity
+            } else {
+                config.max_velocity.min(config.turn_constant / k.abs())
+            };
+            speed.map_or(v, |speed| v.min(speed))
+        })
+        .collect();
+
+    // Come to rest at the end, braking no harder than the acceleration limit
+    if let Some(last) = velocities.last_mut() {
+        *last = 0.0;
+    }
+    for i in (0..velocities.len().saturating_sub(1)).rev() {
+        let distance =
+            (waypoints[i + 1].x - waypoints[i].x).hypot(waypoints[i + 1].y - waypoints[i].y);
+        let reachable =
+            (velocities[i + 1].powi(2) + 2.0 * config.max_acceleration * distance).sqrt();
+        velocities[i] = velocities[i].min(reachable);
+    }
+
+    for v in &mut velocities {
+        *v = v.max(config.min_velocity);
+    }
+    velocities
+}
+
+/// Limits how fast a value may rise.
+///
+/// Decreases pass through at once, so the robot can still slow down as
+/// quickly as the path asks it to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimiter {
+    /// The fastest the value may rise, in units per second.
+    pub max_rate: f64,
+    value:        f64,
+}
+
+impl RateLimiter {
+    /// Creates a limiter starting at zero.
+    pub fn new(max_rate: f64) -> Self {
+        Self {
+            max_rate,
+            value: 0.0,
+        }
+    }
+
+    /// Moves towards `target`, rising by at most `max_rate * dt`, and
+    /// returns the new value.
+    pub fn update(&mut self, target: f64, dt: f64) -> f64 {
+        self.value = target.min(self.value + self.max_rate * dt);
+        self.value
+    }
+
+    /// Returns the current value.
+    pub fn value(&self) -> f64 { self.value }
+
+    /// Sets the current value, such as the robot's velocity at the start.
+    pub fn reset(&mut self, value: f64) { self.value = value; }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corner() -> Path {
+        Path::from_vec(vec![
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 24.0),
+            Point::new(24.0, 24.0),
+        ])
+    }
+
+    #[test]
+    fn inject_test() {
+        let path = inject(
+            &Path::from_vec(vec![Point::new(0.0, 0.0), Point::new(0.0, 10.0)]),
+            4.0,
+        );
+        // 10 inches at 4 inch spacing needs three segments
+        assert_eq!(path.waypoints.len(), 4);
+        assert_eq!(path.waypoints[0], Point::new(0.0, 0.0));
+        assert!((path.waypoints[1].y - 10.0 / 3.0).abs() < 1e-12);
+        assert_eq!(path.waypoints[3], Point::new(0.0, 10.0));
+
+        let path = inject(&corner(), 6.0);
+        assert_eq!(path.waypoints.len(), 9);
+        assert_eq!(path.waypoints[4], Point::new(0.0, 24.0));
+    }
+
+    #[test]
+    fn smooth_test() {
+        let original = inject(&corner(), 6.0);
+        let smoothed = smooth(&original, 0.8, 0.001);
+        // The ends stay put
+        assert_eq!(smoothed.waypoints[0], original.waypoints[0]);
+        assert_eq!(smoothed.waypoints[8], original.waypoints[8]);
+        // The corner is cut towards the inside of the turn
+        let corner = smoothed.waypoints[4];
+        assert!(corner.x > 0.0 && corner.y < 24.0);
+        // Without smoothing nothing moves
+        let unchanged = smooth(&original, 0.0, 0.001);
+        assert_eq!(unchanged.waypoints, original.waypoints);
+    }
+
+    #[test]
+    fn curvature_test() {
+        // Three points on a circle of radius 10, turning right
+        let path = Path::from_vec(vec![
+            Point::new(-10.0, 0.0),
+            Point::new(0.0, 10.0),
+            Point::new(10.0, 0.0),
+        ]);
+        let k = curvatures(&path);
+        assert_eq!(k[0], 0.0);
+        assert!((k[1] - 0.1).abs() < 1e-12);
+        // The same points in reverse turn left
+        let mut reversed = path.clone();
+        reversed.waypoints.reverse();
+        assert!((curvatures(&reversed)[1] + 0.1).abs() < 1e-12);
+        // Straight lines have no curvature
+        let line = inject(
+            &Path::from_vec(vec![Point::new(0.0, 0.0), Point::new(0.0, 12.0)]),
+            4.0,
+        );
+        assert!(curvatures(&line).iter().all(|k| *k == 0.0));
+    }
+
+    #[test]
+    fn velocities_test() {
+        let config = AdaptiveConfig {
+            max_velocity: 40.0,
+            min_velocity: 2.0,
+            max_acceleration: 50.0,
+            turn_constant: 2.0,
+            ..Default::default()
+        };
+        let line = inject(
+            &Path::from_vec(vec![Point::new(0.0, 0.0), Point::new(0.0, 100.0)]),
+            1.0,
+        );
+        let path = AdaptivePath::generate(&line, &config);
+        let v = &path.velocities;
+        // Cruising in the middle, braking at the end, never below the minimum
+        assert_eq!(v[0], 40.0);
+        assert_eq!(v[50], 40.0);
+        assert_eq!(*v.last().unwrap(), 2.0);
+        assert!((v[99] - 10.0).abs() < 1e-9);
+        assert!(v.windows(2).skip(50).all(|w| w[1] <= w[0]));
+
+        // Tight turns are taken slower than straights
+        let path = AdaptivePath::generate(&corner(), &config);
+        let turn = path.velocities[4];
+        assert!(turn < path.velocities[1]);
+        assert!(turn <= config.turn_constant / path.curvatures[4].abs() + 1e-9);
+    }
+
+    #[test]
+    fn from_waypoints_test() {
+        let mut waypoints: Vec<Waypoint> =
+            corner().waypoints.into_iter().map(Waypoint::new).collect();
+        waypoints[0].lookahead = Some(4.0);
+        waypoints[2].lookahead = Some(10.0);
+        let path = AdaptivePath::from_waypoints(&waypoints, &AdaptiveConfig::default());
+        assert_eq!(path.lookaheads.len(), path.path.waypoints.len());
+        // Each waypoint's override lasts until the next waypoint
+        assert!(path.lookaheads[..4].iter().all(|l| *l == Some(4.0)));
+        assert!(path.lookaheads[4..8].iter().all(|l| l.is_none()));
+        assert_eq!(path.lookaheads[8], Some(10.0));
+    }
+
+    #[test]
+    fn from_waypoints_speed_test() {
+        let mut waypoints = vec![
+            Waypoint::new(Point::new(0.0, 0.0)),
+            Waypoint::new(Point::new(0.0, 24.0)),
+            Waypoint::new(Point::new(0.0, 48.0)),
+        ];
+        waypoints[1].speed = Some(10.0);
+        let config = AdaptiveConfig::default();
+        let path = AdaptivePath::from_waypoints(&waypoints, &config);
+        // The speed caps the segment after the waypoint
+        assert_eq!(path.velocities[0], config.max_velocity);
+        assert!(path.velocities[4..8].iter().all(|v| *v <= 10.0));
+        // and the robot brakes for it ahead of time
+        let braking = (10.0_f64.powi(2) + 2.0 * config.max_acceleration * 6.0).sqrt();
+        assert!((path.velocities[3] - braking).abs() < 1e-9);
+    }
+
+    #[test]
+    fn upcoming_curvature_test() {
+        let path = AdaptivePath::generate(&corner(), &AdaptiveConfig::default());
+        let sharpest = path.curvatures.iter().fold(0.0_f64, |a, k| a.max(k.abs()));
+        // The first point is straight, but the corner is within reach
+        assert_eq!(path.upcoming_curvature(0, 0.0), 0.0);
+        assert_eq!(path.upcoming_curvature(0, 100.0), sharpest);
+        assert!(path.upcoming_curvature(0, 6.5) < sharpest);
+    }
+
+    #[test]
+    fn lookahead_test() {
+        let lookahead = AdaptiveLookahead::default();
+        // Faster means further, up to the maximum
+        assert_eq!(lookahead.distance(12.0, 0.0, 0.0, None), 12.0);
+        assert_eq!(lookahead.distance(12.0, -30.0, 0.0, None), 18.0);
+        assert_eq!(lookahead.distance(12.0, 100.0, 0.0, None), 24.0);
+        // Curves shorten it, down to the minimum
+        assert_eq!(lookahead.distance(12.0, 0.0, 0.1, None), 6.0);
+        assert_eq!(lookahead.distance(12.0, 0.0, 1.0, None), 6.0);
+        assert_eq!(lookahead.distance(12.0, 30.0, 0.05, None), 12.0);
+        // Overrides replace it but are still clamped
+        assert_eq!(lookahead.distance(12.0, 30.0, 0.0, Some(8.0)), 8.0);
+        assert_eq!(lookahead.distance(12.0, 30.0, 0.0, Some(40.0)), 24.0);
+
+        // A fixed lookahead ignores speed and curves, but clamps overrides
+        let fixed = AdaptiveLookahead::fixed(30.0);
+        assert_eq!(fixed.distance(30.0, 40.0, 0.5, None), 30.0);
+        assert_eq!(fixed.distance(30.0, 0.0, 0.0, Some(1.0)), 6.0);
+        assert_eq!(fixed.distance(30.0, 0.0, 0.0, Some(50.0)), 30.0);
+    }
+
+    #[test]
+    fn rate_limiter_test() {
+        let mut limiter = RateLimiter::new(100.0);
+        assert_eq!(limiter.update(40.0, 0.1), 10.0);
+        assert_eq!(limiter.update(40.0, 0.1), 20.0);
+        // Slowing down is not limited
+        assert_eq!(limiter.update(5.0, 0.1), 5.0);
+        limiter.reset(30.0);
+        assert_eq!(limiter.value(), 30.0);
+    }
+}
@@ -176,6 +176,38 @@ pub fn pursuit_target(path: geo::Path, cir: geo::Circle) -> geo::Point {
     get_target(candidates, path)
 }
 
+/// Calculates the curvature of the arc from the robot to `target`.
+///
+/// The arc starts tangent to the robot's heading. `pose` is the robot's
+/// `(x, y, heading)` with the heading in degrees, clockwise from the
+/// positive y-axis like the odometry heading. Positive curvature turns
+/// right.
+pub fn curvature_to_target(pose: (f64, f64, f64), target: geo::Point) -> f64 {
+    let (x, y, heading) = pose;
+    let heading = heading.to_radians();
+    let (dx, dy) = (target.x - x, target.y - y);
+    // Offset of the target to the right of the robot
+    let lateral = dx * heading.cos() - dy * heading.sin();
+    let distance_sq = dx * dx + dy * dy;
+    if distance_sq < f64::EPSILON {
+        return 0.0;
+    }
+    2.0 * lateral / distance_sq
+}
+
+/// Turns a curvature into a `(linear, angular)` velocity command.
+///
+/// Drives at `max_velocity` unless that would turn faster than
+/// `max_angular`, in which case the linear velocity is lowered so the
+/// robot still follows the arc.
+pub fn velocity_command(curvature: f64, max_velocity: f64, max_angular: f64) -> (f64, f64) {
+    let mut linear = max_velocity;
+    if (linear * curvature).abs() > max_angular {
+        linear = max_angular / curvature.abs();
+    }
+    (linear, linear * curvature)
+}
+
 #[cfg(test)]
 mod tests {
     use super::geo;
@@ -453,4 +485,28 @@ mod tests {
         let pt = pursuit_target(path, cir);
         assert_eq!(pt, Point { x: 0.0, y: 1.0 })
     }
+
+    #[test]
+    fn curvature_to_target_test() {
+        // Straight ahead
+        let k = curvature_to_target((0.0, 0.0, 0.0), Point::new(0.0, 10.0));
+        assert_eq!(k, 0.0);
+        // A point 10 inches to the right on a circle of radius 10
+        let k = curvature_to_target((0.0, 0.0, 0.0), Point::new(10.0, 10.0));
+        assert!((k - 0.1).abs() < 1e-12);
+        // Facing +x, the same point is to the left
+        let k = curvature_to_target((0.0, 0.0, 90.0), Point::new(10.0, 10.0));
+        assert!((k + 0.1).abs() < 1e-12);
+        assert_eq!(curvature_to_target((1.0, 1.0, 0.0), Point::new(1.0, 1.0)), 0.0);
+    }
+
+    #[test]
+    fn velocity_command_test() {
+        assert_eq!(velocity_command(0.0, 40.0, 3.0), (40.0, 0.0));
+        assert_eq!(velocity_command(0.05, 40.0, 3.0), (40.0, 2.0));
+        // Turning at 40 in/s would exceed 3 rad/s, so it slows down
+        let (linear, angular) = velocity_command(-0.1, 40.0, 3.0);
+        assert!((linear - 30.0).abs() < 1e-12);
+        assert!((angular + 3.0).abs() < 1e-12);
+    }
 }
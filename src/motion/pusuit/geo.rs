@@ -9,6 +9,8 @@
 //! - `Line`: A line segment between two points.
 //! - `Path`: A sequence of waypoints forming a path.
 //! - `Circle`: A circle defined by center and radius.
+//! - `Waypoint`: A point with an optional speed, heading and lookahead, as
+//!   stored in path files.
 
 use std::vec;
 
@@ -39,6 +41,27 @@ pub struct Path {
     pub waypoints: Vec<Point>,
 }
 
+/// A waypoint with optional settings for the robot at that point.
+///
+/// Path files store waypoints, see [`crate::fs::path`]. The settings are
+/// left out of the [`Path`] made from them, but
+/// [`AdaptivePath::from_waypoints`](super::adaptive::AdaptivePath::from_waypoints)
+/// follows the speed and lookahead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Waypoint {
+    /// The position of the waypoint.
+    pub point:     Point,
+    /// The fastest the robot drives from this waypoint to the next, in
+    /// inches per second.
+    pub speed:     Option<f64>,
+    /// The heading at the waypoint in degrees, clockwise from the positive
+    /// y-axis.
+    pub heading:   Option<f64>,
+    /// The lookahead distance in inches to use from this waypoint to the
+    /// next, instead of the one [`Pursuit`](super::Pursuit) picks.
+    pub lookahead: Option<f64>,
+}
+
 /// A line segment between two points.
 ///
 /// Used internally for path calculations. Not an infinite line—
@@ -113,6 +136,18 @@ impl Path {
     }
 }
 
+impl Waypoint {
+    /// Create a waypoint at `point` with no speed, heading or lookahead
+    pub fn new(point: Point) -> Self {
+        Self {
+            point,
+            speed: None,
+            heading: None,
+            lookahead: None,
+        }
+    }
+}
+
 impl Line {
     /// Create a new line from 2 coordinates
     pub fn new(x1: f64, y1: f64, x2: f64, y2: f64) -> Line {
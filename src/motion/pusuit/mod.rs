@@ -15,14 +15,32 @@
 //!    intersections of the circle with path segments, and the closest
 //!    point on the path to the robot.
 //! 3. Select the candidate furthest along the path as the target.
-//! 4. Drive toward that target using arc movements.
+//! 4. Calculate the curvature of the arc from the robot to that target and
+//!    command the matching linear and angular velocity through a
+//!    [`VelocityDrive`](crate::motion::velocity::VelocityDrive).
+//!
+//! # Lookahead
+//!
+//! By default the circle in step 1 has a fixed radius. Set
+//! [`Pursuit::adaptive_lookahead`] to grow it with speed and shrink it
+//! before curves, and give [`Waypoint`](geo::Waypoint)s a
+//! [`lookahead`](geo::Waypoint::lookahead) to override it on parts of the
+//! path.
+//!
+//! # Reversing
+//!
+//! With [`MotionOptions::backwards`] the robot backs along the path. The
+//! rear is treated as the front when steering, and the linear velocity is
+//! negated. Use [`Segment`]s with
+//! [`follow_segments`](Pursuit::follow_segments) to change direction
+//! partway through a route.
 //!
 //! # Example
 //!
 //! ```ignore
 //! use antaeus::motion::pusuit::{Pursuit, geo::{Path, Point}};
 //!
-//! let pursuit = Pursuit { lookahead: 12.0 };
+//! let pursuit = Pursuit::new(12.0);
 //!
 //! let path = Path::from_vec(vec![
 //!     Point::new(0.0, 0.0),
@@ -30,9 +48,16 @@
 //!     Point::new(24.0, 24.0),
 //! ]);
 //!
-//! pursuit.follow(odom, path).await;
+//! velocity.init();
+//! pursuit.follow(&odom, &velocity, path, odom.options()).await;
 //! ```
 
+/// Path generation for adaptive pure pursuit.
+///
+/// Injects and smooths points, then assigns each one a curvature and a
+/// target velocity. See [`AdaptivePath`](adaptive::AdaptivePath).
+pub mod adaptive;
+
 /// Internal algorithm calculations for path following.
 mod algorithm;
 
@@ -42,38 +67,173 @@ mod algorithm;
 /// used by the pursuit algorithm.
 pub mod geo;
 
-use crate::motion::odom::OdomMovement;
+/// Spline curves sampled into paths.
+///
+/// Provides Bézier, quintic Hermite and Catmull-Rom curves that are defined
+/// by a few control points and sampled at an even spacing.
+pub mod spline;
+
+use std::time::{Duration, Instant};
+
+use log::info;
+use vexide::time::sleep;
+
+use self::adaptive::{AdaptiveLookahead, AdaptivePath, RateLimiter};
+use crate::motion::{odom::OdomMovement, options::MotionOptions, velocity::VelocityDrive};
 
 /// Candidate-Based Pursuit path follower.
 ///
 /// Follows a path using the lookahead distance to determine targets.
 /// Larger lookahead values result in smoother but less accurate paths.
 /// Smaller values track the path more precisely but may cause oscillation.
+#[derive(Clone, Copy)]
 pub struct Pursuit {
     /// The lookahead distance in inches.
     ///
     /// This is the radius of the circle used to find target points.
     /// Recommended starting value: 12-18 inches.
-    pub lookahead: f64,
+    pub lookahead:          f64,
+    /// The fastest the robot drives along the path in inches per second.
+    pub max_velocity:       f64,
+    /// The fastest the robot turns in radians per second. Tight curves are
+    /// driven slower to stay under it.
+    pub max_angular:        f64,
+    /// How close to the last waypoint in inches the robot must get for the
+    /// path to be done.
+    pub tolerance:          f64,
+    /// Varies the lookahead with speed and curvature while following. When
+    /// `None`, [`lookahead`](Pursuit::lookahead) is used as it is, unless a
+    /// waypoint overrides it, see [`AdaptiveLookahead::fixed`].
+    pub adaptive_lookahead: Option<AdaptiveLookahead>,
 }
 
 impl Pursuit {
     /// Creates a new `Pursuit` instance with the specified lookahead distance.
     ///
+    /// The speed limits start at 48 in/s and 4 rad/s and the end tolerance
+    /// at 1 inch.
+    ///
     /// # Arguments
     ///
     /// * `lookahead` - The lookahead distance in inches, used as the radius for target point calculations.
-    pub fn new(lookahead: f64) -> Self { Self { lookahead } }
+    pub fn new(lookahead: f64) -> Self {
+        Self {
+            lookahead,
+            max_velocity: 48.0,
+            max_angular: 4.0,
+            tolerance: 1.0,
+            adaptive_lookahead: None,
+        }
+    }
+
+    /// Calculates the velocity command for a robot at `pose` driving at
+    /// `speed` inches per second along `path`.
+    ///
+    /// `pose` is `(x, y, heading)` as tracked by odometry. `closest` is the
+    /// index of the path point closest to the robot, tracked from tick to
+    /// tick with [`AdaptivePath::closest_index`] so that a path which returns
+    /// to its start isn't mistaken for finished. The velocity is the path's
+    /// at that point, and the lookahead comes from
+    /// [`lookahead_at`](Pursuit::lookahead_at) like it does while following.
+    /// Use [`AdaptivePath::unprofiled`] to drive a plain [`Path`](geo::Path)
+    /// at [`max_velocity`](Pursuit::max_velocity).
+    ///
+    /// When `forwards` is `false` the robot backs along the path. Returns
+    /// the linear velocity in inches per second and the angular velocity in
+    /// radians per second (positive clockwise), or `None` once the robot has
+    /// reached the last segment and is within
+    /// [`tolerance`](Pursuit::tolerance) of the end of the path.
+    pub fn command(
+        &self,
+        pose: (f64, f64, f64),
+        path: &AdaptivePath,
+        closest: usize,
+        speed: f64,
+        forwards: bool,
+    ) -> Option<(f64, f64)> {
+        let closest = closest.min(path.path.waypoints.len().saturating_sub(1));
+        if self.finished(pose, &path.path, closest) {
+            return None;
+        }
+        let lookahead = self.lookahead_at(path, closest, speed);
+        Some(self.steer(pose, &path.path, lookahead, path.velocities[closest], forwards))
+    }
+
+    /// Returns `true` once the point `closest` to the robot is on the last
+    /// segment of `path` and `pose` is within tolerance of its end.
+    ///
+    /// Being near the end point alone isn't enough, as a path may end where
+    /// it starts.
+    fn finished(&self, pose: (f64, f64, f64), path: &geo::Path, closest: usize) -> bool {
+        let (x, y, _) = pose;
+        let last_segment = path.waypoints.len().saturating_sub(2);
+        path.waypoints.last().is_none_or(|end| {
+            closest >= last_segment && (end.x - x).hypot(end.y - y) < self.tolerance
+        })
+    }
+
+    /// Returns the lookahead distance at point `closest` of `path` while
+    /// the robot drives at `speed` inches per second, as measured by
+    /// [`VelocityDrive::measured_chassis_velocity`].
+    pub fn lookahead_at(&self, path: &AdaptivePath, closest: usize, speed: f64) -> f64 {
+        let waypoint = path.lookaheads.get(closest).copied().flatten();
+        let adaptive = self
+            .adaptive_lookahead
+            .unwrap_or_else(|| AdaptiveLookahead::fixed(self.lookahead));
+        adaptive.distance(
+            self.lookahead,
+            speed,
+            path.upcoming_curvature(closest, adaptive.preview),
+            waypoint,
+        )
+    }
+
+    /// Steers towards the target on a circle of radius `lookahead` at up to
+    /// `velocity` inches per second, backing up when `forwards` is `false`.
+    fn steer(
+        &self,
+        pose: (f64, f64, f64),
+        path: &geo::Path,
+        lookahead: f64,
+        velocity: f64,
+        forwards: bool,
+    ) -> (f64, f64) {
+        let (x, y, heading) = pose;
+        // Backing up, the rear leads, so steer as if it were the front
+        let facing = if forwards { heading } else { heading + 180.0 };
+        let target = algorithm::pursuit_target(path.clone(), geo::Circle::new(x, y, lookahead));
+        let curvature = algorithm::curvature_to_target((x, y, facing), target);
+        let (linear, angular) = algorithm::velocity_command(
+            curvature,
+            velocity.min(self.max_velocity),
+            self.max_angular,
+        );
+        // The chassis rotates the same way whichever end leads, so only the
+        // linear velocity flips
+        if forwards {
+            (linear, angular)
+        } else {
+            (-linear, angular)
+        }
+    }
 
     /// Follows a path using the Candidate-Based Pursuit algorithm.
     ///
-    /// This method continuously calculates target points and commands
-    /// arc movements until the robot reaches the end of the path.
+    /// This method continuously calculates target points and commands the
+    /// velocity drive at [`max_velocity`](Pursuit::max_velocity) until the
+    /// robot reaches the end of the path or the motion times out, then
+    /// stops the drive. To slow down for turns and at the end, generate an
+    /// [`AdaptivePath`] and use [`follow_adaptive`](Pursuit::follow_adaptive).
     ///
     /// # Arguments
     ///
-    /// * `odom` - The odometry movement controller (must have arc_pid configured).
+    /// * `odom` - The odometry controller tracking the robot's pose.
+    /// * `drive` - The velocity controller driving the robot. It must be
+    ///   initialized.
     /// * `path` - The path to follow, defined as a series of waypoints.
+    /// * `options` - The [`timeout`](MotionOptions::timeout),
+    ///   [`after_delay`](MotionOptions::after_delay) and
+    ///   [`forwards`](MotionOptions::forwards) direction of the motion.
     ///
     /// # Example
     ///
@@ -84,25 +244,241 @@ impl Pursuit {
     ///     Point::new(48.0, 0.0),
     /// ]);
     ///
-    /// pursuit.follow(odom, path).await;
+    /// pursuit.follow(&odom, &velocity, path, odom.options()).await;
     /// ```
-    pub async fn follow(&self, odom: OdomMovement, path: geo::Path) {
-        let mut run = true;
-        while run {
-            let odometry_values = odom.odometry_values.lock().await;
-            let (x, y) = (odometry_values.global_x, odometry_values.global_y);
-            let cir = geo::Circle {
-                x: x,
-                y: y,
-                r: self.lookahead,
-            };
-            let target = algorithm::pursuit_target(path.clone(), cir);
-            odom.arc_point(target.x, target.y).await;
+    pub async fn follow(
+        &self,
+        odom: &OdomMovement,
+        drive: &VelocityDrive,
+        path: geo::Path,
+        options: MotionOptions,
+    ) {
+        let path = AdaptivePath::unprofiled(path, self.max_velocity);
+        self.follow_adaptive(odom, drive, &path, options).await;
+    }
+
+    /// Follows a generated [`AdaptivePath`].
+    ///
+    /// Every tick, the target velocity is read at the path point closest to
+    /// the robot and passed through a [`RateLimiter`] with the path's
+    /// maximum acceleration, so the robot speeds up gradually, slows for
+    /// turns and comes to rest at the end of the path.
+    ///
+    /// Takes the same arguments as [`follow`](Pursuit::follow).
+    pub async fn follow_adaptive(
+        &self,
+        odom: &OdomMovement,
+        drive: &VelocityDrive,
+        path: &AdaptivePath,
+        options: MotionOptions,
+    ) {
+        self.track(odom, drive, path, options.forwards, Instant::now(), options.timeout)
+            .await;
+        drive.stop().await;
+        sleep(Duration::from_millis(options.after_delay)).await;
+    }
+
+    /// Follows each segment in turn, driving forwards or backwards as the
+    /// segment says.
+    ///
+    /// The next segment starts once the robot is within tolerance of the
+    /// end of the current one. The drive only stops after the last segment.
+    /// [`timeout`](MotionOptions::timeout) covers all the segments and
+    /// [`forwards`](MotionOptions::forwards) is ignored.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // Drive to a game piece, then back into the goal
+    /// let segments = [
+    ///     Segment::forward(AdaptivePath::generate(&to_piece, &config)),
+    ///     Segment::reverse(AdaptivePath::generate(&to_goal, &config)),
+    /// ];
+    /// pursuit.follow_segments(&odom, &velocity, &segments, odom.options()).await;
+    /// ```
+    pub async fn follow_segments(
+        &self,
+        odom: &OdomMovement,
+        drive: &VelocityDrive,
+        segments: &[Segment],
+        options: MotionOptions,
+    ) {
+        let start = Instant::now();
+        for segment in segments {
+            if !self
+                .track(odom, drive, &segment.path, segment.forwards, start, options.timeout)
+                .await
+            {
+                break;
+            }
+        }
+        drive.stop().await;
+        sleep(Duration::from_millis(options.after_delay)).await;
+    }
 
-            if let Some(arcpid) = odom.arc_pid.clone() {
-                let values = arcpid.arcpid_values.lock().await;
-                run = values.active;
+    /// Drives along `path` until the robot reaches its end or `timeout`
+    /// milliseconds after `start` have passed. Returns `false` on timeout.
+    ///
+    /// Every tick, the target velocity is read at the path point closest to
+    /// the robot and passed through a [`RateLimiter`]. The lookahead is
+    /// picked at the same point for the limited velocity.
+    async fn track(
+        &self,
+        odom: &OdomMovement,
+        drive: &VelocityDrive,
+        path: &AdaptivePath,
+        forwards: bool,
+        start: Instant,
+        timeout: u64,
+    ) -> bool {
+        let mut last_tick = Instant::now();
+        let mut closest = 0;
+        let mut limiter = RateLimiter::new(path.max_acceleration);
+        loop {
+            let pose = {
+                let odometry_values = odom.odometry_values.lock().await;
+                (
+                    odometry_values.global_x,
+                    odometry_values.global_y,
+                    odometry_values.global_heading,
+                )
+            };
+            closest = path.closest_index(closest, geo::Point::new(pose.0, pose.1));
+            if self.finished(pose, &path.path, closest) {
+                return true;
+            }
+            if start.elapsed().as_millis() as u64 >= timeout {
+                info!("Path following timed out after {} ms", timeout);
+                return false;
             }
+
+            let now = Instant::now();
+            let dt = now.duration_since(last_tick).as_secs_f64();
+            last_tick = now;
+
+            let velocity = limiter.update(path.velocities[closest], dt);
+            // Size the lookahead by how fast the robot really moves, which
+            // lags the commanded velocity
+            let (measured, _) = drive.measured_chassis_velocity().await;
+            let lookahead = self.lookahead_at(path, closest, measured);
+            let (linear, angular) = self.steer(pose, &path.path, lookahead, velocity, forwards);
+            drive.set_chassis_velocity(linear, angular).await;
+            sleep(Duration::from_millis(drive.lifecycle.period())).await;
+        }
+    }
+}
+
+/// A part of a route driven in one direction.
+#[derive(Clone)]
+pub struct Segment {
+    /// The path to follow.
+    pub path:     AdaptivePath,
+    /// Whether the robot drives front first. When `false` it backs along
+    /// the path.
+    pub forwards: bool,
+}
+
+impl Segment {
+    /// A segment driven front first.
+    pub fn forward(path: AdaptivePath) -> Self {
+        Self {
+            path,
+            forwards: true,
+        }
+    }
+
+    /// A segment the robot backs along.
+    pub fn reverse(path: AdaptivePath) -> Self {
+        Self {
+            path,
+            forwards: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        geo::{Path, Point, Waypoint},
+        *,
+    };
+
+    #[test]
+    fn reverse_command_test() {
+        let pursuit = Pursuit::new(12.0);
+        let plain = |path: Path| AdaptivePath::unprofiled(path, pursuit.max_velocity);
+        let behind = plain(Path::from_vec(vec![Point::new(0.0, 0.0), Point::new(0.0, -24.0)]));
+        // Straight back: full speed in reverse without turning
+        let (linear, angular) = pursuit
+            .command((0.0, 0.0, 0.0), &behind, 0, 0.0, false)
+            .unwrap();
+        assert_eq!(linear, -48.0);
+        assert!(angular.abs() < 1e-9);
+
+        // Backing towards the right rear swings the front to the left
+        let right_rear = plain(Path::from_vec(vec![Point::new(0.0, 0.0), Point::new(12.0, -12.0)]));
+        let (linear, angular) = pursuit
+            .command((0.0, 0.0, 0.0), &right_rear, 0, 0.0, false)
+            .unwrap();
+        assert!(linear < 0.0);
+        assert!(angular < 0.0);
+
+        // Forwards, the same path makes the robot turn around to the right
+        let (linear, angular) = pursuit
+            .command((0.0, 0.0, 0.0), &right_rear, 0, 0.0, true)
+            .unwrap();
+        assert!(linear > 0.0);
+        assert!(angular > 0.0);
+        assert!(
+            pursuit
+                .command((12.0, -12.0, 0.0), &right_rear, 1, 0.0, false)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn loop_path_test() {
+        let pursuit = Pursuit::new(10.0);
+        // The compbot route, which ends where it starts
+        let path = AdaptivePath::unprofiled(
+            Path::from_vec(vec![
+                Point::new(0.0, 0.0),
+                Point::new(20.0, 20.0),
+                Point::new(-20.0, 20.0),
+                Point::new(0.0, 0.0),
+            ]),
+            pursuit.max_velocity,
+        );
+        let start = (0.0, 0.0, 0.0);
+        let closest = path.closest_index(0, Point::new(0.0, 0.0));
+        assert_eq!(closest, 0);
+        let (linear, _) = pursuit.command(start, &path, closest, 0.0, true).unwrap();
+        assert!(linear > 0.0);
+        // Back at the start after driving the loop, it is finished
+        assert!(pursuit.command(start, &path, 2, 0.0, true).is_none());
+        assert!(pursuit.command(start, &path, 3, 0.0, true).is_none());
+    }
+
+    #[test]
+    fn lookahead_at_test() {
+        let mut waypoints: Vec<Waypoint> = vec![
+            Waypoint::new(Point::new(0.0, 0.0)),
+            Waypoint::new(Point::new(0.0, 48.0)),
+            Waypoint::new(Point::new(48.0, 48.0)),
+        ];
+        waypoints[1].lookahead = Some(8.0);
+        let path = AdaptivePath::from_waypoints(&waypoints, &Default::default());
+
+        let mut pursuit = Pursuit::new(12.0);
+        // Fixed unless a waypoint overrides it
+        assert_eq!(pursuit.lookahead_at(&path, 0, 40.0), 12.0);
+        assert_eq!(pursuit.lookahead_at(&path, 8, 40.0), 8.0);
+
+        pursuit.adaptive_lookahead = Some(AdaptiveLookahead::default());
+        // Longer on the straight at speed, shorter approaching the corner
+        let straight = pursuit.lookahead_at(&path, 0, 30.0);
+        assert!(straight > 17.0 && straight <= 18.0);
+        assert!(pursuit.lookahead_at(&path, 6, 30.0) < straight / 2.0);
+        assert_eq!(pursuit.lookahead_at(&path, 8, 30.0), 8.0);
+    }
+}
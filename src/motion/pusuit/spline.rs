@@ -0,0 +1,521 @@
+//! Spline curves for defining paths with a few control points.
+//!
+//! Writing out a smooth path point by point is tedious. The curves in this
+//! module describe one with a handful of control points or headings and
+//! are then sampled into a [`Path`] for the pursuit follower.
+//!
+//! # Curves
+//!
+//! - [`CubicBezier`]: Starts at the first control point heading towards
+//!   the second, and ends at the fourth coming from the third.
+//! - [`QuinticHermite`]: Goes from one pose to another, with a chosen
+//!   heading at each end. Curvature is continuous, so chained segments
+//!   have no sudden changes in turning.
+//! - [`CatmullRom`]: Passes through every control point, with the tangent
+//!   at each one pointing from the previous point to the next.
+//!
+//! Every curve implements [`Curve`], which gives its heading and curvature
+//! at any parameter `t` between 0 and 1 and samples it at an even spacing
+//! along its length.
+//!
+//! # Conventions
+//!
+//! Headings are in degrees clockwise from the positive y-axis and
+//! curvature is positive turning right, the same as odometry.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use antaeus::motion::pusuit::{geo::Point, spline::{Curve, QuinticHermite}};
+//!
+//! // From the origin facing forward to (24, 48) facing right
+//! let curve = QuinticHermite::from_headings(
+//!     Point::new(0.0, 0.0),
+//!     0.0,
+//!     Point::new(24.0, 48.0),
+//!     90.0,
+//! );
+//! let path = curve.sample(2.0);
+//! ```
+
+use super::geo::{Path, Point};
+
+/// Number of steps used to measure a curve's length.
+const LENGTH_STEPS: usize = 1000;
+
+/// A parametric curve from `t = 0` to `t = 1`.
+pub trait Curve {
+    /// Returns the point at `t`.
+    fn position(&self, t: f64) -> Point;
+
+    /// Returns the first derivative with respect to `t`, as a vector.
+    fn derivative(&self, t: f64) -> Point;
+
+    /// Returns the second derivative with respect to `t`, as a vector.
+    fn second_derivative(&self, t: f64) -> Point;
+
+    /// Returns the direction of travel at `t` in degrees, clockwise from
+    /// the positive y-axis.
+    fn heading(&self, t: f64) -> f64 {
+        let d = self.derivative(t);
+        d.x.atan2(d.y).to_degrees()
+    }
+
+    /// Returns the curvature at `t` in radians per inch, positive turning
+    /// right.
+    fn curvature(&self, t: f64) -> f64 {
+        let d = self.derivative(t);
+        let dd = self.second_derivative(t);
+        let speed = d.x.hypot(d.y);
+        if speed < f64::EPSILON {
+            return 0.0;
+        }
+        -(d.x * dd.y - d.y * dd.x) / speed.powi(3)
+    }
+
+    /// Returns the length of the curve in inches.
+    fn length(&self) -> f64 { *arc_lengths(self).last().unwrap_or(&0.0) }
+
+    /// Samples the curve into a path with points `spacing` inches apart
+    /// along the curve.
+    ///
+    /// The path starts and ends on the ends of the curve, so the last two
+    /// points may be closer together.
+    fn sample(&self, spacing: f64) -> Path {
+        let lengths = arc_lengths(self);
+        let total = *lengths.last().unwrap_or(&0.0);
+        let mut waypoints = vec![self.position(0.0)];
+        if spacing > 0.0 {
+            let mut step = 0;
+            let mut distance = spacing;
+            while distance < total {
+                while lengths[step + 1] < distance {
+                    step += 1;
+                }
+                // Interpolate `t` within the step that covers `distance`
+                let span = lengths[step + 1] - lengths[step];
+                let fraction = if span > 0.0 {
+                    (distance - lengths[step]) / span
+                } else {
+                    0.0
+                };
+                let t = (step as f64 + fraction) / LENGTH_STEPS as f64;
+                waypoints.push(self.position(t));
+                distance += spacing;
+            }
+        }
+        let end = self.position(1.0);
+        if waypoints.last() != Some(&end) {
+            waypoints.push(end);
+        }
+        Path::from_vec(waypoints)
+    }
+}
+
+/// Cumulative length at each of [`LENGTH_STEPS`] evenly spaced parameters.
+fn arc_lengths<C: Curve + ?Sized>(curve: &C) -> Vec<f64> {
+    let mut lengths = Vec::with_capacity(LENGTH_STEPS + 1);
+    lengths.push(0.0);
+    let mut prev = curve.position(0.0);
+    let mut total = 0.0;
+    for i in 1..=LENGTH_STEPS {
+        let p = curve.position(i as f64 / LENGTH_STEPS as f64);
+        total += (p.x - prev.x).hypot(p.y - prev.y);
+        lengths.push(total);
+        prev = p;
+    }
+    lengths
+}
+
+/// Sum of `points` scaled by `weights`.
+fn weighted<const N: usize>(weights: [f64; N], points: [Point; N]) -> Point {
+    weights
+        .iter()
+        .zip(points.iter())
+        .fold(Point::new(0.0, 0.0), |sum, (w, p)| {
+            Point::new(sum.x + w * p.x, sum.y + w * p.y)
+        })
+}
+
+/// Unit vector pointing along `heading` degrees, scaled by `magnitude`.
+fn heading_vector(heading: f64, magnitude: f64) -> Point {
+    let heading = heading.to_radians();
+    Point::new(heading.sin() * magnitude, heading.cos() * magnitude)
+}
+
+/// A cubic Bézier curve.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CubicBezier {
+    /// The start of the curve.
+    pub p0: Point,
+    /// The control point the curve leaves `p0` towards.
+    pub p1: Point,
+    /// The control point the curve arrives at `p3` from.
+    pub p2: Point,
+    /// The end of the curve.
+    pub p3: Point,
+}
+
+impl CubicBezier {
+    /// Creates a curve from its four control points.
+    pub fn new(p0: Point, p1: Point, p2: Point, p3: Point) -> Self { Self { p0, p1, p2, p3 } }
+}
+
+impl Curve for CubicBezier {
+    fn position(&self, t: f64) -> Point {
+        let u = 1.0 - t;
+        weighted(
+            [u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t],
+            [self.p0, self.p1, self.p2, self.p3],
+        )
+    }
+
+    fn derivative(&self, t: f64) -> Point {
+        let u = 1.0 - t;
+        weighted(
+            [
+                -3.0 * u * u,
+                3.0 * u * u - 6.0 * u * t,
+                6.0 * u * t - 3.0 * t * t,
+                3.0 * t * t,
+            ],
+            [self.p0, self.p1, self.p2, self.p3],
+        )
+    }
+
+    fn second_derivative(&self, t: f64) -> Point {
+        let u = 1.0 - t;
+        weighted(
+            [6.0 * u, -12.0 * u + 6.0 * t, 6.0 * u - 12.0 * t, 6.0 * t],
+            [self.p0, self.p1, self.p2, self.p3],
+        )
+    }
+}
+
+/// A quintic Hermite curve between two poses.
+///
+/// Defined by the position, velocity and acceleration at each end, all
+/// with respect to `t`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuinticHermite {
+    /// Position at the start.
+    pub p0: Point,
+    /// Velocity at the start.
+    pub v0: Point,
+    /// Acceleration at the start.
+    pub a0: Point,
+    /// Position at the end.
+    pub p1: Point,
+    /// Velocity at the end.
+    pub v1: Point,
+    /// Acceleration at the end.
+    pub a1: Point,
+}
+
+impl QuinticHermite {
+    /// Creates a curve from the position, velocity and acceleration at each
+    /// end.
+    pub fn new(p0: Point, v0: Point, a0: Point, p1: Point, v1: Point, a1: Point) -> Self {
+        Self {
+            p0,
+            v0,
+            a0,
+            p1,
+            v1,
+            a1,
+        }
+    }
+
+    /// Creates a curve from `start` facing `start_heading` to `end` facing
+    /// `end_heading`, with headings in degrees.
+    ///
+    /// The tangents are as long as the distance between the ends and the
+    /// curve has no curvature at either end.
+    pub fn from_headings(start: Point, start_heading: f64, end: Point, end_heading: f64) -> Self {
+        let distance = (end.x - start.x).hypot(end.y - start.y);
+        let zero = Point::new(0.0, 0.0);
+        Self::new(
+            start,
+            heading_vector(start_heading, distance),
+            zero,
+            end,
+            heading_vector(end_heading, distance),
+            zero,
+        )
+    }
+
+    fn points(&self) -> [Point; 6] { [self.p0, self.v0, self.a0, self.a1, self.v1, self.p1] }
+}
+
+impl Curve for QuinticHermite {
+    fn position(&self, t: f64) -> Point {
+        let (t2, t3, t4, t5) = (t * t, t.powi(3), t.powi(4), t.powi(5));
+        weighted(
+            [
+                1.0 - 10.0 * t3 + 15.0 * t4 - 6.0 * t5,
+                t - 6.0 * t3 + 8.0 * t4 - 3.0 * t5,
+                0.5 * t2 - 1.5 * t3 + 1.5 * t4 - 0.5 * t5,
+                0.5 * t3 - t4 + 0.5 * t5,
+                -4.0 * t3 + 7.0 * t4 - 3.0 * t5,
+                10.0 * t3 - 15.0 * t4 + 6.0 * t5,
+            ],
+            self.points(),
+        )
+    }
+
+    fn derivative(&self, t: f64) -> Point {
+        let (t2, t3, t4) = (t * t, t.powi(3), t.powi(4));
+        weighted(
+            [
+                -30.0 * t2 + 60.0 * t3 - 30.0 * t4,
+                1.0 - 18.0 * t2 + 32.0 * t3 - 15.0 * t4,
+                t - 4.5 * t2 + 6.0 * t3 - 2.5 * t4,
+                1.5 * t2 - 4.0 * t3 + 2.5 * t4,
+                -12.0 * t2 + 28.0 * t3 - 15.0 * t4,
+                30.0 * t2 - 60.0 * t3 + 30.0 * t4,
+            ],
+            self.points(),
+        )
+    }
+
+    fn second_derivative(&self, t: f64) -> Point {
+        let (t2, t3) = (t * t, t.powi(3));
+        weighted(
+            [
+                -60.0 * t + 180.0 * t2 - 120.0 * t3,
+                -36.0 * t + 96.0 * t2 - 60.0 * t3,
+                1.0 - 9.0 * t + 18.0 * t2 - 10.0 * t3,
+                3.0 * t - 12.0 * t2 + 10.0 * t3,
+                -24.0 * t + 84.0 * t2 - 60.0 * t3,
+                60.0 * t - 180.0 * t2 + 120.0 * t3,
+            ],
+            self.points(),
+        )
+    }
+}
+
+/// A uniform Catmull-Rom spline through a list of points.
+///
+/// Each control point is reached at an even share of `t`: with four points,
+/// the second is at `t = 1/3`. The tangent at each point is half the vector
+/// from the previous point to the next. The ends are extended by mirroring
+/// their neighbours.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CatmullRom {
+    points: Vec<Point>,
+}
+
+impl CatmullRom {
+    /// Creates a spline through `points`.
+    ///
+    /// Returns `None` if there are fewer than two points.
+    pub fn new(points: Vec<Point>) -> Option<Self> {
+        if points.len() < 2 {
+            return None;
+        }
+        Some(Self { points })
+    }
+
+    /// Returns the points the spline passes through.
+    pub fn points(&self) -> &[Point] { &self.points }
+
+    /// Returns the four control points of the segment containing `t`, the
+    /// parameter within it, and the number of segments.
+    fn segment(&self, t: f64) -> ([Point; 4], f64, f64) {
+        let n = self.points.len();
+        let segments = n - 1;
+        let scaled = t.clamp(0.0, 1.0) * segments as f64;
+        let i = (scaled.floor() as usize).min(segments - 1);
+        let get = |j: isize| -> Point {
+            let last = n as isize - 1;
+            if j < 0 {
+                let (a, b) = (self.points[0], self.points[1]);
+                Point::new(2.0 * a.x - b.x, 2.0 * a.y - b.y)
+            } else if j > last {
+                let (a, b) = (self.points[n - 1], self.points[n - 2]);
+                Point::new(2.0 * a.x - b.x, 2.0 * a.y - b.y)
+            } else {
+                self.points[j as usize]
+            }
+        };
+        let i = i as isize;
+        (
+            [get(i - 1), get(i), get(i + 1), get(i + 2)],
+            scaled - i as f64,
+            segments as f64,
+        )
+    }
+}
+
+impl Curve for CatmullRom {
+    fn position(&self, t: f64) -> Point {
+        let (p, u, _) = self.segment(t);
+        let (u2, u3) = (u * u, u * u * u);
+        weighted(
+            [
+                0.5 * (-u + 2.0 * u2 - u3),
+                0.5 * (2.0 - 5.0 * u2 + 3.0 * u3),
+                0.5 * (u + 4.0 * u2 - 3.0 * u3),
+                0.5 * (-u2 + u3),
+            ],
+            p,
+        )
+    }
+
+    fn derivative(&self, t: f64) -> Point {
+        let (p, u, segments) = self.segment(t);
+        let u2 = u * u;
+        let d = weighted(
+            [
+                0.5 * (-1.0 + 4.0 * u - 3.0 * u2),
+                0.5 * (-10.0 * u + 9.0 * u2),
+                0.5 * (1.0 + 8.0 * u - 9.0 * u2),
+                0.5 * (-2.0 * u + 3.0 * u2),
+            ],
+            p,
+        );
+        Point::new(d.x * segments, d.y * segments)
+    }
+
+    fn second_derivative(&self, t: f64) -> Point {
+        let (p, u, segments) = self.segment(t);
+        let dd = weighted(
+            [
+                0.5 * (4.0 - 6.0 * u),
+                0.5 * (-10.0 + 18.0 * u),
+                0.5 * (8.0 - 18.0 * u),
+                0.5 * (-2.0 + 6.0 * u),
+            ],
+            p,
+        );
+        let scale = segments * segments;
+        Point::new(dd.x * scale, dd.y * scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Point, b: Point, tolerance: f64) -> bool {
+        (a.x - b.x).abs() < tolerance && (a.y - b.y).abs() < tolerance
+    }
+
+    /// Checks that consecutive samples are `spacing` apart along a curve
+    /// without sharp turns, and that the ends match.
+    fn check_sampling<C: Curve>(curve: &C, spacing: f64) {
+        let path = curve.sample(spacing);
+        let points = &path.waypoints;
+        assert!(close(points[0], curve.position(0.0), 1e-9));
+        assert!(close(*points.last().unwrap(), curve.position(1.0), 1e-9));
+        for pair in points[..points.len() - 1].windows(2) {
+            let d = (pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y);
+            assert!((d - spacing).abs() < spacing * 0.01, "spacing {}", d);
+        }
+        let expected = (curve.length() / spacing).ceil() as usize + 1;
+        assert_eq!(points.len(), expected);
+    }
+
+    #[test]
+    fn bezier_test() {
+        // A straight line with evenly spaced control points
+        let line = CubicBezier::new(
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 10.0),
+            Point::new(0.0, 20.0),
+            Point::new(0.0, 30.0),
+        );
+        assert!((line.length() - 30.0).abs() < 1e-9);
+        assert_eq!(line.heading(0.5), 0.0);
+        assert_eq!(line.curvature(0.5), 0.0);
+        assert!(close(line.position(0.5), Point::new(0.0, 15.0), 1e-9));
+        check_sampling(&line, 4.0);
+
+        // A quarter circle of radius 10, from facing +y to facing +x
+        let k = 0.5522847498 * 10.0;
+        let arc = CubicBezier::new(
+            Point::new(0.0, 0.0),
+            Point::new(0.0, k),
+            Point::new(10.0 - k, 10.0),
+            Point::new(10.0, 10.0),
+        );
+        assert!(arc.heading(0.0).abs() < 1e-9);
+        assert!((arc.heading(1.0) - 90.0).abs() < 1e-9);
+        for t in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            assert!((arc.curvature(t) - 0.1).abs() < 0.01);
+        }
+        assert!((arc.length() - 5.0 * std::f64::consts::PI).abs() < 0.01);
+        check_sampling(&arc, 1.0);
+    }
+
+    #[test]
+    fn hermite_test() {
+        let curve =
+            QuinticHermite::from_headings(Point::new(0.0, 0.0), 0.0, Point::new(24.0, 48.0), 90.0);
+        assert!(close(curve.position(0.0), Point::new(0.0, 0.0), 1e-9));
+        assert!(close(curve.position(1.0), Point::new(24.0, 48.0), 1e-9));
+        assert!(curve.heading(0.0).abs() < 1e-9);
+        assert!((curve.heading(1.0) - 90.0).abs() < 1e-9);
+        // No acceleration at the ends means no curvature there
+        assert!(curve.curvature(0.0).abs() < 1e-9);
+        assert!(curve.curvature(1.0).abs() < 1e-9);
+        // Turning from +y to +x is a right turn
+        assert!(curve.curvature(0.5) > 0.0);
+        check_sampling(&curve, 2.0);
+
+        // The derivatives match finite differences
+        let h = 1e-6;
+        for t in [0.2, 0.5, 0.8] {
+            let (a, b) = (curve.position(t - h), curve.position(t + h));
+            let d = curve.derivative(t);
+            assert!(close(
+                d,
+                Point::new((b.x - a.x) / (2.0 * h), (b.y - a.y) / (2.0 * h)),
+                1e-4
+            ));
+            let (a, b) = (curve.derivative(t - h), curve.derivative(t + h));
+            let dd = curve.second_derivative(t);
+            assert!(close(
+                dd,
+                Point::new((b.x - a.x) / (2.0 * h), (b.y - a.y) / (2.0 * h)),
+                1e-3
+            ));
+        }
+    }
+
+    #[test]
+    fn catmull_rom_test() {
+        let points = vec![
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 24.0),
+            Point::new(24.0, 24.0),
+            Point::new(24.0, 48.0),
+        ];
+        let curve = CatmullRom::new(points.clone()).unwrap();
+        // Passes through every control point
+        for (i, p) in points.iter().enumerate() {
+            assert!(close(curve.position(i as f64 / 3.0), *p, 1e-9));
+        }
+        // The tangent at an inner point runs from its previous to its next
+        // point, and is the same from both segments
+        let h = 1e-9;
+        let (before, after) = (curve.derivative(1.0 / 3.0 - h), curve.derivative(1.0 / 3.0 + h));
+        assert!(close(before, after, 1e-4));
+        assert!((curve.heading(1.0 / 3.0) - 45.0).abs() < 1e-6);
+        // Turns right into the second point and left into the third
+        assert!(curve.curvature(0.3) > 0.0);
+        assert!(curve.curvature(0.7) < 0.0);
+        check_sampling(&curve, 1.0);
+    }
+
+    #[test]
+    fn catmull_rom_too_few_points_test() {
+        assert!(CatmullRom::new(Vec::new()).is_none());
+        assert!(CatmullRom::new(vec![Point::new(1.0, 2.0)]).is_none());
+        // Two points give a straight line between them
+        let curve = CatmullRom::new(vec![Point::new(0.0, 0.0), Point::new(0.0, 24.0)]).unwrap();
+        assert!(close(curve.position(0.5), Point::new(0.0, 12.0), 1e-9));
+        assert!((curve.length() - 24.0).abs() < 1e-6);
+        check_sampling(&curve, 1.0);
+    }
+}
@@ -0,0 +1,189 @@
+//! Fixed-rate timing for background control loops.
+//!
+//! Sleeping for the loop period after each tick stretches the period by
+//! however long the tick took, and other tasks or mutex waits can delay
+//! the wake-up further. A controller that assumes a constant `dt` then gets
+//! its I and D terms wrong.
+//!
+//! [`LoopTimer`] sleeps until the next deadline instead and measures the
+//! time that actually passed between ticks, which the loops use as `dt`.
+//! Every tick is recorded in [`LoopStats`], readable through the
+//! controller's [`LoopHandle`](super::lifecycle::LoopHandle), and a warning
+//! is logged when a tick takes much longer than its period.
+//!
+//! The period also paces everything that waits on the controller, such as
+//! a motion checking its exit conditions, path following, homing and
+//! autotuning, so [`set_period`](super::lifecycle::LoopHandle::set_period)
+//! changes the rate of the whole controller.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let handle = pid.init();
+//! handle.set_period(10); // run the loop at 100 Hz
+//!
+//! pid.travel(24.0, pid.options().timeout(2000)).await;
+//! let stats = handle.stats();
+//! info!("mean dt {:.2} ms, jitter {:.2} ms", stats.mean_dt * 1000.0, stats.jitter() * 1000.0);
+//! ```
+
+use std::time::{Duration, Instant};
+
+use log::warn;
+use vexide::time::{sleep, sleep_until};
+
+use super::lifecycle::LoopHandle;
+
+/// Default control loop period in milliseconds.
+pub const DEFAULT_LOOPRATE: u64 = 5;
+
+/// A tick overruns once it takes this many times the loop period.
+const OVERRUN_FACTOR: f64 = 1.5;
+
+/// Minimum time between overrun warnings, so a slow loop doesn't flood the log.
+const WARN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Timing statistics of a control loop.
+///
+/// All times are in seconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LoopStats {
+    /// Number of measured ticks.
+    pub ticks:    u64,
+    /// Number of ticks that overran their period.
+    pub overruns: u64,
+    /// Mean time between ticks.
+    pub mean_dt:  f64,
+    /// Shortest time between ticks.
+    pub min_dt:   f64,
+    /// Longest time between ticks.
+    pub max_dt:   f64,
+    sum_sq_dev:   f64,
+}
+
+impl LoopStats {
+    /// Records a tick `dt` seconds after the previous one, for a loop with a
+    /// period of `period` seconds. Returns `true` if the tick overran.
+    pub fn record(&mut self, dt: f64, period: f64) -> bool {
+        if self.ticks == 0 {
+            self.min_dt = dt;
+            self.max_dt = dt;
+        } else {
+            self.min_dt = self.min_dt.min(dt);
+            self.max_dt = self.max_dt.max(dt);
+        }
+        self.ticks += 1;
+        self.mean_dt += (dt - self.mean_dt) / self.ticks as f64;
+        self.sum_sq_dev += (dt - period).powi(2);
+
+        let overrun = dt > period * OVERRUN_FACTOR;
+        if overrun {
+            self.overruns += 1;
+        }
+        overrun
+    }
+
+    /// Root-mean-square deviation of the time between ticks from the
+    /// configured period.
+    pub fn jitter(&self) -> f64 {
+        if self.ticks == 0 {
+            0.0
+        } else {
+            (self.sum_sq_dev / self.ticks as f64).sqrt()
+        }
+    }
+}
+
+/// Paces a control loop and measures its `dt`.
+///
+/// Call [`tick`](LoopTimer::tick) at the top of every iteration and
+/// [`wait`](LoopTimer::wait) in place of sleeping.
+pub(crate) struct LoopTimer {
+    name:      &'static str,
+    handle:    LoopHandle,
+    start:     Instant,
+    last:      Option<Instant>,
+    last_warn: Option<Instant>,
+}
+
+impl LoopTimer {
+    pub(crate) fn new(name: &'static str, handle: &LoopHandle) -> Self {
+        Self {
+            name,
+            handle: handle.clone(),
+            start: Instant::now(),
+            last: None,
+            last_warn: None,
+        }
+    }
+
+    /// Starts a tick and returns the seconds since the previous one.
+    ///
+    /// The first tick returns the configured period.
+    pub(crate) fn tick(&mut self) -> f64 {
+        let now = Instant::now();
+        let period = self.handle.period() as f64 / 1000.0;
+        let dt = match self.last {
+            Some(last) => {
+                let dt = now.duration_since(last).as_secs_f64();
+                if self.handle.record_tick(dt) &&
+                    self.last_warn
+                        .is_none_or(|t| now.duration_since(t) >= WARN_INTERVAL)
+                {
+                    warn!(
+                        "{} overran its {} ms period: tick took {:.1} ms",
+                        self.name,
+                        self.handle.period(),
+                        dt * 1000.0
+                    );
+                    self.last_warn = Some(now);
+                }
+                dt
+            }
+            None => period,
+        };
+        self.last = Some(now);
+        self.start = now;
+        dt
+    }
+
+    /// Sleeps until the next tick is due. If the tick already used up its
+    /// period, sleeps for a millisecond so other tasks still get to run.
+    pub(crate) async fn wait(&self) {
+        let deadline = self.start + Duration::from_millis(self.handle.period());
+        if Instant::now() >= deadline {
+            sleep(Duration::from_millis(1)).await;
+        } else {
+            sleep_until(deadline).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loop_stats_test() {
+        let mut stats = LoopStats::default();
+        assert_eq!(stats.jitter(), 0.0);
+        assert!(!stats.record(0.005, 0.005));
+        assert!(!stats.record(0.006, 0.005));
+        assert!(!stats.record(0.004, 0.005));
+        assert_eq!(stats.ticks, 3);
+        assert!((stats.mean_dt - 0.005).abs() < 1e-12);
+        assert_eq!(stats.min_dt, 0.004);
+        assert_eq!(stats.max_dt, 0.006);
+        // Deviations of 0, 1 and 1 ms
+        assert!((stats.jitter() - (2.0f64 / 3.0).sqrt() * 0.001).abs() < 1e-12);
+    }
+
+    #[test]
+    fn loop_stats_overrun_test() {
+        let mut stats = LoopStats::default();
+        assert!(!stats.record(0.007, 0.005));
+        assert!(stats.record(0.012, 0.005));
+        assert_eq!(stats.overruns, 1);
+        assert_eq!(stats.max_dt, 0.012);
+    }
+}
@@ -0,0 +1,482 @@
+//! Closed-loop wheel velocity control.
+//!
+//! Voltage control makes the robot's speed depend on the battery and on the
+//! load: 6 V on a fresh battery drives faster than 6 V on a drained one.
+//! [`VelocityDrive`] instead takes a target velocity for each side of the
+//! drivetrain in inches per second and tracks it in a background loop.
+//!
+//! # How It Works
+//!
+//! Every tick, each side's voltage is the sum of:
+//!
+//! - A [`Feedforward`] term that predicts the voltage for the target
+//!   velocity and the acceleration between targets.
+//! - A PID term on the difference between the target and the measured
+//!   velocity, which corrects whatever the model gets wrong.
+//!
+//! The two sides are then desaturated together, see
+//! [`Desaturation`](crate::peripherals::drivetrain::Desaturation).
+//!
+//! The velocity is measured from the drive motors' integrated encoders, or
+//! from a pair of tracking wheels, see [`VelocitySource`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! use antaeus::motion::velocity::{VelocityDrive, VelocityValues};
+//!
+//! let ff = Feedforward::new(0.8, 0.15, 0.02); // from characterization
+//! let velocity = VelocityDrive::new(drivetrain, config, VelocityValues::new(ff, 0.05, 0.0, 0.0));
+//! velocity.init();
+//!
+//! // 30 in/s forward while turning right at 1 rad/s
+//! velocity.set_chassis_velocity(30.0, 1.0).await;
+//! sleep(Duration::from_millis(500)).await;
+//! velocity.stop().await;
+//! ```
+
+use std::sync::Arc;
+
+use log::info;
+use vexide::{prelude::Motor, sync::Mutex, task::spawn, time::user_uptime};
+
+use crate::{
+    motion::{
+        feedforward::Feedforward,
+        lifecycle::{LoopCommand, LoopHandle},
+        odom::WheelTracker,
+        pid::{
+            DrivetrainConfig,
+            telemetry::{Telemetry, TickRecord},
+        },
+        timing::LoopTimer,
+    },
+    peripherals::drivetrain::{Differential, DriveOwner},
+    to_mutex,
+};
+
+async fn velocity_loop(
+    values: &Arc<Mutex<VelocityValues>>,
+    drivetrain: Differential,
+    config: DrivetrainConfig,
+    source: VelocitySource,
+    lifecycle: LoopHandle,
+    telemetry: Telemetry,
+) {
+    info!("Velocity Control Loop Started");
+    let mut left = SideController::default();
+    let mut right = SideController::default();
+    let mut meter = VelocityMeter::default();
+    let mut was_active = false;
+    let mut paused = false;
+
+    let mut timer = LoopTimer::new("Velocity Control Loop", &lifecycle);
+
+    loop {
+        // Seconds since the previous tick
+        let dt = timer.tick();
+        match lifecycle.poll() {
+            LoopCommand::Shutdown => break,
+            LoopCommand::Paused => {
+                if !paused {
+                    drivetrain.set_voltages(DriveOwner::Velocity, 0.0, 0.0);
+                    paused = true;
+                }
+                timer.wait().await;
+                continue;
+            }
+            LoopCommand::Restart => {
+                info!("Velocity Control Loop Restarted");
+                {
+                    let mut s = values.lock().await;
+                    s.target_left = 0.0;
+                    s.target_right = 0.0;
+                    s.active = false;
+                }
+                left = SideController::default();
+                right = SideController::default();
+            }
+            LoopCommand::Run => {}
+        }
+        paused = false;
+
+        let (measured_left, measured_right) =
+            meter.measure(&drivetrain, &config, &source, dt).await;
+        let s = {
+            let mut s = values.lock().await;
+            s.measured_left = measured_left;
+            s.measured_right = measured_right;
+            *s
+        };
+
+        let now = user_uptime().as_millis() as u64;
+        if s.active && !was_active {
+            telemetry.begin_motion(now);
+            left = SideController::default();
+            right = SideController::default();
+        }
+        was_active = s.active;
+
+        if !s.active {
+            timer.wait().await;
+            continue;
+        }
+
+        let out_left = left.update(&s, s.target_left, measured_left, dt);
+        let out_right = right.update(&s, s.target_right, measured_right, dt);
+        let (u_left, u_right) =
+            drivetrain.desaturate(out_left.total(), out_right.total(), s.maxpwr);
+        drivetrain.set_voltages(DriveOwner::Velocity, u_left, u_right);
+
+        for (channel, target, measured, out, u) in [
+            ("left", s.target_left, measured_left, out_left, u_left),
+            ("right", s.target_right, measured_right, out_right, u_right),
+        ] {
+            telemetry.record(TickRecord {
+                time: now,
+                channel,
+                target,
+                measurement: measured,
+                error: target - measured,
+                p: out.p,
+                i: out.i,
+                d: out.d,
+                output: u,
+                tolerance: 0.0,
+            });
+        }
+        timer.wait().await;
+    }
+
+    drivetrain.release(DriveOwner::Velocity);
+    lifecycle.stopped();
+    info!("Velocity Control Loop Stopped");
+}
+
+/// Contributions to one side's output in volts.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct SideOutput {
+    ff: f64,
+    p:  f64,
+    i:  f64,
+    d:  f64,
+}
+
+impl SideOutput {
+    fn total(&self) -> f64 { self.ff + self.p + self.i + self.d }
+}
+
+/// Feedforward and PID state of one side of the drivetrain.
+#[derive(Default)]
+struct SideController {
+    prev_target: Option<f64>,
+    prev_error:  f64,
+    integral:    f64,
+}
+
+impl SideController {
+    /// Returns the output for tracking `target` in/s while moving at
+    /// `measured` in/s, `dt` seconds after the previous update.
+    fn update(
+        &mut self,
+        values: &VelocityValues,
+        target: f64,
+        measured: f64,
+        dt: f64,
+    ) -> SideOutput {
+        let acceleration = match self.prev_target {
+            Some(prev) if dt > 0.0 => (target - prev) / dt,
+            _ => 0.0,
+        };
+        self.prev_target = Some(target);
+
+        let error = target - measured;
+        self.integral += error * dt;
+        if values.ki != 0.0 {
+            let i_max = values.maxpwr.abs() / values.ki.abs();
+            self.integral = self.integral.clamp(-i_max, i_max);
+        }
+        let derivative = if dt > 0.0 {
+            (error - self.prev_error) / dt
+        } else {
+            0.0
+        };
+        self.prev_error = error;
+
+        SideOutput {
+            ff: values.feedforward.calculate(target, acceleration),
+            p:  values.kp * error,
+            i:  values.ki * self.integral,
+            d:  values.kd * derivative,
+        }
+    }
+}
+
+/// Measures the velocity of both sides of the drivetrain.
+#[derive(Default)]
+struct VelocityMeter {
+    /// Previous tracking wheel distances, for differentiating.
+    prev: Option<(f64, f64)>,
+}
+
+impl VelocityMeter {
+    async fn measure(
+        &mut self,
+        drivetrain: &Differential,
+        config: &DrivetrainConfig,
+        source: &VelocitySource,
+        dt: f64,
+    ) -> (f64, f64) {
+        match source {
+            VelocitySource::Motors => (
+                config.rpm_to_velocity(group_velocity(&mut *drivetrain.left.borrow_mut())),
+                config.rpm_to_velocity(group_velocity(&mut *drivetrain.right.borrow_mut())),
+            ),
+            VelocitySource::TrackingWheels { left, right } => {
+                let distances = (wheel_distance(left).await, wheel_distance(right).await);
+                let velocities = match self.prev {
+                    Some(prev) if dt > 0.0 => {
+                        ((distances.0 - prev.0) / dt, (distances.1 - prev.1) / dt)
+                    }
+                    _ => (0.0, 0.0),
+                };
+                self.prev = Some(distances);
+                velocities
+            }
+        }
+    }
+}
+
+/// Average velocity in RPM of a motor group.
+fn group_velocity(motors: &mut dyn AsMut<[Motor]>) -> f64 {
+    let (sum, count) = motors
+        .as_mut()
+        .iter()
+        .filter_map(|motor| motor.velocity().ok())
+        .fold((0.0, 0.0), |(sum, count), v| (sum + v, count + 1.0));
+    if count > 0.0 { sum / count } else { 0.0 }
+}
+
+/// Distance in inches rolled by a tracking wheel.
+async fn wheel_distance(wheel: &WheelTracker) -> f64 {
+    let distance = wheel.device.position().await.as_radians() * wheel.wheel_diameter / 2.0;
+    if wheel.reverse { -distance } else { distance }
+}
+
+/// Where [`VelocityDrive`] measures the velocity of each side.
+#[derive(Clone)]
+pub enum VelocitySource {
+    /// The integrated encoders of the drive motors.
+    Motors,
+    /// A tracking wheel on each side of the drivetrain. The wheels'
+    /// `offset` is not used.
+    TrackingWheels {
+        left:  WheelTracker,
+        right: WheelTracker,
+    },
+}
+
+/// **The Velocity Drive Controller**
+///
+/// Tracks left and right wheel velocities in inches per second. Initialize
+/// it with [`init`](VelocityDrive::init), then command velocities with
+/// [`set_velocities`](VelocityDrive::set_velocities) or
+/// [`set_chassis_velocity`](VelocityDrive::set_chassis_velocity).
+///
+/// Like the PID controllers, it only drives while it owns the drivetrain as
+/// [`DriveOwner::Velocity`]. Commanding a velocity takes ownership.
+#[derive(Clone)]
+pub struct VelocityDrive {
+    pub drivetrain:        Differential,
+    pub drivetrain_config: DrivetrainConfig,
+    pub velocity_values:   Arc<Mutex<VelocityValues>>,
+    /// Where the velocity of each side is measured.
+    pub source:            VelocitySource,
+    /// Handle to the background loop started by [`init`](VelocityDrive::init).
+    pub lifecycle:         LoopHandle,
+    /// Per-tick recording of the control loop, disabled by default. Records
+    /// the `left` and `right` channels in inches per second.
+    pub telemetry:         Telemetry,
+}
+
+impl VelocityDrive {
+    /// Creates a velocity controller measuring the drive motors.
+    pub fn new(
+        drivetrain: Differential,
+        drivetrain_config: DrivetrainConfig,
+        velocity_values: VelocityValues,
+    ) -> Self {
+        VelocityDrive {
+            drivetrain,
+            drivetrain_config,
+            velocity_values: to_mutex(velocity_values),
+            source: VelocitySource::Motors,
+            lifecycle: LoopHandle::default(),
+            telemetry: Telemetry::default(),
+        }
+    }
+
+    /// Starts the velocity control loop in a separate task.
+    ///
+    /// Returns a [`LoopHandle`] for pausing, restarting or shutting down the
+    /// loop. Calling `init` again while the loop is running returns the same
+    /// handle without spawning a second loop.
+    pub fn init(&self) -> LoopHandle {
+        if !self.lifecycle.start() {
+            info!("Velocity Control Loop Already Running");
+            return self.lifecycle.clone();
+        }
+        let values = self.velocity_values.clone();
+        let drivetrain = self.drivetrain.clone();
+        let config = self.drivetrain_config;
+        let source = self.source.clone();
+        let lifecycle = self.lifecycle.clone();
+        let telemetry = self.telemetry.clone();
+        spawn(async move {
+            velocity_loop(&values, drivetrain, config, source, lifecycle, telemetry).await;
+        })
+        .detach();
+        self.lifecycle.clone()
+    }
+
+    /// Sets the PID gains. The error is in inches per second.
+    pub async fn tune(&self, kp: f64, ki: f64, kd: f64) {
+        let mut s = self.velocity_values.lock().await;
+        s.kp = kp;
+        s.ki = ki;
+        s.kd = kd;
+    }
+
+    /// Sets the feedforward model, in volts per inch per second.
+    pub async fn set_feedforward(&self, feedforward: Feedforward) {
+        self.velocity_values.lock().await.feedforward = feedforward;
+    }
+
+    /// Sets the target velocity of each side in inches per second and takes
+    /// the drivetrain.
+    pub async fn set_velocities(&self, left: f64, right: f64) {
+        let mut s = self.velocity_values.lock().await;
+        self.drivetrain.acquire(DriveOwner::Velocity);
+        s.target_left = left;
+        s.target_right = right;
+        s.active = true;
+    }
+
+    /// Sets the target linear velocity in inches per second and angular
+    /// velocity in radians per second (positive clockwise).
+    pub async fn set_chassis_velocity(&self, linear: f64, angular: f64) {
+        let (left, right) = self.drivetrain_config.inverse(linear, angular);
+        self.set_velocities(left, right).await;
+    }
+
+    /// Returns the measured linear velocity in inches per second and angular
+    /// velocity in radians per second (positive clockwise).
+    ///
+    /// The sides are measured by the background loop, which updates them
+    /// even while it isn't tracking a target.
+    pub async fn measured_chassis_velocity(&self) -> (f64, f64) {
+        let s = self.velocity_values.lock().await;
+        self.drivetrain_config
+            .forward(s.measured_left, s.measured_right)
+    }
+
+    /// Stops tracking and gives up the drivetrain.
+    pub async fn stop(&self) {
+        let mut s = self.velocity_values.lock().await;
+        s.target_left = 0.0;
+        s.target_right = 0.0;
+        s.active = false;
+        self.drivetrain.release(DriveOwner::Velocity);
+    }
+}
+
+/// Runtime values for the velocity controller.
+///
+/// These values are updated by the commands of [`VelocityDrive`] and read
+/// by the background control loop.
+#[derive(Clone, Copy)]
+pub struct VelocityValues {
+    /// Feedforward model in volts per inch per second.
+    pub feedforward:    Feedforward,
+    /// Proportional gain on the velocity error.
+    pub kp:             f64,
+    /// Integral gain on the velocity error.
+    pub ki:             f64,
+    /// Derivative gain on the velocity error.
+    pub kd:             f64,
+    /// Maximum motor voltage (0-12 volts).
+    pub maxpwr:         f64,
+    /// Target velocity of the left side in inches per second.
+    pub target_left:    f64,
+    /// Target velocity of the right side in inches per second.
+    pub target_right:   f64,
+    /// Whether the loop is tracking the targets.
+    pub active:         bool,
+    /// Velocity of the left side in inches per second, as measured on the
+    /// loop's last tick.
+    pub measured_left:  f64,
+    /// Velocity of the right side in inches per second, as measured on the
+    /// loop's last tick.
+    pub measured_right: f64,
+}
+
+impl VelocityValues {
+    pub fn new(feedforward: Feedforward, kp: f64, ki: f64, kd: f64) -> VelocityValues {
+        VelocityValues {
+            feedforward,
+            kp,
+            ki,
+            kd,
+            maxpwr: 12.0,
+            target_left: 0.0,
+            target_right: 0.0,
+            active: false,
+            measured_left: 0.0,
+            measured_right: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> VelocityValues {
+        VelocityValues::new(Feedforward::new(1.0, 0.1, 0.01), 0.5, 0.0, 0.0)
+    }
+
+    #[test]
+    fn side_controller_feedforward_test() {
+        let values = values();
+        let mut side = SideController::default();
+        // The first update has no acceleration and no error
+        let out = side.update(&values, 40.0, 40.0, 0.01);
+        assert_eq!(
+            out,
+            SideOutput {
+                ff: 5.0,
+                p:  0.0,
+                i:  0.0,
+                d:  0.0,
+            }
+        );
+        // Going from 40 to 50 in/s in 10 ms is 1000 in/s²
+        let out = side.update(&values, 50.0, 40.0, 0.01);
+        assert!((out.ff - (1.0 + 5.0 + 10.0)).abs() < 1e-9);
+        assert!((out.p - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn side_controller_integral_test() {
+        let mut values = values();
+        values.ki = 2.0;
+        values.maxpwr = 4.0;
+        let mut side = SideController::default();
+        for _ in 0..100 {
+            side.update(&values, 10.0, 0.0, 0.1);
+        }
+        // The integral is limited to maxpwr / ki
+        let out = side.update(&values, 10.0, 0.0, 0.1);
+        assert!((out.i - 4.0).abs() < 1e-9);
+    }
+}
@@ -31,10 +31,47 @@
 //! let controller = Controller::new(ControllerId::Primary);
 //! drivetrain.tank(&controller);
 //! ```
+//!
+//! # Ownership
+//!
+//! The driver, the PID controllers and manual routines can all write to the
+//! same drivetrain. To keep them from fighting, exactly one [`DriveOwner`]
+//! may drive it at a time, and every other writer leaves the motors alone.
+//!
+//! - Motion commands such as `PIDMovement::travel` take ownership for their
+//!   controller and release it once the motion settles. Motions run with
+//!   `MotionOptions::hold` keep it, so the controller holds position until
+//!   another owner takes over.
+//! - Driver methods such as [`tank`](Differential::tank) only drive when
+//!   the driver owns the drivetrain, or when nobody does.
+//! - Ownership is handed over explicitly with [`acquire`](Differential::acquire)
+//!   and given up with [`release`](Differential::release).
+//!
+//! ```ignore
+//! // At the start of driver control, take the drivetrain back from autonomous
+//! drivetrain.acquire(DriveOwner::Driver);
+//! ```
+//!
+//! # Desaturation
+//!
+//! Mixing a forward and a turning command can ask one side for more than
+//! the motors can give. Clamping each side on its own throws away part of
+//! the difference between them, so a robot turning while driving at full
+//! power turns less than it was told to. Arcade drive and the PID loops
+//! instead pass both sides through the drivetrain's [`Desaturation`], which
+//! brings them back within the limit while keeping their ratio.
+//!
+//! ```ignore
+//! // Keep the turn and slow down instead when the sides saturate
+//! drivetrain.set_desaturation(Desaturation::FavorAngular);
+//! ```
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
-use log::warn;
+use log::{info, warn};
 use vexide::{
     controller::ControllerState,
     math::Angle,
@@ -49,7 +86,8 @@ use vexide::{
 /// as well as utility functions for autonomous operation.
 ///
 /// The motors are stored in reference-counted cells to allow shared ownership
-/// with other systems (e.g., PID controllers, odometry).
+/// with other systems (e.g., PID controllers, odometry). Only the current
+/// [`DriveOwner`] writes to them; see the [module docs](self#ownership).
 ///
 /// # Motor Configuration
 ///
@@ -82,6 +120,82 @@ pub struct Differential {
     /// relative to each other (typically opposite to the left side for
     /// forward movement).
     pub right: Rc<RefCell<dyn AsMut<[Motor]>>>,
+
+    /// Who is currently allowed to drive the motors.
+    ///
+    /// Shared between clones so every controller sees the same owner.
+    owner: Rc<Cell<DriveOwner>>,
+
+    /// How mixed outputs are brought back within the voltage limit.
+    ///
+    /// Shared between clones so the PID loops use the same mode.
+    desaturation: Rc<Cell<Desaturation>>,
+}
+
+/// Who is allowed to write to a [`Differential`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DriveOwner {
+    /// Nobody owns the drivetrain. Driver input may claim it.
+    #[default]
+    Idle,
+    /// Driver control through the controller sticks.
+    Driver,
+    /// The [`PIDMovement`](crate::motion::pid::pid::PIDMovement) loop.
+    Pid,
+    /// The [`ArcPIDMovement`](crate::motion::pid::arcpid::ArcPIDMovement) loop.
+    ArcPid,
+    /// The [`VelocityDrive`](crate::motion::velocity::VelocityDrive) loop.
+    Velocity,
+    /// A manual routine driving the motors directly, such as
+    /// characterization or autotuning.
+    Manual,
+}
+
+/// How a pair of left and right outputs is brought back within a limit.
+///
+/// See the [module docs](self#desaturation).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Desaturation {
+    /// Scales both sides by the same factor, so the ratio between them and
+    /// therefore the curvature of the path is kept.
+    #[default]
+    Proportional,
+    /// Keeps as much of the turning component as possible and gives up
+    /// forward speed first. Turns are tighter under saturation, but the
+    /// robot slows down more.
+    FavorAngular,
+}
+
+impl Desaturation {
+    /// Brings `left` and `right` within `[-limit, limit]`.
+    ///
+    /// Outputs already within the limit are returned unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let (left, right) = Desaturation::Proportional.apply(18.0, 6.0, 12.0);
+    /// assert_eq!((left, right), (12.0, 4.0));
+    /// ```
+    pub fn apply(self, left: f64, right: f64, limit: f64) -> (f64, f64) {
+        let limit = limit.abs();
+        let peak = left.abs().max(right.abs());
+        if peak <= limit {
+            return (left, right);
+        }
+        match self {
+            Desaturation::Proportional => {
+                let scale = limit / peak;
+                (left * scale, right * scale)
+            }
+            Desaturation::FavorAngular => {
+                let angular = ((left - right) / 2.0).clamp(-limit, limit);
+                let room = limit - angular.abs();
+                let linear = ((left + right) / 2.0).clamp(-room, room);
+                (linear + angular, linear - angular)
+            }
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -108,8 +222,10 @@ impl Differential {
         right: R,
     ) -> Self {
         Self {
-            left:  Rc::new(RefCell::new(left)),
-            right: Rc::new(RefCell::new(right)),
+            left:         Rc::new(RefCell::new(left)),
+            right:        Rc::new(RefCell::new(right)),
+            owner:        Rc::new(Cell::new(DriveOwner::Idle)),
+            desaturation: Rc::new(Cell::new(Desaturation::default())),
         }
     }
 
@@ -134,16 +250,8 @@ impl Differential {
         let left_voltage = left_power * 12.0;
         let right_voltage = right_power * 12.0;
 
-        if let Ok(mut left_motors) = self.left.try_borrow_mut() {
-            for motor in left_motors.as_mut() {
-                let _ = motor.set_voltage(left_voltage);
-            }
-        }
-
-        if let Ok(mut right_motors) = self.right.try_borrow_mut() {
-            for motor in right_motors.as_mut() {
-                let _ = motor.set_voltage(right_voltage);
-            }
+        if self.claim_driver() {
+            self.write_voltages(left_voltage, right_voltage);
         }
     }
 
@@ -155,6 +263,8 @@ impl Differential {
     /// - The two values are mixed into left/right voltages as:
     ///   - left = (fwd + turn) * 12.0
     ///   - right = (fwd - turn) * 12.0
+    /// - If either side exceeds 12 V, both are brought back within it by the
+    ///   drivetrain's [`Desaturation`].
     /// - If reading the controller state fails, zeroed inputs are used (no movement) and a warning is logged.
     ///
     /// Notes:
@@ -177,19 +287,11 @@ impl Differential {
         let fwd = state.left_stick.y();
         let turn = state.right_stick.x();
 
-        let left_voltage = (fwd + turn) * 12.0;
-        let right_voltage = (fwd - turn) * 12.0;
+        let (left_voltage, right_voltage) =
+            self.desaturate((fwd + turn) * 12.0, (fwd - turn) * 12.0, 12.0);
 
-        if let Ok(mut left_motors) = self.left.try_borrow_mut() {
-            for motor in left_motors.as_mut() {
-                let _ = motor.set_voltage(left_voltage);
-            }
-        }
-
-        if let Ok(mut right_motors) = self.right.try_borrow_mut() {
-            for motor in right_motors.as_mut() {
-                let _ = motor.set_voltage(right_voltage);
-            }
+        if self.claim_driver() {
+            self.write_voltages(left_voltage, right_voltage);
         }
     }
 
@@ -221,16 +323,8 @@ impl Differential {
         let left_voltage = (-state.right_stick.y()) * 12.0;
         let right_voltage = (-state.left_stick.y()) * 12.0;
 
-        if let Ok(mut left_motors) = self.left.try_borrow_mut() {
-            for motor in left_motors.as_mut() {
-                let _ = motor.set_voltage(left_voltage);
-            }
-        }
-
-        if let Ok(mut right_motors) = self.right.try_borrow_mut() {
-            for motor in right_motors.as_mut() {
-                let _ = motor.set_voltage(right_voltage);
-            }
+        if self.claim_driver() {
+            self.write_voltages(left_voltage, right_voltage);
         }
     }
 
@@ -242,6 +336,7 @@ impl Differential {
     /// - Mixed into left/right voltages as:
     ///   - left = (fwd + turn) * 12.0
     ///   - right = (fwd - turn) * 12.0
+    /// - Saturated outputs are handled as in [`arcade`](Differential::arcade).
     /// - This inversion preserves intuitive steering when the robot is driving backwards
     ///   (pushing the right stick right still causes a clockwise turn relative to the driver).
     /// - On controller read error, zeroed inputs are used and a warning is logged.
@@ -265,9 +360,86 @@ impl Differential {
         let fwd = -state.left_stick.y();
         let turn = -state.right_stick.x();
 
-        let left_voltage = (fwd + turn) * 12.0;
-        let right_voltage = (fwd - turn) * 12.0;
+        let (left_voltage, right_voltage) =
+            self.desaturate((fwd + turn) * 12.0, (fwd - turn) * 12.0, 12.0);
+
+        if self.claim_driver() {
+            self.write_voltages(left_voltage, right_voltage);
+        }
+    }
+
+    /// Sets how mixed outputs are brought back within the voltage limit.
+    ///
+    /// Applies to arcade drive and to the PID loops sharing this drivetrain.
+    pub fn set_desaturation(&self, desaturation: Desaturation) {
+        self.desaturation.set(desaturation);
+    }
+
+    /// Returns how mixed outputs are brought back within the voltage limit.
+    pub fn desaturation(&self) -> Desaturation { self.desaturation.get() }
+
+    /// Brings `left` and `right` within `[-limit, limit]` using the
+    /// drivetrain's [`Desaturation`].
+    pub fn desaturate(&self, left: f64, right: f64, limit: f64) -> (f64, f64) {
+        self.desaturation.get().apply(left, right, limit)
+    }
+
+    /// Returns who currently owns the drivetrain.
+    pub fn owner(&self) -> DriveOwner { self.owner.get() }
+
+    /// Returns `true` if `owner` currently owns the drivetrain.
+    pub fn is_owner(&self, owner: DriveOwner) -> bool { self.owner.get() == owner }
+
+    /// Hands the drivetrain over to `owner`, taking it from whoever had it.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // Stop any PID hold and give the sticks control
+    /// drivetrain.acquire(DriveOwner::Driver);
+    /// ```
+    pub fn acquire(&self, owner: DriveOwner) {
+        let previous = self.owner.replace(owner);
+        if previous != owner {
+            info!("Drivetrain Owner: {:?} -> {:?}", previous, owner);
+        }
+    }
 
+    /// Gives up the drivetrain if `owner` currently has it.
+    ///
+    /// The motors are stopped and the drivetrain becomes [`DriveOwner::Idle`].
+    pub fn release(&self, owner: DriveOwner) {
+        if self.is_owner(owner) {
+            self.write_voltages(0.0, 0.0);
+            self.acquire(DriveOwner::Idle);
+        }
+    }
+
+    /// Sets the left and right voltages if `owner` owns the drivetrain.
+    ///
+    /// Returns `false` without writing anything if another owner has it.
+    pub fn set_voltages(&self, owner: DriveOwner, left: f64, right: f64) -> bool {
+        if !self.is_owner(owner) {
+            return false;
+        }
+        self.write_voltages(left, right);
+        true
+    }
+
+    /// Lets driver input drive if the driver owns the drivetrain, claiming
+    /// it if nobody does.
+    fn claim_driver(&self) -> bool {
+        match self.owner.get() {
+            DriveOwner::Driver => true,
+            DriveOwner::Idle => {
+                self.acquire(DriveOwner::Driver);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn write_voltages(&self, left_voltage: f64, right_voltage: f64) {
         if let Ok(mut left_motors) = self.left.try_borrow_mut() {
             for motor in left_motors.as_mut() {
                 let _ = motor.set_voltage(left_voltage);
@@ -313,6 +485,17 @@ impl Differential {
         }
     }
 
+    /// Resets the encoder position of every motor in the drivetrain to zero.
+    pub fn reset_position(&self) {
+        for side in [&self.left, &self.right] {
+            if let Ok(mut motors) = side.try_borrow_mut() {
+                for motor in motors.as_mut() {
+                    let _ = motor.reset_position();
+                }
+            }
+        }
+    }
+
     /// Returns the average encoder position of all motors in the drivetrain.
     ///
     /// This method reads the position from each motor's integrated encoder
@@ -400,6 +583,47 @@ impl Differential {
         left: Rc<RefCell<L>>,
         right: Rc<RefCell<R>>,
     ) -> Self {
-        Self { left, right }
+        Self {
+            left,
+            right,
+            owner: Rc::new(Cell::new(DriveOwner::Idle)),
+            desaturation: Rc::new(Cell::new(Desaturation::default())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desaturate_unsaturated_test() {
+        for mode in [Desaturation::Proportional, Desaturation::FavorAngular] {
+            assert_eq!(mode.apply(6.0, -4.0, 12.0), (6.0, -4.0));
+            assert_eq!(mode.apply(12.0, 12.0, 12.0), (12.0, 12.0));
+        }
+    }
+
+    #[test]
+    fn desaturate_proportional_test() {
+        let (left, right) = Desaturation::Proportional.apply(18.0, 6.0, 12.0);
+        assert!((left - 12.0).abs() < 1e-12);
+        assert!((right - 4.0).abs() < 1e-12);
+        // The ratio between the sides is kept
+        let (left, right) = Desaturation::Proportional.apply(-5.0, 20.0, 10.0);
+        assert!((left + 2.5).abs() < 1e-12);
+        assert!((right - 10.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn desaturate_favor_angular_test() {
+        // Linear 12, angular 6: the turn is kept and forward gives way
+        let (left, right) = Desaturation::FavorAngular.apply(18.0, 6.0, 12.0);
+        assert!((left - 12.0).abs() < 1e-12);
+        assert!((right - 0.0).abs() < 1e-12);
+        // A turn larger than the limit is clamped, with no forward left
+        let (left, right) = Desaturation::FavorAngular.apply(10.0, -20.0, 12.0);
+        assert!((left - 12.0).abs() < 1e-12);
+        assert!((right + 12.0).abs() < 1e-12);
     }
 }